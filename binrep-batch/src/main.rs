@@ -3,17 +3,19 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 use anyhow::{Context, Error};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
-use binrep_core::config_resolver::resolve_config;
-use binrep_core::{binrep, file_utils};
-use binrep_core::{binrep::Binrep, slack_hook3};
+use binrep_core::binrep;
+use binrep_core::config::Config;
+use binrep_core::config_resolver::{read_config_file, resolve_config};
+use binrep_core::{binrep::SyncOptions, slack_hook3};
 use glob::glob;
 use serde::Deserialize;
 use serde::Serialize;
 
 use binrep_core::extended_exec::{Line, Type};
+use binrep_core::notify::{WebhookNotifier, WebhookNotifierConfig};
 use binrep_core::progress::InteractiveProgressReporter;
 use binrep_core::slack::{SlackConfig, WebhookConfig};
 use log::debug;
@@ -21,12 +23,24 @@ use slack_hook3::PayloadBuilder;
 
 #[derive(StructOpt)]
 struct Opt {
-    /// Configuration file, if not specified, default to ~/.binrep/config.sane and /etc/binrep/config.sane
+    /// Configuration file, if not specified, default to $BINREP_CONFIG,
+    /// $XDG_CONFIG_HOME/binrep/config.sane, ~/.binrep/config.sane and /etc/binrep/config.sane
     #[structopt(short = "c", long = "config", parse(from_os_str))]
     config_file: Option<PathBuf>,
-    /// batch configuration file, if not provided default to  ~/.binrep/batch.sane
-    /// and /etc/binrep/batch.sane
+    /// batch configuration file, if not provided default to $XDG_CONFIG_HOME/binrep/batch.sane,
+    /// ~/.binrep/batch.sane and /etc/binrep/batch.sane
     batch_configuration_file: Option<PathBuf>,
+    /// Write a JSON summary of all operations to this file
+    #[structopt(long = "report", parse(from_os_str))]
+    report_file: Option<PathBuf>,
+    /// Go on with the next operations when one fails, same as `continue_on_error = true` in the
+    /// batch configuration
+    #[structopt(long = "continue-on-error")]
+    continue_on_error: bool,
+    /// Only print which artifacts would be updated, same as `dry_run = true` in the batch
+    /// configuration
+    #[structopt(short = "n", long = "dry-run")]
+    dry_run: bool,
 }
 
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
@@ -38,7 +52,12 @@ pub struct SyncOperation {
     #[serde(rename = "destination")]
     pub destination_dir: String,
     pub exec: Option<String>,
+    /// Kill the exec command if still running after this number of seconds
+    pub exec_timeout_secs: Option<u64>,
+    /// Working directory of the exec command, default to the destination directory
+    pub workdir: Option<String>,
     pub slack: Option<SlackNotifier>,
+    pub webhook: Option<WebhookNotifier>,
 }
 
 #[derive(Debug, Deserialize, PartialEq, Serialize, Clone)]
@@ -74,9 +93,24 @@ impl SlackNotifier {
 struct BatchConfig {
     /// eg. includes=/etc/binrep/batch.d/*.sane
     includes: Option<String>,
+    /// Go on with the next operations when one fails, the batch still fails at the end
+    #[serde(default)]
+    continue_on_error: bool,
+    /// Resolve versions without pulling anything nor running exec commands
+    #[serde(default)]
+    dry_run: bool,
+    /// Restore the previously installed version of an artifact if its exec command fails
+    #[serde(default)]
+    rollback_on_exec_failure: bool,
+    /// Number of replaced versions archived in the destination directories (default to 0)
+    keep_history: Option<usize>,
+    /// Number of operations run concurrently (default to 1: sequentially), the operations
+    /// of a destination directory are always run in order
+    max_parallel: Option<usize>,
     #[serde(rename = "sync")]
     sync_operations: Vec<SyncOperation>,
     slack: Option<SlackNotifier>,
+    webhook: Option<WebhookNotifier>,
 }
 #[tokio::main]
 async fn main() {
@@ -109,8 +143,13 @@ async fn _main(opt: Opt) -> Result<(), Error> {
         enabled: batch_config.slack.map(|s| s.enabled).unwrap_or(false),
     };
 
+    // ---- parse webhook section of binrep config, overridden by the batch config
+    let webhook_configuration: WebhookNotifierConfig = binrep::resolve_config(&opt.config_file)?;
+    let default_webhook_notifier = WebhookNotifier::from(webhook_configuration)
+        .override_with(batch_config.webhook.unwrap_or_default());
+
     // ----- setup binrep
-    let mut binrep = Binrep::<InteractiveProgressReporter>::new(&opt.config_file)?;
+    let config: Config = binrep::resolve_config(&opt.config_file)?;
 
     // ----- SYNC!!
     let operations: Vec<SyncOperation> = batch_config
@@ -119,7 +158,37 @@ async fn _main(opt: Opt) -> Result<(), Error> {
         .chain(get_operation_from_includes(batch_config.includes))
         .collect();
 
-    batch::sync(&mut binrep, operations, default_slack_notifier).await?;
+    let mut report = Vec::new();
+    let options = batch::BatchOptions {
+        continue_on_error: batch_config.continue_on_error || opt.continue_on_error,
+        max_parallel: batch_config.max_parallel.unwrap_or(1),
+        sync_options: SyncOptions {
+            dry_run: batch_config.dry_run || opt.dry_run,
+            rollback_on_exec_failure: batch_config.rollback_on_exec_failure,
+            keep_history: batch_config.keep_history.unwrap_or(0),
+            ..Default::default()
+        },
+    };
+    let result = batch::sync::<InteractiveProgressReporter>(
+        &config,
+        operations,
+        &default_slack_notifier,
+        &default_webhook_notifier,
+        &options,
+        &mut report,
+    )
+    .await;
+    if let Some(report_file) = &opt.report_file {
+        // written even if an operation failed
+        write_report(report_file, &report)?;
+    }
+    result
+}
+
+fn write_report(report_file: &Path, report: &[batch::OperationReport]) -> Result<(), Error> {
+    let json = serde_json::to_string_pretty(report)?;
+    std::fs::write(report_file, json)
+        .with_context(|| format!("Unable to write report {}", report_file.to_string_lossy()))?;
     Ok(())
 }
 
@@ -131,7 +200,7 @@ fn get_operation_from_includes(includes: Option<String>) -> Vec<SyncOperation> {
         .map(|path| path.unwrap())
         .map(|path| {
             debug!("Reading included config file {:?}", path);
-            file_utils::read_sane_from_file::<_, BatchConfig>(&path)
+            read_config_file::<_, BatchConfig>(&path)
                 .with_context(|| format!("Unable to read {}", path.to_string_lossy()))
                 .unwrap()
                 .sync_operations
@@ -142,23 +211,83 @@ fn get_operation_from_includes(includes: Option<String>) -> Vec<SyncOperation> {
 
 mod batch {
     use crate::{execution_commands_to_text, SlackNotifier};
-    use anyhow::Error;
-    use binrep_core::binrep::{parse_version_req, Binrep, SyncStatus};
-    use binrep_core::exec::{exec, ExecutionError};
+    use anyhow::{anyhow, Error};
+    use binrep_core::binrep::{
+        exec_after_sync, parse_version_selector, Binrep, SyncOptions, SyncStatus, VersionSelector,
+    };
+    use binrep_core::config::Config;
+    use binrep_core::exec::{ExecOptions, ExecutionError};
     use binrep_core::extended_exec::Line;
     use binrep_core::metadata::Artifact;
+    use binrep_core::notify::{Notification, WebhookNotifier};
     use binrep_core::progress::ProgressReporter;
-    use binrep_core::semver::VersionReq;
     use binrep_core::slack_hook3::{AttachmentBuilder, PayloadBuilder};
+    use serde::Serialize;
+    use std::collections::VecDeque;
     use std::convert::{TryFrom, TryInto};
+    use std::io::Write;
     use std::path::PathBuf;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    #[derive(Debug, Serialize, PartialEq)]
+    pub enum OperationStatus {
+        Updated,
+        UpToDate,
+        Failed,
+    }
+
+    /// Outcome of a sync operation, as written in the JSON report
+    #[derive(Debug, Serialize)]
+    pub struct OperationReport {
+        pub name: String,
+        pub destination: String,
+        pub status: OperationStatus,
+        /// Resolved version, if the sync succeeded
+        pub version: Option<String>,
+        pub duration_ms: u64,
+        /// Outcome of the exec command, if one was run
+        pub exec: Option<ExecReport>,
+        pub error: Option<String>,
+    }
+
+    #[derive(Debug, Serialize)]
+    pub struct ExecReport {
+        pub success: bool,
+        /// Exit code of the command, none if killed by a signal or if it could not be run
+        pub exit_code: Option<i32>,
+        pub error: Option<String>,
+    }
+
+    impl ExecReport {
+        fn new(exec_result: &Result<Option<Vec<Line>>, Error>) -> Option<Self> {
+            match exec_result {
+                Ok(None) => None,
+                Ok(Some(_)) => Some(ExecReport {
+                    success: true,
+                    exit_code: Some(0),
+                    error: None,
+                }),
+                Err(e) => Some(ExecReport {
+                    success: false,
+                    exit_code: e
+                        .downcast_ref::<ExecutionError>()
+                        .and_then(|e| e.exit_status.code()),
+                    error: Some(e.to_string()),
+                }),
+            }
+        }
+    }
 
     struct SyncOperation {
         artifact_name: String,
-        version_req: VersionReq,
+        version_req: VersionSelector,
         destination_dir: PathBuf,
         command: Option<String>,
+        command_options: ExecOptions,
         slack: Option<SlackNotifier>,
+        webhook: Option<WebhookNotifier>,
     }
 
     impl TryFrom<super::SyncOperation> for SyncOperation {
@@ -167,18 +296,86 @@ mod batch {
         fn try_from(value: super::SyncOperation) -> Result<Self, Self::Error> {
             Ok(SyncOperation {
                 artifact_name: value.artifact_name,
-                version_req: parse_version_req(&value.version_req)?,
+                version_req: parse_version_selector(&value.version_req)?,
                 destination_dir: PathBuf::from(value.destination_dir),
                 command: value.exec,
+                command_options: ExecOptions {
+                    timeout: value.exec_timeout_secs.map(Duration::from_secs),
+                    working_dir: value.workdir.map(PathBuf::from),
+                },
                 slack: value.slack,
+                webhook: value.webhook,
             })
         }
     }
 
+    /// Options of [`sync`]
+    #[derive(Debug, Clone, Default)]
+    pub struct BatchOptions {
+        /// Go on with the next operations when one fails, the batch still fails at the end
+        pub continue_on_error: bool,
+        /// Number of operations run concurrently, 0 or 1 runs them sequentially
+        pub max_parallel: usize,
+        pub sync_options: SyncOptions,
+    }
+
+    /// Lines printed by an operation. Buffered until the operation completes when several
+    /// operations run concurrently, so that their outputs do not interleave.
+    struct Output {
+        buffered: bool,
+        /// stderr or stdout line
+        lines: Vec<(bool, String)>,
+    }
+
+    impl Output {
+        fn new(buffered: bool) -> Self {
+            Self {
+                buffered,
+                lines: Vec::new(),
+            }
+        }
+
+        fn out(&mut self, line: String) {
+            self.print(false, line)
+        }
+
+        fn err(&mut self, line: String) {
+            self.print(true, line)
+        }
+
+        fn print(&mut self, stderr: bool, line: String) {
+            if self.buffered {
+                self.lines.push((stderr, line));
+            } else if stderr {
+                eprintln!("{}", line);
+            } else {
+                println!("{}", line);
+            }
+        }
+
+        fn flush(&mut self) {
+            let stdout = std::io::stdout();
+            let stderr = std::io::stderr();
+            let (mut stdout, mut stderr) = (stdout.lock(), stderr.lock());
+            for (is_stderr, line) in self.lines.drain(..) {
+                let _ = if is_stderr {
+                    writeln!(stderr, "{}", line)
+                } else {
+                    writeln!(stdout, "{}", line)
+                };
+            }
+        }
+    }
+
+    type Outcome = (OperationReport, Result<(), Error>);
+
     pub async fn sync<T>(
-        binrep: &mut Binrep<T>,
+        config: &Config,
         operations: Vec<super::SyncOperation>,
-        default_slack_notifier: SlackNotifier,
+        default_slack_notifier: &SlackNotifier,
+        default_webhook_notifier: &WebhookNotifier,
+        options: &BatchOptions,
+        report: &mut Vec<OperationReport>,
     ) -> Result<(), Error>
     where
         T: ProgressReporter + 'static,
@@ -192,57 +389,316 @@ mod batch {
                 Ok(acc)
             },
         )?;
-        for operation in operations {
-            println!(
-                "Syncing {} to {}",
-                operation.artifact_name,
-                operation.destination_dir.to_string_lossy()
-            );
-            let result = binrep
-                .sync(
-                    &operation.artifact_name,
-                    &operation.version_req,
-                    &operation.destination_dir,
+        let outcomes = if options.max_parallel > 1 {
+            sync_concurrently::<T>(
+                config,
+                operations,
+                default_slack_notifier,
+                default_webhook_notifier,
+                options,
+            )?
+        } else {
+            let mut binrep = Binrep::<T>::from_config(config.clone())?;
+            let mut outcomes = Vec::new();
+            for operation in &operations {
+                let outcome = sync_operation(
+                    &mut binrep,
+                    operation,
+                    default_slack_notifier,
+                    default_webhook_notifier,
+                    options,
+                    &mut Output::new(false),
                 )
-                .await?;
-            let slack_notifier = if let Some(op_slack_notifier) = &operation.slack {
-                op_slack_notifier
-                    .clone()
-                    .merge_with_default(&default_slack_notifier)
-            } else {
-                default_slack_notifier.clone()
+                .await;
+                let failed = outcome.1.is_err();
+                outcomes.push(outcome);
+                if failed && !options.continue_on_error {
+                    break;
+                }
+            }
+            outcomes
+        };
+        let mut results: Vec<Result<(), Error>> = Vec::new();
+        for (operation_report, result) in outcomes {
+            let result = match result {
+                Err(e) if !options.continue_on_error => Err(e),
+                Err(e) => Err(e.context(format!("Unable to sync {}", operation_report.name))),
+                Ok(()) => Ok(()),
             };
-            match &result.status {
-                SyncStatus::Updated => {
-                    println!("Updated: {}", result.artifact);
-                    match handle_exec_result(
-                        exec(
-                            &result.artifact,
-                            &operation.destination_dir,
-                            &operation.command,
-                        ),
-                        &slack_notifier,
-                        &operation.artifact_name,
-                        &result.artifact,
-                    )
-                    .await
-                    {
-                        Ok(sent) => {
-                            if sent {
-                                println!("Slack notification sent!");
-                            }
+            report.push(operation_report);
+            results.push(result);
+        }
+        if !options.continue_on_error {
+            if let Some(position) = results.iter().position(Result::is_err) {
+                return results.swap_remove(position);
+            }
+        }
+        summarize(&results)
+    }
+
+    /// Run the operations on `max_parallel` threads. The operations of a destination directory
+    /// are run in order by the same thread, two operations never write the same directory at
+    /// the same time.
+    fn sync_concurrently<T>(
+        config: &Config,
+        operations: Vec<SyncOperation>,
+        default_slack_notifier: &SlackNotifier,
+        default_webhook_notifier: &WebhookNotifier,
+        options: &BatchOptions,
+    ) -> Result<Vec<Outcome>, Error>
+    where
+        T: ProgressReporter + 'static,
+        T::Output: Send + Sync + 'static,
+    {
+        let mut groups: Vec<Vec<(usize, SyncOperation)>> = Vec::new();
+        for (index, operation) in operations.into_iter().enumerate() {
+            match groups
+                .iter_mut()
+                .find(|group| group[0].1.destination_dir == operation.destination_dir)
+            {
+                Some(group) => group.push((index, operation)),
+                None => groups.push(vec![(index, operation)]),
+            }
+        }
+        let workers = options.max_parallel.min(groups.len());
+        let queue = Mutex::new(groups.into_iter().collect::<VecDeque<_>>());
+        let failed = AtomicBool::new(false);
+        let outcomes = Mutex::new(Vec::new());
+        let worker = || -> Result<(), Error> {
+            // binrep futures are not Send: each thread runs its own runtime and repository
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+            let mut binrep = Binrep::<T>::from_config(config.clone())?;
+            loop {
+                let group = match queue.lock().unwrap().pop_front() {
+                    Some(group) => group,
+                    None => return Ok(()),
+                };
+                for (index, operation) in group {
+                    if failed.load(Ordering::SeqCst) && !options.continue_on_error {
+                        return Ok(());
+                    }
+                    let mut output = Output::new(true);
+                    let outcome = runtime.block_on(sync_operation(
+                        &mut binrep,
+                        &operation,
+                        default_slack_notifier,
+                        default_webhook_notifier,
+                        options,
+                        &mut output,
+                    ));
+                    output.flush();
+                    if outcome.1.is_err() {
+                        failed.store(true, Ordering::SeqCst);
+                    }
+                    outcomes.lock().unwrap().push((index, outcome));
+                }
+            }
+        };
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers).map(|_| scope.spawn(worker)).collect();
+            handles
+                .into_iter()
+                .try_for_each(|handle| handle.join().expect("sync thread panicked"))
+        })?;
+        let mut outcomes = outcomes.into_inner().unwrap();
+        // in the order of the configuration
+        outcomes.sort_by_key(|(index, _)| *index);
+        Ok(outcomes.into_iter().map(|(_, outcome)| outcome).collect())
+    }
+
+    async fn sync_operation<T>(
+        binrep: &mut Binrep<T>,
+        operation: &SyncOperation,
+        default_slack_notifier: &SlackNotifier,
+        default_webhook_notifier: &WebhookNotifier,
+        options: &BatchOptions,
+        output: &mut Output,
+    ) -> Outcome
+    where
+        T: ProgressReporter + 'static,
+        T::Output: Send + Sync + 'static,
+    {
+        output.out(format!(
+            "Syncing {} to {}",
+            operation.artifact_name,
+            operation.destination_dir.to_string_lossy()
+        ));
+        let started = Instant::now();
+        let mut operation_report = OperationReport {
+            name: operation.artifact_name.clone(),
+            destination: operation.destination_dir.to_string_lossy().into_owned(),
+            status: OperationStatus::Failed,
+            version: None,
+            duration_ms: 0,
+            exec: None,
+            error: None,
+        };
+        let slack_notifier = if let Some(op_slack_notifier) = &operation.slack {
+            op_slack_notifier
+                .clone()
+                .merge_with_default(default_slack_notifier)
+        } else {
+            default_slack_notifier.clone()
+        };
+        let result = match binrep
+            .sync_with_options(
+                &operation.artifact_name,
+                &operation.version_req,
+                &operation.destination_dir,
+                &options.sync_options,
+            )
+            .await
+            .map_err(Error::from)
+        {
+            Ok(result) => result,
+            Err(e) => {
+                operation_report.duration_ms = started.elapsed().as_millis() as u64;
+                operation_report.error = Some(e.to_string());
+                match handle_sync_error(&slack_notifier, &operation.artifact_name, &e).await {
+                    Ok(sent) => {
+                        if sent {
+                            output.out("Slack notification sent!".to_string());
                         }
-                        Err(e) => {
-                            eprintln!("Cannot send slack notification: {}", e);
+                    }
+                    Err(e) => {
+                        output.err(format!("Cannot send slack notification: {}", e));
+                    }
+                }
+                if options.continue_on_error {
+                    output.err(format!("Cannot sync {}: {}", operation.artifact_name, e));
+                }
+                return (operation_report, Err(e));
+            }
+        };
+        operation_report.version = Some(result.artifact.version.to_string());
+        let webhook_notifier = match &operation.webhook {
+            Some(op_webhook_notifier) => {
+                default_webhook_notifier.override_with(op_webhook_notifier.clone())
+            }
+            None => default_webhook_notifier.clone(),
+        };
+        match &result.status {
+            SyncStatus::Updated if result.dry_run => {
+                output.out(format!("Would update: {}", result.artifact));
+                operation_report.status = OperationStatus::Updated;
+            }
+            SyncStatus::Updated => {
+                output.out(format!("Updated: {}", result.artifact));
+                operation_report.status = OperationStatus::Updated;
+                let exec_result = exec_after_sync(
+                    &result,
+                    &operation.destination_dir,
+                    &operation.command,
+                    &operation.command_options,
+                );
+                operation_report.exec = ExecReport::new(&exec_result);
+                let notification = Notification {
+                    artifact: &operation.artifact_name,
+                    version: &result.artifact.version.to_string(),
+                    hostname: &current_hostname(),
+                    status: if exec_result.is_ok() {
+                        "updated"
+                    } else {
+                        "failed"
+                    },
+                };
+                match webhook_notifier.send(&notification).await {
+                    Ok(sent) => {
+                        if sent {
+                            output.out("Webhook notification sent!".to_string());
                         }
                     }
+                    Err(e) => {
+                        output.err(format!("Cannot send webhook notification: {}", e));
+                    }
+                }
+                if let Err(e) = &exec_result {
+                    output.err(format!("Execution error: {}", e));
                 }
-                SyncStatus::UpToDate => {
-                    println!("Already the latest version {}", result.artifact.version);
+                match handle_exec_result(
+                    exec_result,
+                    &slack_notifier,
+                    &operation.artifact_name,
+                    &result.artifact,
+                )
+                .await
+                {
+                    Ok(sent) => {
+                        if sent {
+                            output.out("Slack notification sent!".to_string());
+                        }
+                    }
+                    Err(e) => {
+                        output.err(format!("Cannot send slack notification: {}", e));
+                    }
                 }
             }
+            SyncStatus::UpToDate => {
+                output.out(format!(
+                    "Already the latest version {}",
+                    result.artifact.version
+                ));
+                operation_report.status = OperationStatus::UpToDate;
+            }
+        }
+        operation_report.duration_ms = started.elapsed().as_millis() as u64;
+        (operation_report, Ok(()))
+    }
+
+    /// Print the failed operations, fail if there is any
+    pub fn summarize(results: &[Result<(), Error>]) -> Result<(), Error> {
+        let failures: Vec<&Error> = results.iter().filter_map(|r| r.as_ref().err()).collect();
+        if failures.is_empty() {
+            return Ok(());
         }
-        Ok(())
+        eprintln!(
+            "{} of {} sync operations failed:",
+            failures.len(),
+            results.len()
+        );
+        for e in &failures {
+            eprintln!("  - {:#}", e);
+        }
+        Err(anyhow!(
+            "{} of {} sync operations failed",
+            failures.len(),
+            results.len()
+        ))
+    }
+
+    fn current_hostname() -> String {
+        hostname::get()
+            .ok()
+            .map(|hostname| hostname.to_string_lossy().into_owned())
+            .unwrap_or("#unknown".into())
+    }
+
+    async fn handle_sync_error(
+        slack_notifier: &SlackNotifier,
+        artifact_name: &str,
+        error: &Error,
+    ) -> Result<bool, anyhow::Error> {
+        let hostname = current_hostname();
+        slack_notifier
+            .send(|| {
+                let error_text = format!("```\n{}\n```", error);
+                Ok(PayloadBuilder::new()
+                    .text(format!(
+                        "Cannot sync *{}* on *{}*.",
+                        artifact_name, hostname
+                    ))
+                    .attachments(
+                        AttachmentBuilder::new(error_text.clone())
+                            .text(error_text)
+                            .color("danger")
+                            .build()
+                            .into_iter()
+                            .collect(),
+                    ))
+            })
+            .await
     }
 
     async fn handle_exec_result(
@@ -251,10 +707,7 @@ mod batch {
         artifact_name: &str,
         artifact: &Artifact,
     ) -> Result<bool, anyhow::Error> {
-        let hostname = hostname::get()
-            .ok()
-            .map(|hostname| hostname.to_string_lossy().into_owned())
-            .unwrap_or("#unknown".into());
+        let hostname = current_hostname();
         match exec_result {
             Ok(output_lines) => {
                 slack_notifier
@@ -281,7 +734,6 @@ mod batch {
                     .await
             }
             Err(e) => {
-                eprintln!("Execution error: {}", e);
                 slack_notifier
                     .send(|| {
                         let updated_text = format!(
@@ -366,11 +818,23 @@ mod test {
             destination="/srv/www/binrep-bootstrap"
             exec="echo hello"
             slack={ enabled=true }
+            webhook={ url="https://bot.example.com/hooks/binrep" }
         "#;
         sane::from_str::<BatchConfig>(c).unwrap();
         // test empty config
 
         sane::from_str::<BatchConfig>("sync=[]").unwrap();
+        assert!(
+            sane::from_str::<BatchConfig>("continue_on_error=true\nsync=[]")
+                .unwrap()
+                .continue_on_error
+        );
+        assert_eq!(
+            Some(4),
+            sane::from_str::<BatchConfig>("max_parallel=4\nsync=[]")
+                .unwrap()
+                .max_parallel
+        );
 
         sane::from_str::<BatchConfig>("includes=\"/etc/batch.d/*.sync\"\nsync=[]").unwrap();
 
@@ -397,11 +861,21 @@ mod test {
                 artifact_name: "coucou".to_string(),
                 version_req: "latest".to_string(),
                 destination_dir: "/tmp/abcde".to_string(),
-                exec: None,
+                // expanded by the shell, not when the configuration is read
+                exec: Some("echo ${BINREP_ARTIFACT_VERSION} > ${BINREP_TEST_UNSET}".to_string()),
+                exec_timeout_secs: None,
+                workdir: None,
                 slack: None,
+                webhook: None,
             }],
             includes: None,
             slack: None,
+            webhook: None,
+            continue_on_error: false,
+            dry_run: false,
+            rollback_on_exec_failure: false,
+            keep_history: None,
+            max_parallel: None,
         };
         file_utils::write_sane_to_file(&file1, &operations1).unwrap();
 
@@ -413,18 +887,30 @@ mod test {
                     version_req: "1.3.0".to_string(),
                     destination_dir: "/tmp/abcdef".to_string(),
                     exec: None,
+                    exec_timeout_secs: None,
+                    workdir: None,
                     slack: None,
+                    webhook: None,
                 },
                 SyncOperation {
                     artifact_name: "coucou2".to_string(),
                     version_req: "1.0.3".to_string(),
                     destination_dir: "/tmp/abcdsdsdef".to_string(),
                     exec: None,
+                    exec_timeout_secs: None,
+                    workdir: None,
                     slack: None,
+                    webhook: None,
                 },
             ],
             includes: None,
             slack: None,
+            webhook: None,
+            continue_on_error: false,
+            dry_run: false,
+            rollback_on_exec_failure: false,
+            keep_history: None,
+            max_parallel: None,
         };
         file_utils::write_sane_to_file(&file2, &operations2).unwrap();
 
@@ -440,4 +926,40 @@ mod test {
             )))
         );
     }
+
+    #[test]
+    fn test_summarize() {
+        use crate::batch::summarize;
+        use anyhow::anyhow;
+        assert!(summarize(&[Ok(()), Ok(())]).is_ok());
+        let e = summarize(&[Ok(()), Err(anyhow!("boom")), Err(anyhow!("bang"))]).unwrap_err();
+        assert_eq!("2 of 3 sync operations failed", e.to_string());
+    }
+
+    #[test]
+    fn test_report() {
+        use crate::batch::{ExecReport, OperationReport, OperationStatus};
+        let report = vec![OperationReport {
+            name: "binrep".to_string(),
+            destination: "/srv/dist/binrep/bin".to_string(),
+            status: OperationStatus::Updated,
+            version: Some("1.2.3".to_string()),
+            duration_ms: 42,
+            exec: Some(ExecReport {
+                success: false,
+                exit_code: Some(2),
+                error: Some("Command false returned with status 2".to_string()),
+            }),
+            error: None,
+        }];
+        let temp_dir = tempfile::tempdir().unwrap();
+        let report_file = file_utils::path_concat2(&temp_dir, "report.json");
+        crate::write_report(&report_file, &report).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&report_file).unwrap()).unwrap();
+        assert_eq!("Updated", json[0]["status"]);
+        assert_eq!("1.2.3", json[0]["version"]);
+        assert_eq!(2, json[0]["exec"]["exit_code"]);
+    }
 }
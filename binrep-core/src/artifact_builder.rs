@@ -0,0 +1,95 @@
+//! Build artifacts from in memory content
+use crate::binrep::Binrep;
+use crate::metadata::{Artifact, Labels};
+use crate::progress::ProgressReporter;
+use crate::repository::{FileContent, PushOptions, PushedFile};
+use anyhow::Error;
+use semver::Version;
+
+/// Artifact whose files are held in memory, eg. generated by a service.
+///
+/// ```no_run
+/// # async fn doc(binrep: &mut binrep_core::binrep::Binrep<binrep_core::progress::NOOPProgress>) -> anyhow::Result<()> {
+/// use binrep_core::artifact_builder::ArtifactBuilder;
+/// use binrep_core::semver::Version;
+///
+/// let artifact = ArtifactBuilder::new("generated-config", Version::new(1, 0, 0))
+///     .file("app.conf", "listen = 8080\n", Some(0o644))
+///     .push(binrep)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ArtifactBuilder {
+    name: String,
+    version: Version,
+    files: Vec<InMemoryFile>,
+    labels: Labels,
+}
+
+struct InMemoryFile {
+    name: String,
+    data: Vec<u8>,
+    unix_mode: Option<u32>,
+}
+
+impl ArtifactBuilder {
+    pub fn new<S: Into<String>>(name: S, version: Version) -> Self {
+        Self {
+            name: name.into(),
+            version,
+            files: Vec::new(),
+            labels: Labels::new(),
+        }
+    }
+
+    /// Add a file, `name` is its relative path in the artifact (eg. `conf/app.conf`)
+    pub fn file<S: Into<String>, D: Into<Vec<u8>>>(
+        mut self,
+        name: S,
+        data: D,
+        unix_mode: Option<u32>,
+    ) -> Self {
+        self.files.push(InMemoryFile {
+            name: name.into(),
+            data: data.into(),
+            unix_mode,
+        });
+        self
+    }
+
+    /// Add a label, `key` must only contain alphanumeric characters and `-_.`
+    pub fn label<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.labels.insert(key.into(), value.into());
+        self
+    }
+
+    /// Compute checksums, sign and push the artifact
+    pub async fn push<T>(&self, binrep: &mut Binrep<T>) -> Result<Artifact, Error>
+    where
+        T: ProgressReporter + 'static,
+        T::Output: Send + Sync + 'static,
+    {
+        let files: Vec<PushedFile> = self
+            .files
+            .iter()
+            .map(|file| PushedFile {
+                name: file.name.clone(),
+                content: FileContent::InMemory(&file.data),
+                unix_mode: file.unix_mode,
+            })
+            .collect();
+        binrep
+            .repository_mut()
+            .push_files(
+                &self.name,
+                &self.version,
+                &files,
+                &PushOptions {
+                    labels: self.labels.clone(),
+                    ..Default::default()
+                },
+            )
+            .await
+    }
+}
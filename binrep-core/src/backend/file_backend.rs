@@ -1,14 +1,18 @@
 use crate::backend::{Backend, BackendError, ProgressReporter};
 use crate::file_utils;
+use crate::progress::{Progress, SharedProgress};
 use anyhow::Error;
+use chrono::{DateTime, Utc};
+use ring::digest;
 use std::fs::File;
 use std::io::Write;
 use std::io::{ErrorKind, Read};
 use std::marker::PhantomData;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub struct FileBackend<T: ProgressReporter> {
     root: PathBuf,
+    max_metadata_size: u64,
     _progress_reporter: PhantomData<T>,
 }
 
@@ -16,10 +20,17 @@ impl<T: ProgressReporter> FileBackend<T> {
     pub fn new(root: &str) -> Self {
         FileBackend {
             root: PathBuf::from(root),
+            max_metadata_size: u64::MAX,
             _progress_reporter: PhantomData,
         }
     }
 
+    /// Fail reading text files bigger than `max_metadata_size` bytes
+    pub fn with_max_metadata_size(mut self, max_metadata_size: u64) -> Self {
+        self.max_metadata_size = max_metadata_size;
+        self
+    }
+
     fn mkdirs(&self, file_path: &PathBuf) -> Result<(), Error> {
         // check dir existence, create if is does not exists, throw an error
         // if the dir is not a dir ;)
@@ -45,38 +56,201 @@ impl From<std::io::Error> for BackendError {
 }
 #[async_trait::async_trait(?Send)]
 impl<T: ProgressReporter> Backend<T> for FileBackend<T> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(path = path))
+    )]
     async fn read_file(&mut self, path: &str) -> Result<String, BackendError> {
         let file_path = get_path(self.root.clone(), path);
         let mut ret = String::new();
-        File::open(file_path)?.read_to_string(&mut ret)?;
+        // read one more byte to detect oversized files
+        File::open(file_path)?
+            .take(self.max_metadata_size.saturating_add(1))
+            .read_to_string(&mut ret)?;
+        if ret.len() as u64 > self.max_metadata_size {
+            Err(BackendError::MetadataTooLarge {
+                path: path.to_string(),
+                max_size: self.max_metadata_size,
+            })?;
+        }
         Ok(ret)
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(path = path))
+    )]
     async fn create_file(&mut self, path: &str, data: String) -> Result<(), BackendError> {
         let file_path = get_path(self.root.clone(), path);
         self.mkdirs(&file_path)?;
-        let mut file = File::create(file_path)?;
-        file.write_all(data.as_bytes())?;
+        write_atomically(&file_path, |file| file.write_all(data.as_bytes()))?;
         Ok(())
     }
 
-    async fn push_file(&mut self, local: PathBuf, remote: &str) -> Result<(), BackendError> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(remote = remote))
+    )]
+    async fn push_file(&self, local: PathBuf, remote: &str) -> Result<(), BackendError> {
         let remote_file_path = get_path(self.root.clone(), remote);
         self.mkdirs(&remote_file_path)?;
         std::fs::copy(local, remote_file_path)?;
         Ok(())
     }
 
-    async fn pull_file(&mut self, remote: &str, local: PathBuf) -> Result<(), BackendError> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(remote = remote))
+    )]
+    async fn push_bytes(&self, data: Vec<u8>, remote: &str) -> Result<(), BackendError> {
+        let remote_file_path = get_path(self.root.clone(), remote);
+        self.mkdirs(&remote_file_path)?;
+        std::fs::write(remote_file_path, data)?;
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(path = path))
+    )]
+    async fn head_file(&mut self, path: &str) -> Result<bool, BackendError> {
+        match std::fs::metadata(get_path(self.root.clone(), path)) {
+            Ok(metadata) => Ok(metadata.is_file()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e)?,
+        }
+    }
+
+    async fn last_modified(&mut self, path: &str) -> Result<Option<DateTime<Utc>>, BackendError> {
+        match std::fs::metadata(get_path(self.root.clone(), path)) {
+            Ok(metadata) => Ok(Some(metadata.modified()?.into())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(BackendError::ResourceNotFound)
+            }
+            Err(e) => Err(e)?,
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(path = path))
+    )]
+    async fn delete_file(&mut self, path: &str) -> Result<(), BackendError> {
+        let file_path = get_path(self.root.clone(), path);
+        std::fs::remove_file(&file_path)?;
+        // remove the directories once empty (eg. the directory of a version)
+        let mut dir = file_path.parent();
+        while let Some(empty_dir) = dir.filter(|dir| *dir != self.root) {
+            if std::fs::remove_dir(empty_dir).is_err() {
+                break;
+            }
+            dir = empty_dir.parent();
+        }
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(prefix = prefix))
+    )]
+    async fn list_files(&mut self, prefix: &str) -> Result<Vec<String>, BackendError> {
+        let prefix = prefix.trim_start_matches('/');
+        // only walk the directory holding the prefix
+        let dir = prefix.rfind('/').map(|i| &prefix[..i]).unwrap_or("");
+        let mut files = Vec::new();
+        match list_dir(&get_path(self.root.clone(), dir), dir, &mut files) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => Err(e)?,
+        }
+        files.retain(|file| file.starts_with(prefix));
+        Ok(files)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(remote = remote))
+    )]
+    async fn pull_file(&self, remote: &str, local: PathBuf) -> Result<(), BackendError> {
         let remote_file_path = get_path(self.root.clone(), remote);
         std::fs::copy(remote_file_path, local)?;
         Ok(())
     }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(remote = remote))
+    )]
+    async fn pull_file_with_digest(
+        &self,
+        remote: &str,
+        local: PathBuf,
+        algorithm: &'static digest::Algorithm,
+        shared_progress: Option<&SharedProgress>,
+    ) -> Result<digest::Digest, BackendError> {
+        let remote_file_path = get_path(self.root.clone(), remote);
+        let mut remote_file = File::open(&remote_file_path)?;
+        let mut local_file = File::create(&local)?;
+        let mut hash_context = digest::Context::new(algorithm);
+        let mut shared_progress = shared_progress.cloned();
+        let mut buf: Vec<u8> = vec![0; 64 * 1024];
+        loop {
+            let bytes_read = remote_file.read(buf.as_mut_slice())?;
+            if bytes_read == 0 {
+                break;
+            }
+            hash_context.update(&buf[0..bytes_read]);
+            local_file.write_all(&buf[0..bytes_read])?;
+            if let Some(progress) = &mut shared_progress {
+                progress.inc(bytes_read);
+            }
+        }
+        // like std::fs::copy
+        std::fs::set_permissions(&local, remote_file.metadata()?.permissions())?;
+        Ok(hash_context.finish())
+    }
+}
+
+/// Write `path` through a temporary file of the same directory renamed into place: readers
+/// (eg. a sync reading `versions.sane` during a push) never see a partially written file, and
+/// concurrent writers do not share the temporary file
+fn write_atomically<F>(path: &Path, write: F) -> Result<(), std::io::Error>
+where
+    F: FnOnce(&mut File) -> Result<(), std::io::Error>,
+{
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut tmp_file = tempfile::NamedTempFile::new_in(dir)?;
+    write(tmp_file.as_file_mut())?;
+    // the temporary file is removed on drop if the move fails
+    let tmp_path = tmp_file.into_temp_path();
+    file_utils::mv(&tmp_path, path)
+}
+
+/// Add the files of `dir` and its sub directories to `files`, as `/` separated paths starting
+/// with `path`, the path of `dir` relative to the root
+fn list_dir(dir: &Path, path: &str, files: &mut Vec<String>) -> Result<(), std::io::Error> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let entry_path = if path.is_empty() {
+            name
+        } else {
+            format!("{}/{}", path, name)
+        };
+        if entry.path().is_dir() {
+            list_dir(&entry.path(), &entry_path, files)?;
+        } else {
+            files.push(entry_path);
+        }
+    }
+    Ok(())
 }
 
+/// Resolve `path` under `root`, `..` components are dropped so that crafted paths (eg. file
+/// names read from untrusted metadata) cannot escape the root
 fn get_path(root: PathBuf, path: &str) -> PathBuf {
     path.split("/")
-        .filter(|element| element.len() > 0)
+        .filter(|element| element.len() > 0 && *element != "." && *element != "..")
         .fold(root, |mut path, path_element| {
             path.push(path_element);
             path
@@ -86,13 +260,25 @@ fn get_path(root: PathBuf, path: &str) -> PathBuf {
 #[cfg(test)]
 mod test {
     use crate::backend::file_backend::FileBackend;
-    use crate::backend::Backend;
-    use crate::progress::NOOPProgress;
+    use crate::backend::{Backend, BackendError};
+    use crate::progress::{NOOPProgress, Progress, SharedProgress};
     use std::fs::File;
-    use std::io::Read;
+    use std::io::{Read, Write};
     use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
     use tempfile::tempdir;
 
+    struct CountingProgress(Arc<AtomicUsize>);
+
+    impl Progress for CountingProgress {
+        fn inc(&mut self, amount: usize) {
+            self.0.fetch_add(amount, Ordering::SeqCst);
+        }
+
+        fn tick(&mut self) {}
+    }
+
     #[test]
     fn test_get_path() {
         assert_eq!(
@@ -111,6 +297,19 @@ mod test {
             PathBuf::from("/var/lib/some/file.txt"),
             super::get_path(PathBuf::from("/var/lib"), "./some/file.txt")
         );
+        // stays within the root
+        assert_eq!(
+            PathBuf::from("/var/lib/foo"),
+            super::get_path(PathBuf::from("/var/lib"), "../foo")
+        );
+        assert_eq!(
+            PathBuf::from("/var/lib/some/etc/passwd"),
+            super::get_path(PathBuf::from("/var/lib"), "some/../../../etc/passwd")
+        );
+        assert_eq!(
+            PathBuf::from("/var/lib/etc/passwd"),
+            super::get_path(PathBuf::from("/var/lib"), "/etc/passwd")
+        );
     }
 
     #[tokio::test]
@@ -152,6 +351,142 @@ mod test {
         bck.pull_file("/foo2/bar/othername.toml", dest_file.clone())
             .await
             .unwrap();
+
+        let pulled_bytes = Arc::new(AtomicUsize::new(0));
+        let digest = bck
+            .pull_file_with_digest(
+                "/foo2/bar/othername.toml",
+                dest_file.clone(),
+                &ring::digest::SHA256,
+                Some(&SharedProgress::new(CountingProgress(pulled_bytes.clone()))),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::metadata("./Cargo.toml").unwrap().len() as usize,
+            pulled_bytes.load(Ordering::SeqCst)
+        );
+        assert_eq!(
+            crate::crypto::digest_file("./Cargo.toml", &ring::digest::SHA256, 4096)
+                .unwrap()
+                .as_ref(),
+            digest.as_ref()
+        );
+        assert_file_equals("./Cargo.toml", std::fs::read_to_string(&dest_file).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_max_metadata_size() {
+        let root = tempdir().unwrap();
+        let mut bck: FileBackend<NOOPProgress> =
+            super::FileBackend::new(&root.path().to_string_lossy()).with_max_metadata_size(10);
+        bck.create_file("small.sane", "0123456789".to_string())
+            .await
+            .unwrap();
+        bck.create_file("big.sane", "0123456789A".to_string())
+            .await
+            .unwrap();
+        assert_eq!("0123456789", bck.read_file("small.sane").await.unwrap());
+        assert!(matches!(
+            bck.read_file("big.sane").await,
+            Err(BackendError::MetadataTooLarge { max_size: 10, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_delete_file() {
+        let root = tempdir().unwrap();
+        let mut bck: FileBackend<NOOPProgress> =
+            super::FileBackend::new(&root.path().to_string_lossy());
+        bck.create_file("foo/1.0.0/some.txt", "data".to_string())
+            .await
+            .unwrap();
+        bck.create_file("root.txt", "data".to_string())
+            .await
+            .unwrap();
+        assert!(bck.head_file("foo/1.0.0/some.txt").await.unwrap());
+        assert!(!bck.head_file("foo/1.0.0").await.unwrap());
+        bck.delete_file("foo/1.0.0/some.txt").await.unwrap();
+        assert!(!bck.head_file("foo/1.0.0/some.txt").await.unwrap());
+        bck.delete_file("root.txt").await.unwrap();
+        assert!(matches!(
+            bck.read_file("foo/1.0.0/some.txt").await,
+            Err(BackendError::ResourceNotFound)
+        ));
+        assert!(matches!(
+            bck.delete_file("root.txt").await,
+            Err(BackendError::ResourceNotFound)
+        ));
+        // the emptied directory is removed, not the root
+        assert!(!root.path().join("foo").join("1.0.0").exists());
+        assert!(root.path().is_dir());
+    }
+
+    #[tokio::test]
+    async fn test_list_files() {
+        let root = tempdir().unwrap();
+        let mut bck: FileBackend<NOOPProgress> =
+            super::FileBackend::new(&root.path().join("repo").to_string_lossy());
+        // the root is created on the first write
+        assert!(bck.list_files("").await.unwrap().is_empty());
+        for path in &[
+            "artifacts.sane",
+            "foo/1.0.0/a.txt",
+            "foo/1.0.0/b/c.txt",
+            "foobar.sane",
+        ] {
+            bck.create_file(path, "data".to_string()).await.unwrap();
+        }
+        let list = |mut files: Vec<String>| {
+            files.sort();
+            files
+        };
+        assert_eq!(
+            vec![
+                "artifacts.sane",
+                "foo/1.0.0/a.txt",
+                "foo/1.0.0/b/c.txt",
+                "foobar.sane"
+            ],
+            list(bck.list_files("").await.unwrap())
+        );
+        assert_eq!(
+            vec!["foo/1.0.0/a.txt", "foo/1.0.0/b/c.txt", "foobar.sane"],
+            list(bck.list_files("foo").await.unwrap())
+        );
+        assert_eq!(
+            vec!["foo/1.0.0/a.txt", "foo/1.0.0/b/c.txt"],
+            list(bck.list_files("/foo/").await.unwrap())
+        );
+        assert_eq!(
+            vec!["foo/1.0.0/b/c.txt"],
+            list(bck.list_files("foo/1.0.0/b").await.unwrap())
+        );
+        assert!(bck.list_files("bar/").await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn write_atomically() {
+        let root = tempdir().unwrap();
+        let path = root.path().join("versions.sane");
+        std::fs::write(&path, "old content").unwrap();
+        super::write_atomically(&path, |file| {
+            file.write_all(b"new ")?;
+            // a reader during a slow write still sees the previous content
+            assert_eq!("old content", std::fs::read_to_string(&path).unwrap());
+            file.write_all(b"content")
+        })
+        .unwrap();
+        assert_eq!("new content", std::fs::read_to_string(&path).unwrap());
+
+        // a failed write leaves the file and the directory untouched
+        let err = super::write_atomically(&path, |file| {
+            file.write_all(b"partial")?;
+            Err(std::io::Error::other("interrupted"))
+        });
+        assert!(err.is_err());
+        assert_eq!("new content", std::fs::read_to_string(&path).unwrap());
+        assert_eq!(1, std::fs::read_dir(root.path()).unwrap().count());
     }
 
     fn assert_file_equals<A: AsRef<Path>>(file: A, data: String) {
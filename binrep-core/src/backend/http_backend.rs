@@ -0,0 +1,288 @@
+//! Read only backend serving a repository over HTTP(S), eg. a static mirror of a file backend
+use crate::backend::{Backend, BackendError, ProgressReporter};
+use crate::config::HttpBackendOpt;
+use crate::progress::{Progress, SharedProgress, TransferProgress};
+use anyhow::Error;
+use log::debug;
+use reqwest::{Client, Method, Response, StatusCode, Url};
+use ring::digest;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::time::timeout;
+
+/// Maximum delay between two chunks of a response body
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct HttpBackend<T: ProgressReporter> {
+    client: Client,
+    /// ending with a `/`
+    base_url: Url,
+    /// login and password sent with basic authentication
+    credentials: Option<(String, Option<String>)>,
+    request_timeout: Duration,
+    max_metadata_size: u64,
+    _progress_reporter: PhantomData<T>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum HttpBackendError {
+    #[error("Access denied to {url} (HTTP {status}), check the credentials of the http backend")]
+    AccessDenied { url: String, status: u16 },
+    #[error("Unexpected HTTP status {status} for {url}")]
+    UnexpectedStatus { url: String, status: u16 },
+    #[error("The http backend is read only")]
+    ReadOnly,
+    #[error("The http backend cannot list files")]
+    ListingNotSupported,
+}
+
+impl From<HttpBackendError> for BackendError {
+    fn from(e: HttpBackendError) -> Self {
+        BackendError::Other { cause: e.into() }
+    }
+}
+
+impl From<reqwest::Error> for BackendError {
+    fn from(e: reqwest::Error) -> Self {
+        BackendError::Other { cause: e.into() }
+    }
+}
+
+impl<T: ProgressReporter> HttpBackend<T> {
+    pub fn new(opt: &HttpBackendOpt) -> Result<Self, Error> {
+        let mut base_url = Url::parse(&opt.url)?;
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+        let credentials = match (&opt.username, &opt.netrc) {
+            (Some(username), _) => Some((username.clone(), opt.password.clone())),
+            (None, Some(netrc)) => {
+                let netrc = shellexpand::tilde(netrc);
+                let host = base_url.host_str().unwrap_or_default();
+                let credentials = netrc_credentials(&std::fs::read_to_string(&*netrc)?, host);
+                if credentials.is_none() {
+                    debug!("No credentials for {} in {}", host, netrc);
+                }
+                credentials.map(|(login, password)| (login, Some(password)))
+            }
+            (None, None) => None,
+        };
+        let request_timeout = Duration::from_secs(opt.request_timeout_secs.unwrap_or(120));
+        Ok(Self {
+            client: Client::builder().connect_timeout(request_timeout).build()?,
+            base_url,
+            credentials,
+            request_timeout,
+            max_metadata_size: u64::MAX,
+            _progress_reporter: PhantomData,
+        })
+    }
+
+    /// Fail reading text files bigger than `max_metadata_size` bytes
+    pub fn with_max_metadata_size(mut self, max_metadata_size: u64) -> Self {
+        self.max_metadata_size = max_metadata_size;
+        self
+    }
+
+    async fn get(&self, path: &str) -> Result<Response, BackendError> {
+        self.send(Method::GET, path).await
+    }
+
+    async fn send(&self, method: Method, path: &str) -> Result<Response, BackendError> {
+        let url = self
+            .base_url
+            .join(path.trim_start_matches('/'))
+            .map_err(Error::from)?;
+        let mut request = self.client.request(method, url.clone());
+        if let Some((login, password)) = &self.credentials {
+            request = request.basic_auth(login, password.as_ref());
+        }
+        let response = timeout(self.request_timeout, request.send()).await??;
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            StatusCode::NOT_FOUND => Err(BackendError::ResourceNotFound),
+            status @ (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => {
+                Err(HttpBackendError::AccessDenied {
+                    url: url.to_string(),
+                    status: status.as_u16(),
+                })?
+            }
+            status => Err(HttpBackendError::UnexpectedStatus {
+                url: url.to_string(),
+                status: status.as_u16(),
+            })?,
+        }
+    }
+}
+
+#[derive(Default)]
+struct NetrcEntry<'a> {
+    /// none for the default entry
+    machine: Option<&'a str>,
+    login: Option<&'a str>,
+    password: Option<&'a str>,
+}
+
+/// Login and password of `host` in a netrc file: the first `machine` entry matching the host,
+/// else the `default` entry
+fn netrc_credentials(netrc: &str, host: &str) -> Option<(String, String)> {
+    let mut tokens = netrc.split_whitespace();
+    let mut entries: Vec<NetrcEntry> = Vec::new();
+    while let Some(token) = tokens.next() {
+        match (token, entries.last_mut()) {
+            ("machine", _) => entries.push(NetrcEntry {
+                machine: Some(tokens.next().unwrap_or_default()),
+                ..Default::default()
+            }),
+            ("default", _) => entries.push(NetrcEntry::default()),
+            ("login", Some(entry)) => entry.login = tokens.next(),
+            ("password", Some(entry)) => entry.password = tokens.next(),
+            // macro definitions end with a blank line, not supported: stop there
+            ("macdef", _) => break,
+            _ => {}
+        }
+    }
+    entries
+        .iter()
+        .find(|entry| entry.machine == Some(host))
+        .or_else(|| entries.iter().find(|entry| entry.machine.is_none()))
+        .and_then(|entry| Some((entry.login?.to_string(), entry.password?.to_string())))
+}
+
+#[async_trait::async_trait(?Send)]
+impl<T: ProgressReporter> Backend<T> for HttpBackend<T> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(path = path))
+    )]
+    async fn read_file(&mut self, path: &str) -> Result<String, BackendError> {
+        let mut response = self.get(path).await?;
+        let too_large = || BackendError::MetadataTooLarge {
+            path: path.to_string(),
+            max_size: self.max_metadata_size,
+        };
+        if response
+            .content_length()
+            .map(|size| size > self.max_metadata_size)
+            == Some(true)
+        {
+            Err(too_large())?;
+        }
+        let mut buf = Vec::new();
+        while let Some(chunk) = timeout(READ_TIMEOUT, response.chunk()).await?? {
+            buf.extend_from_slice(&chunk);
+            if buf.len() as u64 > self.max_metadata_size {
+                Err(too_large())?;
+            }
+        }
+        Ok(String::from_utf8(buf).map_err(Error::from)?)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(path = path))
+    )]
+    async fn head_file(&mut self, path: &str) -> Result<bool, BackendError> {
+        match self.send(Method::HEAD, path).await {
+            Ok(_) => Ok(true),
+            Err(BackendError::ResourceNotFound) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn create_file(&mut self, _path: &str, _data: String) -> Result<(), BackendError> {
+        Err(HttpBackendError::ReadOnly)?
+    }
+
+    async fn push_file(&self, _local: PathBuf, _remote: &str) -> Result<(), BackendError> {
+        Err(HttpBackendError::ReadOnly)?
+    }
+
+    async fn delete_file(&mut self, _path: &str) -> Result<(), BackendError> {
+        Err(HttpBackendError::ReadOnly)?
+    }
+
+    async fn list_files(&mut self, _prefix: &str) -> Result<Vec<String>, BackendError> {
+        Err(HttpBackendError::ListingNotSupported)?
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(remote = remote))
+    )]
+    async fn pull_file(&self, remote: &str, local: PathBuf) -> Result<(), BackendError> {
+        let mut response = self.get(remote).await?;
+        let mut progress = T::create(
+            Some(format!("downloading {}", remote)),
+            response.content_length().map(|size| size as usize),
+        );
+        let mut file = tokio::fs::File::create(&local).await?;
+        while let Some(chunk) = timeout(READ_TIMEOUT, response.chunk()).await?? {
+            file.write_all(&chunk).await?;
+            progress.inc(chunk.len());
+        }
+        file.flush().await?;
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(remote = remote))
+    )]
+    async fn pull_file_with_digest(
+        &self,
+        remote: &str,
+        local: PathBuf,
+        algorithm: &'static digest::Algorithm,
+        shared_progress: Option<&SharedProgress>,
+    ) -> Result<digest::Digest, BackendError> {
+        let mut response = self.get(remote).await?;
+        let mut progress = TransferProgress::new::<T>(
+            shared_progress,
+            format!("downloading {}", remote),
+            response.content_length().map(|size| size as usize),
+        );
+        let mut file = tokio::fs::File::create(&local).await?;
+        let mut hash_context = digest::Context::new(algorithm);
+        while let Some(chunk) = timeout(READ_TIMEOUT, response.chunk()).await?? {
+            hash_context.update(&chunk);
+            file.write_all(&chunk).await?;
+            progress.inc(chunk.len());
+        }
+        file.flush().await?;
+        Ok(hash_context.finish())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::netrc_credentials;
+
+    #[test]
+    fn netrc() {
+        let netrc = "machine mirror.example.com login deploy password s3cr3t
+            machine other.example.com
+                login other
+                password other-password
+            default login anonymous password guest";
+        assert_eq!(
+            Some(("deploy".to_string(), "s3cr3t".to_string())),
+            netrc_credentials(netrc, "mirror.example.com")
+        );
+        assert_eq!(
+            Some(("other".to_string(), "other-password".to_string())),
+            netrc_credentials(netrc, "other.example.com")
+        );
+        assert_eq!(
+            Some(("anonymous".to_string(), "guest".to_string())),
+            netrc_credentials(netrc, "unknown.example.com")
+        );
+        assert_eq!(
+            None,
+            netrc_credentials("machine a.example.com login a", "a.example.com")
+        );
+        assert_eq!(None, netrc_credentials("", "a.example.com"));
+    }
+}
@@ -1,8 +1,17 @@
-use crate::progress::ProgressReporter;
+use crate::config::DEFAULT_CHECKSUM_BUFFER_SIZE;
+use crate::crypto;
+use crate::progress::{Progress, ProgressReporter, SharedProgress};
+use chrono::{DateTime, Utc};
+use ring::digest;
 use std::error::Error;
+use std::io::Write;
 use std::path::PathBuf;
 
 pub mod file_backend;
+pub mod gcs_backend;
+pub mod http_backend;
+#[cfg(test)]
+pub mod recording_backend;
 pub mod s3_backend;
 
 #[derive(Debug, thiserror::Error)]
@@ -11,6 +20,8 @@ pub enum BackendError {
     ResourceNotFound,
     #[error("backend returned error: {cause}")]
     Other { cause: anyhow::Error },
+    #[error("{path} exceeds the maximum metadata size of {max_size} bytes")]
+    MetadataTooLarge { path: String, max_size: u64 },
 }
 
 impl From<anyhow::Error> for BackendError {
@@ -26,15 +37,82 @@ pub trait Backend<T: ProgressReporter> {
     /// The path is relative to the ROOT of the backend
     async fn read_file(&mut self, path: &str) -> Result<String, BackendError>;
 
+    /// Tell whether a file exists, without downloading it
+    ///
+    /// The path is relative to the ROOT of the backend
+    async fn head_file(&mut self, path: &str) -> Result<bool, BackendError>;
+
+    /// Last modification time of a file, `None` if the backend does not know it
+    ///
+    /// The path is relative to the ROOT of the backend
+    async fn last_modified(&mut self, _path: &str) -> Result<Option<DateTime<Utc>>, BackendError> {
+        Ok(None)
+    }
+
     /// create text a file in the specified path
     ///
     /// The path is relative to the ROOT of the backend
     async fn create_file(&mut self, path: &str, data: String) -> Result<(), BackendError>;
 
-    async fn push_file(&mut self, local: PathBuf, remote: &str) -> Result<(), BackendError>;
+    async fn push_file(&self, local: PathBuf, remote: &str) -> Result<(), BackendError>;
+
+    /// Push in memory content to a file of the backend.
+    ///
+    /// The default implementation writes the content to a temporary file pushed with
+    /// [`Backend::push_file`].
+    async fn push_bytes(&self, data: Vec<u8>, remote: &str) -> Result<(), BackendError> {
+        let mut local = tempfile::NamedTempFile::new()?;
+        local.write_all(&data)?;
+        self.push_file(local.path().to_path_buf(), remote).await
+    }
+
+    /// Delete a file of the backend
+    ///
+    /// The path is relative to the ROOT of the backend
+    ///
+    /// Fails with [`BackendError::ResourceNotFound`] if the file does not exist.
+    async fn delete_file(&mut self, path: &str) -> Result<(), BackendError>;
+
+    /// List the files whose path starts with `prefix` (eg. `binrep/` for the files of an
+    /// artifact, an empty prefix for all the files), in no particular order
+    ///
+    /// The paths are relative to the ROOT of the backend
+    async fn list_files(&mut self, prefix: &str) -> Result<Vec<String>, BackendError>;
 
     /// Pull a file from the backend to a local file.
     ///
     /// It does not check if the local file exists!
-    async fn pull_file(&mut self, remote: &str, local: PathBuf) -> Result<(), BackendError>;
+    async fn pull_file(&self, remote: &str, local: PathBuf) -> Result<(), BackendError>;
+
+    /// Whether the pulls complete the partial file left at the local path by an interrupted
+    /// download instead of starting over
+    fn resumes_downloads(&self) -> bool {
+        false
+    }
+
+    /// Pull a file from the backend to a local file and compute the digest of its content.
+    ///
+    /// The progress is reported to `shared_progress` if given, else to a progress of the file.
+    ///
+    /// The default implementation reads back the pulled file, backends able to hash the bytes
+    /// while writing them should override it.
+    async fn pull_file_with_digest(
+        &self,
+        remote: &str,
+        local: PathBuf,
+        algorithm: &'static digest::Algorithm,
+        shared_progress: Option<&SharedProgress>,
+    ) -> Result<digest::Digest, BackendError> {
+        self.pull_file(remote, local.clone()).await?;
+        if let Some(shared_progress) = shared_progress {
+            shared_progress
+                .clone()
+                .inc(std::fs::metadata(&local)?.len() as usize);
+        }
+        Ok(crypto::digest_file(
+            local,
+            algorithm,
+            DEFAULT_CHECKSUM_BUFFER_SIZE,
+        )?)
+    }
 }
@@ -0,0 +1,196 @@
+//! Backend decorator recording operations, used to assert on backend interactions in tests.
+//! Hooks run around transfers to simulate slow, failing or partial transfers.
+use crate::backend::{Backend, BackendError};
+use crate::progress::{ProgressReporter, SharedProgress};
+use chrono::{DateTime, Utc};
+use futures::future::LocalBoxFuture;
+use ring::digest;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// An operation and the backend path it applies to
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum BackendOperation {
+    Read(String),
+    Head(String),
+    Create(String),
+    Push(String),
+    Pull(String),
+    Delete(String),
+    List(String),
+}
+
+/// Hook called with the remote path of a push
+pub type PushHook = Box<dyn Fn(&str) -> LocalBoxFuture<'static, Result<(), BackendError>>>;
+/// Hook called with the remote path and the local file of a pull
+pub type PullHook = Box<dyn Fn(&str, &Path) -> LocalBoxFuture<'static, Result<(), BackendError>>>;
+
+pub struct RecordingBackend<B, T> {
+    inner: B,
+    operations: Arc<Mutex<Vec<BackendOperation>>>,
+    before_push: Option<PushHook>,
+    after_push: Option<PushHook>,
+    before_pull: Option<PullHook>,
+    after_pull: Option<PullHook>,
+    resumes_downloads: bool,
+    modification_times: bool,
+    _progress_reporter: PhantomData<T>,
+}
+
+impl<B: Backend<T>, T: ProgressReporter> RecordingBackend<B, T> {
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            operations: Arc::new(Mutex::new(Vec::new())),
+            before_push: None,
+            after_push: None,
+            before_pull: None,
+            after_pull: None,
+            resumes_downloads: false,
+            modification_times: true,
+            _progress_reporter: PhantomData,
+        }
+    }
+
+    /// Run `hook` before each push, an error fails the push
+    pub fn with_before_push(mut self, hook: PushHook) -> Self {
+        self.before_push = Some(hook);
+        self
+    }
+
+    /// Run `hook` once each push is done, an error fails the push
+    pub fn with_after_push(mut self, hook: PushHook) -> Self {
+        self.after_push = Some(hook);
+        self
+    }
+
+    /// Run `hook` before each pull, an error fails the pull
+    pub fn with_before_pull(mut self, hook: PullHook) -> Self {
+        self.before_pull = Some(hook);
+        self
+    }
+
+    /// Run `hook` once each pull is done, an error fails the pull
+    pub fn with_after_pull(mut self, hook: PullHook) -> Self {
+        self.after_pull = Some(hook);
+        self
+    }
+
+    /// Claim to resume the downloads of partial files
+    pub fn with_resumed_downloads(mut self) -> Self {
+        self.resumes_downloads = true;
+        self
+    }
+
+    /// Report no modification time, like backends unable to tell
+    pub fn without_modification_times(mut self) -> Self {
+        self.modification_times = false;
+        self
+    }
+
+    /// Shared handle on the recorded operations, still readable once the backend is boxed
+    pub fn operations(&self) -> Arc<Mutex<Vec<BackendOperation>>> {
+        self.operations.clone()
+    }
+
+    fn record(&self, operation: BackendOperation) {
+        self.operations.lock().unwrap().push(operation);
+    }
+
+    async fn run_push_hook(hook: &Option<PushHook>, remote: &str) -> Result<(), BackendError> {
+        match hook {
+            Some(hook) => hook(remote).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn run_pull_hook(
+        hook: &Option<PullHook>,
+        remote: &str,
+        local: &Path,
+    ) -> Result<(), BackendError> {
+        match hook {
+            Some(hook) => hook(remote, local).await,
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<B: Backend<T>, T: ProgressReporter> Backend<T> for RecordingBackend<B, T> {
+    async fn read_file(&mut self, path: &str) -> Result<String, BackendError> {
+        self.record(BackendOperation::Read(path.to_string()));
+        self.inner.read_file(path).await
+    }
+
+    async fn head_file(&mut self, path: &str) -> Result<bool, BackendError> {
+        self.record(BackendOperation::Head(path.to_string()));
+        self.inner.head_file(path).await
+    }
+
+    async fn last_modified(&mut self, path: &str) -> Result<Option<DateTime<Utc>>, BackendError> {
+        self.record(BackendOperation::Head(path.to_string()));
+        if !self.modification_times {
+            return Ok(None);
+        }
+        self.inner.last_modified(path).await
+    }
+
+    async fn create_file(&mut self, path: &str, data: String) -> Result<(), BackendError> {
+        self.record(BackendOperation::Create(path.to_string()));
+        self.inner.create_file(path, data).await
+    }
+
+    async fn push_file(&self, local: PathBuf, remote: &str) -> Result<(), BackendError> {
+        self.record(BackendOperation::Push(remote.to_string()));
+        Self::run_push_hook(&self.before_push, remote).await?;
+        self.inner.push_file(local, remote).await?;
+        Self::run_push_hook(&self.after_push, remote).await
+    }
+
+    async fn push_bytes(&self, data: Vec<u8>, remote: &str) -> Result<(), BackendError> {
+        self.record(BackendOperation::Push(remote.to_string()));
+        Self::run_push_hook(&self.before_push, remote).await?;
+        self.inner.push_bytes(data, remote).await?;
+        Self::run_push_hook(&self.after_push, remote).await
+    }
+
+    async fn delete_file(&mut self, path: &str) -> Result<(), BackendError> {
+        self.record(BackendOperation::Delete(path.to_string()));
+        self.inner.delete_file(path).await
+    }
+
+    async fn list_files(&mut self, prefix: &str) -> Result<Vec<String>, BackendError> {
+        self.record(BackendOperation::List(prefix.to_string()));
+        self.inner.list_files(prefix).await
+    }
+
+    async fn pull_file(&self, remote: &str, local: PathBuf) -> Result<(), BackendError> {
+        self.record(BackendOperation::Pull(remote.to_string()));
+        Self::run_pull_hook(&self.before_pull, remote, &local).await?;
+        self.inner.pull_file(remote, local.clone()).await?;
+        Self::run_pull_hook(&self.after_pull, remote, &local).await
+    }
+
+    fn resumes_downloads(&self) -> bool {
+        self.resumes_downloads
+    }
+
+    async fn pull_file_with_digest(
+        &self,
+        remote: &str,
+        local: PathBuf,
+        algorithm: &'static digest::Algorithm,
+        shared_progress: Option<&SharedProgress>,
+    ) -> Result<digest::Digest, BackendError> {
+        self.record(BackendOperation::Pull(remote.to_string()));
+        Self::run_pull_hook(&self.before_pull, remote, &local).await?;
+        let digest = self
+            .inner
+            .pull_file_with_digest(remote, local.clone(), algorithm, shared_progress)
+            .await?;
+        Self::run_pull_hook(&self.after_pull, remote, &local).await?;
+        Ok(digest)
+    }
+}
@@ -1,17 +1,32 @@
 use crate::backend::{Backend, BackendError, ProgressReporter};
-use crate::config::S3BackendOpt;
+use crate::config::{S3BackendOpt, DEFAULT_CHECKSUM_BUFFER_SIZE};
+use crate::crypto;
+use crate::crypto::DigestReaderAsyncAdapter;
 use crate::file_utils;
-use crate::progress::{ProgressReaderAdapter, ProgressReaderAsyncAdapter};
+use crate::progress::{
+    ProgressReaderAdapter, ProgressReaderAsyncAdapter, SharedProgress, TransferProgress,
+};
 use anyhow::Error;
 use atty::Stream;
+use chrono::{DateTime, Utc};
 use futures::future::lazy;
 use futures::StreamExt;
 use futures::TryStreamExt;
 use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
 use rusoto_core::{ByteStream, HttpClient, Region, RusotoError};
-use rusoto_credential::ProfileProvider;
+use rusoto_credential::{
+    DefaultCredentialsProvider, EnvironmentProvider, ProfileProvider, ProvideAwsCredentials,
+    StaticProvider,
+};
 use rusoto_s3::{
-    GetObjectError, GetObjectRequest, PutObjectError, PutObjectRequest, S3Client, StreamingBody, S3,
+    AbortMultipartUploadError, AbortMultipartUploadRequest, CompleteMultipartUploadError,
+    CompleteMultipartUploadRequest, CompletedMultipartUpload, CompletedPart,
+    CreateMultipartUploadError, CreateMultipartUploadRequest, DeleteObjectError,
+    DeleteObjectRequest, GetObjectError, GetObjectRequest, HeadObjectError, HeadObjectRequest,
+    ListObjectsV2Error, ListObjectsV2Request, PutObjectError, PutObjectRequest, S3Client,
+    StreamingBody, UploadPartError, UploadPartRequest, S3,
 };
 use std::cell::RefCell;
 use std::default::Default;
@@ -21,7 +36,7 @@ use std::path::PathBuf;
 use std::str::FromStr;
 use std::time::Duration;
 use tokio::{
-    io::AsyncReadExt,
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
     time::{timeout, Timeout},
 };
 use tokio::{
@@ -29,18 +44,140 @@ use tokio::{
     time::error::Elapsed,
 };
 use tokio_io_timeout::TimeoutReader;
+use tokio_util::bytes::Bytes;
 use tokio_util::codec;
 pub struct S3Backend<T: ProgressReporter> {
     s3client: S3Client,
     bucket: String,
+    /// empty or ending with a `/`
+    prefix: String,
+    storage_class: Option<String>,
+    acl: String,
+    server_side_encryption: Option<String>,
+    sse_kms_key_id: Option<String>,
+    /// files bigger than this size in bytes are pushed with a multipart upload
+    multipart_threshold: u64,
+    max_retries: u32,
+    /// delay before the first retry, doubled on each retry
+    retry_base_delay: Duration,
     request_timeout: Duration,
+    read_timeout: Duration,
+    max_metadata_size: u64,
+    resume_downloads: bool,
+    /// size of the buffer used to hash the resumed downloads
+    checksum_buffer_size: usize,
     _progress_reporter: PhantomData<T>,
 }
 
+/// Number of times an interrupted download is resumed before failing
+const MAX_DOWNLOAD_RESUMES: usize = 5;
+
+/// Limits of S3 multipart uploads: the size of every part but the last one, and the number of
+/// parts
+const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+const MAX_PARTS: u64 = 10_000;
+
 #[derive(thiserror::Error, Debug)]
 pub enum S3BackendError {
     #[error("No body in response")]
     NoBodyInResponse,
+    #[error(
+        "Unknown AWS region '{region}', non-standard regions require a custom endpoint: set \
+        `endpoint` in the backend configuration"
+    )]
+    UnknownRegion { region: String },
+    #[error(
+        "Virtual-hosted style bucket addressing is not supported, remove `path_style = false`"
+    )]
+    VirtualHostedStyleUnsupported,
+    #[error("Static credentials require both `access_key_id` and `secret_access_key`")]
+    IncompleteStaticCredentials,
+    #[error("No {0} in the multipart upload response")]
+    IncompleteMultipartUploadResponse(&'static str),
+}
+
+/// Resolve the region of the backend, a custom one if an endpoint is configured
+fn region(opt: &S3BackendOpt) -> Result<Region, S3BackendError> {
+    match &opt.endpoint {
+        Some(endpoint) => Ok(Region::Custom {
+            name: opt.region.clone(),
+            endpoint: endpoint.clone(),
+        }),
+        None => Region::from_str(&opt.region).map_err(|_| S3BackendError::UnknownRegion {
+            region: opt.region.clone(),
+        }),
+    }
+}
+
+/// Static credentials of the backend, if configured
+fn static_credentials(opt: &S3BackendOpt) -> Result<Option<StaticProvider>, S3BackendError> {
+    match (&opt.access_key_id, &opt.secret_access_key) {
+        (Some(access_key_id), Some(secret_access_key)) => Ok(Some(StaticProvider::new(
+            access_key_id.clone(),
+            secret_access_key.clone(),
+            opt.session_token.clone(),
+            None,
+        ))),
+        (None, None) => Ok(None),
+        _ => Err(S3BackendError::IncompleteStaticCredentials),
+    }
+}
+
+/// Resolve the credentials of the backend without sending any request. Instance and container
+/// roles cannot be checked this way: missing environment and profile credentials are only
+/// logged when no credentials are configured.
+pub(crate) async fn check_credentials(opt: &S3BackendOpt) -> Result<(), Error> {
+    match (static_credentials(opt)?, &opt.profile) {
+        (Some(_), _) => {}
+        (None, Some(profile)) => {
+            let mut profile_provider = ProfileProvider::new()?;
+            profile_provider.set_profile(profile.as_str());
+            profile_provider.credentials().await?;
+        }
+        (None, None) => {
+            if EnvironmentProvider::default().credentials().await.is_err()
+                && ProfileProvider::new()?.credentials().await.is_err()
+            {
+                info!("No S3 credentials in the environment nor in the credentials file, an instance or container role is required");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Transient failures worth retrying: connection errors, throttling and server errors
+fn is_transient<E>(e: &RusotoError<E>) -> bool {
+    match e {
+        RusotoError::HttpDispatch(_) => true,
+        RusotoError::Unknown(response) => {
+            response.status.is_server_error() || response.status.as_u16() == 429
+        }
+        _ => false,
+    }
+}
+
+/// Delay before the retry following the failed `attempt` (0 for the first attempt): `base`
+/// doubled on each attempt, of which a random part up to the half is removed (jitter)
+fn retry_delay(base: Duration, attempt: u32) -> Duration {
+    let delay = base.saturating_mul(1 << attempt.min(16));
+    let mut random = [0u8; 4];
+    // without randomness, the delay is only shorter
+    let _ = SecureRandom::fill(&SystemRandom::new(), &mut random);
+    delay - delay.mul_f64(u32::from_le_bytes(random) as f64 / u32::MAX as f64 / 2.0)
+}
+
+/// Size of the parts of a multipart upload of `len` bytes, at least `threshold`
+fn part_size(len: u64, threshold: u64) -> u64 {
+    threshold.max(MIN_PART_SIZE).max(len.div_ceil(MAX_PARTS))
+}
+
+/// Range header requesting the bytes from `offset` to the end, none for the whole object
+fn range(offset: u64) -> Option<String> {
+    if offset == 0 {
+        None
+    } else {
+        Some(format!("bytes={}-", offset))
+    }
 }
 
 impl From<RusotoError<GetObjectError>> for BackendError {
@@ -61,6 +198,55 @@ impl From<RusotoError<PutObjectError>> for BackendError {
     }
 }
 
+impl From<RusotoError<DeleteObjectError>> for BackendError {
+    fn from(e: RusotoError<DeleteObjectError>) -> Self {
+        BackendError::Other { cause: e.into() }
+    }
+}
+
+impl From<RusotoError<ListObjectsV2Error>> for BackendError {
+    fn from(e: RusotoError<ListObjectsV2Error>) -> Self {
+        BackendError::Other { cause: e.into() }
+    }
+}
+
+impl From<RusotoError<CreateMultipartUploadError>> for BackendError {
+    fn from(e: RusotoError<CreateMultipartUploadError>) -> Self {
+        BackendError::Other { cause: e.into() }
+    }
+}
+
+impl From<RusotoError<UploadPartError>> for BackendError {
+    fn from(e: RusotoError<UploadPartError>) -> Self {
+        BackendError::Other { cause: e.into() }
+    }
+}
+
+impl From<RusotoError<CompleteMultipartUploadError>> for BackendError {
+    fn from(e: RusotoError<CompleteMultipartUploadError>) -> Self {
+        BackendError::Other { cause: e.into() }
+    }
+}
+
+impl From<RusotoError<AbortMultipartUploadError>> for BackendError {
+    fn from(e: RusotoError<AbortMultipartUploadError>) -> Self {
+        BackendError::Other { cause: e.into() }
+    }
+}
+
+impl From<RusotoError<HeadObjectError>> for BackendError {
+    fn from(e: RusotoError<HeadObjectError>) -> Self {
+        match &e {
+            // HEAD responses have no body telling the error code
+            RusotoError::Service(HeadObjectError::NoSuchKey(_)) => BackendError::ResourceNotFound,
+            RusotoError::Unknown(response) if response.status.as_u16() == 404 => {
+                BackendError::ResourceNotFound
+            }
+            _ => BackendError::Other { cause: e.into() },
+        }
+    }
+}
+
 impl From<S3BackendError> for BackendError {
     fn from(e: S3BackendError) -> Self {
         BackendError::Other { cause: e.into() }
@@ -75,30 +261,106 @@ impl From<Elapsed> for BackendError {
 
 impl<T: ProgressReporter> S3Backend<T> {
     pub fn new(opt: &S3BackendOpt) -> Result<Self, Error> {
-        let mut profile_provider = ProfileProvider::new()?;
-        if let Some(profile) = &opt.profile {
-            profile_provider.set_profile(profile.as_str());
+        // rusoto always puts the bucket in the request path
+        if opt.path_style == Some(false) {
+            Err(S3BackendError::VirtualHostedStyleUnsupported)?;
         }
-        let s3client = S3Client::new_with(
-            HttpClient::new()?,
-            profile_provider,
-            Region::from_str(&opt.region)?,
-        );
+        let region = region(opt)?;
+        let s3client = match (static_credentials(opt)?, &opt.profile) {
+            (Some(static_provider), _) => {
+                S3Client::new_with(HttpClient::new()?, static_provider, region)
+            }
+            (None, Some(profile)) => {
+                let mut profile_provider = ProfileProvider::new()?;
+                profile_provider.set_profile(profile.as_str());
+                S3Client::new_with(HttpClient::new()?, profile_provider, region)
+            }
+            // environment, credentials file, then ECS task or EC2 instance role
+            (None, None) => S3Client::new_with(
+                HttpClient::new()?,
+                DefaultCredentialsProvider::new()?,
+                region,
+            ),
+        };
         Ok(Self {
             s3client,
             bucket: opt.bucket.clone(),
+            prefix: opt
+                .prefix
+                .as_deref()
+                .map(|prefix| prefix.trim_matches('/'))
+                .filter(|prefix| !prefix.is_empty())
+                .map(|prefix| format!("{}/", prefix))
+                .unwrap_or_default(),
+            storage_class: opt.storage_class.clone(),
+            acl: opt
+                .acl
+                .clone()
+                .unwrap_or_else(|| "bucket-owner-full-control".to_string()),
+            server_side_encryption: match (&opt.server_side_encryption, &opt.sse_kms_key_id) {
+                (None, Some(_)) => Some("aws:kms".to_string()),
+                (sse, _) => sse.clone(),
+            },
+            sse_kms_key_id: opt.sse_kms_key_id.clone(),
+            multipart_threshold: opt.multipart_threshold_mb.unwrap_or(64) * 1024 * 1024,
+            max_retries: opt.max_retries.unwrap_or(3),
+            retry_base_delay: Duration::from_millis(100),
             request_timeout: Duration::from_secs(opt.request_timeout_secs.unwrap_or(120)),
+            read_timeout: Duration::from_secs(opt.read_timeout_secs.unwrap_or(30)),
+            max_metadata_size: u64::MAX,
+            resume_downloads: opt.resume_downloads.unwrap_or(false),
+            checksum_buffer_size: DEFAULT_CHECKSUM_BUFFER_SIZE,
             _progress_reporter: PhantomData,
         })
     }
 
-    async fn get_body(&mut self, path: &str) -> Result<(ByteStream, Option<usize>), BackendError> {
-        let request = self.s3client.get_object(GetObjectRequest {
+    /// Fail reading text files bigger than `max_metadata_size` bytes
+    pub fn with_max_metadata_size(mut self, max_metadata_size: u64) -> Self {
+        self.max_metadata_size = max_metadata_size;
+        self
+    }
+
+    /// Hash the resumed downloads with a buffer of `checksum_buffer_size` bytes
+    pub fn with_checksum_buffer_size(mut self, checksum_buffer_size: usize) -> Self {
+        self.checksum_buffer_size = checksum_buffer_size;
+        self
+    }
+
+    fn key(&self, path: &str) -> String {
+        format!("{}{}", self.prefix, path)
+    }
+
+    /// Request putting the object of `path`, with the configured ACL and encryption
+    fn put_object_request(&self, path: &str) -> PutObjectRequest {
+        PutObjectRequest {
             bucket: self.bucket.clone(),
-            key: path.to_string(),
+            key: self.key(path),
+            acl: Some(self.acl.clone()),
+            server_side_encryption: self.server_side_encryption.clone(),
+            ssekms_key_id: self.sse_kms_key_id.clone(),
+            ..Default::default()
+        }
+    }
+
+    async fn get_body(&self, path: &str) -> Result<(ByteStream, Option<usize>), BackendError> {
+        self.get_body_from(path, 0).await
+    }
+
+    /// Body of `path` starting at the byte `offset`, the size is the size of the returned range
+    async fn get_body_from(
+        &self,
+        path: &str,
+        offset: u64,
+    ) -> Result<(ByteStream, Option<usize>), BackendError> {
+        let request = GetObjectRequest {
+            bucket: self.bucket.clone(),
+            key: self.key(path),
+            range: range(offset),
             ..Default::default() // this one is hacky
-        });
-        let output = self.execute_with_timeout(request).await??;
+        };
+        let output = self
+            .with_retry(|| self.s3client.get_object(request.clone()))
+            .await?;
         let size = output.content_length.map(|i| i as usize);
         match output.body {
             None => Err(S3BackendError::NoBodyInResponse)?,
@@ -106,6 +368,25 @@ impl<T: ProgressReporter> S3Backend<T> {
         }
     }
 
+    async fn object_size(&self, path: &str) -> Result<u64, BackendError> {
+        let head = HeadObjectRequest {
+            bucket: self.bucket.clone(),
+            key: self.key(path),
+            ..Default::default()
+        };
+        let output = self
+            .with_retry(|| self.s3client.head_object(head.clone()))
+            .await?;
+        Ok(output.content_length.unwrap_or_default() as u64)
+    }
+
+    /// Fail reads of `reader` idle for more than `read_timeout`
+    fn with_read_timeout<R: AsyncRead>(&self, reader: R) -> TimeoutReader<R> {
+        let mut reader = TimeoutReader::new(reader);
+        reader.set_timeout(Some(self.read_timeout));
+        reader
+    }
+
     async fn execute_with_timeout<R, F: std::future::Future<Output = R>>(
         &self,
         fut: F,
@@ -114,70 +395,399 @@ impl<T: ProgressReporter> S3Backend<T> {
         // we use the lazy trick to get our future
         tokio::time::timeout(self.request_timeout, fut).await
     }
+
+    /// Execute the requests returned by `request` with the request timeout until one succeeds
+    /// or fails with an error which is not transient, at most `max_retries` + 1 times. Retries
+    /// are delayed by an exponential backoff with jitter.
+    async fn with_retry<R, E, F, Fut>(&self, mut request: F) -> Result<R, BackendError>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<R, RusotoError<E>>>,
+        BackendError: From<RusotoError<E>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let error = match self.execute_with_timeout(request()).await {
+                Ok(Ok(output)) => return Ok(output),
+                Ok(Err(e)) if is_transient(&e) => BackendError::from(e),
+                Ok(Err(e)) => return Err(e.into()),
+                Err(elapsed) => <BackendError as From<Elapsed>>::from(elapsed),
+            };
+            if attempt >= self.max_retries {
+                return Err(error);
+            }
+            let delay = retry_delay(self.retry_base_delay, attempt);
+            warn!("S3 request failed, retrying in {:?}: {}", delay, error);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
 }
+
+impl<T> S3Backend<T>
+where
+    T: ProgressReporter,
+    T::Output: Send + Sync + 'static,
+{
+    /// Push `local` with a multipart upload of `len` bytes, aborted on failure so that no
+    /// uploaded part is left in the bucket
+    async fn push_file_multipart(
+        &self,
+        local: PathBuf,
+        remote: &str,
+        len: u64,
+    ) -> Result<(), BackendError> {
+        let req = CreateMultipartUploadRequest {
+            bucket: self.bucket.clone(),
+            key: self.key(remote),
+            acl: Some(self.acl.clone()),
+            server_side_encryption: self.server_side_encryption.clone(),
+            ssekms_key_id: self.sse_kms_key_id.clone(),
+            storage_class: self.storage_class.clone(),
+            ..Default::default()
+        };
+        let upload_id = self
+            .with_retry(|| self.s3client.create_multipart_upload(req.clone()))
+            .await?
+            .upload_id
+            .ok_or(S3BackendError::IncompleteMultipartUploadResponse(
+                "upload id",
+            ))?;
+        let uploaded = self.upload_parts(local, remote, len, &upload_id).await;
+        if uploaded.is_err() {
+            let req = AbortMultipartUploadRequest {
+                bucket: self.bucket.clone(),
+                key: self.key(remote),
+                upload_id,
+                ..Default::default()
+            };
+            if let Err(e) = self
+                .with_retry(|| self.s3client.abort_multipart_upload(req.clone()))
+                .await
+            {
+                warn!("Cannot abort the multipart upload of {}: {}", remote, e);
+            }
+        }
+        uploaded
+    }
+
+    async fn upload_parts(
+        &self,
+        local: PathBuf,
+        remote: &str,
+        len: u64,
+        upload_id: &str,
+    ) -> Result<(), BackendError> {
+        let progress = T::create(Some(format!("Uploading to {}", remote)), Some(len as usize));
+        // a single progress over the parts
+        let mut file = Box::pin(ProgressReaderAsyncAdapter::new(
+            tokio::fs::File::open(local).await?,
+            progress,
+        ));
+        let part_size = part_size(len, self.multipart_threshold);
+        let mut parts = Vec::new();
+        loop {
+            let mut part = Vec::with_capacity(part_size.min(len) as usize);
+            (&mut file).take(part_size).read_to_end(&mut part).await?;
+            if part.is_empty() {
+                break;
+            }
+            // shared by the attempts instead of copied
+            let part = Bytes::from(part);
+            let part_number = parts.len() as i64 + 1;
+            let e_tag = self
+                .with_retry(|| {
+                    self.s3client.upload_part(UploadPartRequest {
+                        bucket: self.bucket.clone(),
+                        key: self.key(remote),
+                        upload_id: upload_id.to_string(),
+                        part_number,
+                        content_length: Some(part.len() as i64),
+                        body: Some(StreamingBody::new_with_size(
+                            futures::stream::once(futures::future::ready(Ok(part.clone()))),
+                            part.len(),
+                        )),
+                        ..Default::default()
+                    })
+                })
+                .await?
+                .e_tag
+                .ok_or(S3BackendError::IncompleteMultipartUploadResponse("ETag"))?;
+            parts.push(CompletedPart {
+                e_tag: Some(e_tag),
+                part_number: Some(part_number),
+            });
+        }
+        let req = CompleteMultipartUploadRequest {
+            bucket: self.bucket.clone(),
+            key: self.key(remote),
+            upload_id: upload_id.to_string(),
+            multipart_upload: Some(CompletedMultipartUpload { parts: Some(parts) }),
+            ..Default::default()
+        };
+        self.with_retry(|| self.s3client.complete_multipart_upload(req.clone()))
+            .await?;
+        Ok(())
+    }
+
+    /// Download `remote` to `local`, completing the partial file left by an interrupted
+    /// download with range requests. The content is not verified: the caller must check the
+    /// checksum of the whole file.
+    async fn pull_file_resuming(
+        &self,
+        remote: &str,
+        local: PathBuf,
+        shared_progress: Option<&SharedProgress>,
+    ) -> Result<(), BackendError> {
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&local)
+            .await?;
+        let mut resumes = 0;
+        loop {
+            let offset = file.metadata().await?.len();
+            if offset > 0 {
+                let size = self.object_size(remote).await?;
+                if offset == size {
+                    return Ok(());
+                }
+                if offset > size {
+                    // not a partial download of the remote file, start over
+                    file.set_len(0).await?;
+                    continue;
+                }
+                info!("Resuming download of {} at byte {}", remote, offset);
+            }
+            let (body, size) = self.get_body_from(remote, offset).await?;
+            let body = self.with_read_timeout(body.into_async_read());
+            let body = ProgressReaderAsyncAdapter::new(
+                body,
+                TransferProgress::new::<T>(
+                    shared_progress,
+                    format!("downloading {}", remote),
+                    size,
+                ),
+            );
+            let copied = tokio::io::copy(&mut Box::pin(body), &mut file).await;
+            // the bytes received before a failure are kept
+            file.flush().await?;
+            match copied {
+                Ok(_) => return Ok(()),
+                Err(e) if resumes < MAX_DOWNLOAD_RESUMES => {
+                    warn!("Download of {} interrupted: {}", remote, e);
+                    resumes += 1;
+                }
+                Err(e) => Err(e)?,
+            }
+        }
+    }
+}
+
 #[async_trait::async_trait(?Send)]
 impl<T> Backend<T> for S3Backend<T>
 where
     T: ProgressReporter,
     T::Output: Send + Sync + 'static,
 {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(path = path))
+    )]
     async fn read_file(&mut self, path: &str) -> Result<String, BackendError> {
         let mut buf = String::new();
         let progress = T::unnamed_ticker();
 
         let (body, body_size) = self.get_body(path).await?;
+        let too_large = || BackendError::MetadataTooLarge {
+            path: path.to_string(),
+            max_size: self.max_metadata_size,
+        };
+        if body_size.map(|size| size as u64 > self.max_metadata_size) == Some(true) {
+            Err(too_large())?;
+        }
 
-        let mut body = ProgressReaderAsyncAdapter::new(body.into_async_read(), progress);
+        let body = ProgressReaderAsyncAdapter::new(body.into_async_read(), progress);
 
-        body.read_to_string(&mut buf).await?;
+        // the content length may be missing: read one more byte to detect oversized files
+        body.take(self.max_metadata_size.saturating_add(1))
+            .read_to_string(&mut buf)
+            .await?;
+        if buf.len() as u64 > self.max_metadata_size {
+            Err(too_large())?;
+        }
         Ok(buf)
     }
 
-    async fn create_file(&mut self, path: &str, data: String) -> Result<(), BackendError> {
-        let req = PutObjectRequest {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(path = path))
+    )]
+    async fn head_file(&mut self, path: &str) -> Result<bool, BackendError> {
+        let head = HeadObjectRequest {
             bucket: self.bucket.clone(),
-            key: path.to_string(),
-            body: Some(data.as_bytes().to_vec().into()),
-            acl: Some("bucket-owner-full-control".to_string()),
+            key: self.key(path),
             ..Default::default()
         };
+        match self
+            .with_retry(|| self.s3client.head_object(head.clone()))
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(BackendError::ResourceNotFound) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
 
-        self.execute_with_timeout(self.s3client.put_object(req))
-            .await??;
+    async fn last_modified(&mut self, path: &str) -> Result<Option<DateTime<Utc>>, BackendError> {
+        let head = HeadObjectRequest {
+            bucket: self.bucket.clone(),
+            key: self.key(path),
+            ..Default::default()
+        };
+        let output = self
+            .with_retry(|| self.s3client.head_object(head.clone()))
+            .await?;
+        // an HTTP date, eg. "Wed, 21 Oct 2015 07:28:00 GMT"
+        Ok(output
+            .last_modified
+            .and_then(|date| DateTime::parse_from_rfc2822(&date).ok())
+            .map(|date| date.with_timezone(&Utc)))
+    }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(path = path))
+    )]
+    async fn create_file(&mut self, path: &str, data: String) -> Result<(), BackendError> {
+        self.with_retry(|| {
+            self.s3client.put_object(PutObjectRequest {
+                body: Some(data.as_bytes().to_vec().into()),
+                ..self.put_object_request(path)
+            })
+        })
+        .await?;
         Ok(())
     }
 
-    async fn push_file(&mut self, local: PathBuf, remote: &str) -> Result<(), BackendError> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(remote = remote))
+    )]
+    async fn push_file(&self, local: PathBuf, remote: &str) -> Result<(), BackendError> {
         let meta = std::fs::metadata(&local)?;
+        if meta.len() > self.multipart_threshold {
+            return self.push_file_multipart(local, remote, meta.len()).await;
+        }
 
-        let progress = T::create(
-            Some(format!("Uploading to {}", remote)),
-            Some(meta.len() as usize),
-        );
-        let file = tokio::fs::File::open(local).await?;
-        let file = ProgressReaderAsyncAdapter::new(file, progress);
-        let byte_stream =
-            codec::FramedRead::new(file, codec::BytesCodec::new()).map_ok(|r| r.freeze());
+        let len = meta.len();
+        let local = &local;
+        // the file is read again on retries
+        self.with_retry(|| async move {
+            let progress = T::create(Some(format!("Uploading to {}", remote)), Some(len as usize));
+            let file = tokio::fs::File::open(local).await?;
+            let file = ProgressReaderAsyncAdapter::new(file, progress);
+            let byte_stream =
+                codec::FramedRead::new(file, codec::BytesCodec::new()).map_ok(|r| r.freeze());
+            let req = PutObjectRequest {
+                content_length: Some(len as i64),
+                body: Some(StreamingBody::new(byte_stream)),
+                storage_class: self.storage_class.clone(),
+                ..self.put_object_request(remote)
+            };
+            self.s3client.put_object(req).await
+        })
+        .await?;
+        Ok(())
+    }
 
-        let req = PutObjectRequest {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(remote = remote))
+    )]
+    async fn push_bytes(&self, data: Vec<u8>, remote: &str) -> Result<(), BackendError> {
+        self.with_retry(|| {
+            self.s3client.put_object(PutObjectRequest {
+                content_length: Some(data.len() as i64),
+                body: Some(data.clone().into()),
+                storage_class: self.storage_class.clone(),
+                ..self.put_object_request(remote)
+            })
+        })
+        .await?;
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(path = path))
+    )]
+    async fn delete_file(&mut self, path: &str) -> Result<(), BackendError> {
+        // S3 does not report missing keys on delete, check the key exists first
+        if !self.head_file(path).await? {
+            Err(BackendError::ResourceNotFound)?;
+        }
+        let req = DeleteObjectRequest {
             bucket: self.bucket.clone(),
-            key: remote.to_string(),
-            content_length: Some(meta.len() as i64),
-            body: Some(StreamingBody::new(byte_stream)),
-            acl: Some("bucket-owner-full-control".to_string()),
+            key: self.key(path),
             ..Default::default()
         };
-        self.execute_with_timeout(self.s3client.put_object(req))
-            .await??;
+        self.with_retry(|| self.s3client.delete_object(req.clone()))
+            .await?;
         Ok(())
     }
 
-    async fn pull_file(&mut self, remote: &str, local: PathBuf) -> Result<(), BackendError> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(prefix = prefix))
+    )]
+    async fn list_files(&mut self, prefix: &str) -> Result<Vec<String>, BackendError> {
+        let mut files = Vec::new();
+        let mut continuation_token = None;
+        loop {
+            let req = ListObjectsV2Request {
+                bucket: self.bucket.clone(),
+                prefix: Some(self.key(prefix.trim_start_matches('/'))),
+                continuation_token,
+                ..Default::default()
+            };
+            let output = self
+                .with_retry(|| self.s3client.list_objects_v2(req.clone()))
+                .await?;
+            files.extend(
+                output
+                    .contents
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|object| object.key)
+                    .filter_map(|key| key.strip_prefix(&self.prefix).map(str::to_string)),
+            );
+            // at most 1000 keys per response
+            match output.next_continuation_token {
+                Some(token) if output.is_truncated == Some(true) => {
+                    continuation_token = Some(token)
+                }
+                _ => break,
+            }
+        }
+        Ok(files)
+    }
+
+    fn resumes_downloads(&self) -> bool {
+        self.resume_downloads
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(remote = remote))
+    )]
+    async fn pull_file(&self, remote: &str, local: PathBuf) -> Result<(), BackendError> {
+        if self.resume_downloads {
+            return self.pull_file_resuming(remote, local, None).await;
+        }
         let mut file = tokio::fs::File::create(&local).await?;
         let (body, size) = self.get_body(remote).await?;
-        let mut body = TimeoutReader::new(body.into_async_read());
-        body.set_timeout(Some(Duration::from_secs(30)));
+        let body = self.with_read_timeout(body.into_async_read());
         let body = ProgressReaderAsyncAdapter::new(
             body,
             T::create(Some(format!("downloading {}", remote)), size),
@@ -187,4 +797,392 @@ where
 
         Ok(())
     }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(remote = remote))
+    )]
+    async fn pull_file_with_digest(
+        &self,
+        remote: &str,
+        local: PathBuf,
+        algorithm: &'static digest::Algorithm,
+        shared_progress: Option<&SharedProgress>,
+    ) -> Result<digest::Digest, BackendError> {
+        if self.resume_downloads {
+            // a resumed download is hashed once complete
+            self.pull_file_resuming(remote, local.clone(), shared_progress)
+                .await?;
+            return Ok(crypto::digest_file(
+                local,
+                algorithm,
+                self.checksum_buffer_size,
+            )?);
+        }
+        let mut file = tokio::fs::File::create(&local).await?;
+        let (body, size) = self.get_body(remote).await?;
+        let body = self.with_read_timeout(body.into_async_read());
+        let body = ProgressReaderAsyncAdapter::new(
+            body,
+            TransferProgress::new::<T>(shared_progress, format!("downloading {}", remote), size),
+        );
+        // hash the bytes while they are written
+        let mut body = Box::pin(DigestReaderAsyncAdapter::new(body, algorithm));
+        tokio::io::copy(&mut body, &mut file).await?;
+
+        Ok(body.digest())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::S3Backend;
+    use crate::config::S3BackendOpt;
+    use crate::progress::NOOPProgress;
+    use reqwest::header::HeaderMap;
+    use reqwest::StatusCode;
+    use rusoto_core::request::{DispatchSignedRequestFuture, HttpResponse};
+    use rusoto_core::signature::SignedRequest;
+    use rusoto_core::{ByteStream, DispatchSignedRequest, Region};
+    use rusoto_credential::StaticProvider;
+    use rusoto_s3::S3Client;
+    use std::io::ErrorKind;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn region() {
+        let mut opt = S3BackendOpt {
+            bucket: "bucket".to_string(),
+            region: "eu-west-3".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(Region::EuWest3, super::region(&opt).unwrap());
+
+        opt.region = "fr-par".to_string();
+        let err = super::region(&opt).unwrap_err();
+        assert!(err.to_string().contains("fr-par"));
+        assert!(err.to_string().contains("endpoint"));
+
+        opt.endpoint = Some("https://s3.fr-par.scw.cloud".to_string());
+        assert_eq!(
+            Region::Custom {
+                name: "fr-par".to_string(),
+                endpoint: "https://s3.fr-par.scw.cloud".to_string()
+            },
+            super::region(&opt).unwrap()
+        );
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(None, super::range(0));
+        assert_eq!(Some("bytes=1024-".to_string()), super::range(1024));
+    }
+
+    #[tokio::test]
+    async fn read_timeout() {
+        let opt = S3BackendOpt {
+            bucket: "bucket".to_string(),
+            region: "eu-west-3".to_string(),
+            read_timeout_secs: Some(1),
+            ..Default::default()
+        };
+        let backend = S3Backend::<NOOPProgress>::new(&opt).unwrap();
+        // a stream that never sends anything
+        let (_writer, reader) = tokio::io::duplex(64);
+        let reader = backend.with_read_timeout(reader);
+        let err = tokio::io::copy(&mut Box::pin(reader), &mut tokio::io::sink())
+            .await
+            .unwrap_err();
+        assert_eq!(ErrorKind::TimedOut, err.kind());
+    }
+
+    /// Answers the queued HTTP statuses, then 200, with the queued bodies, then an empty body.
+    /// Every response carries an ETag, the creation of a multipart upload its id.
+    struct MockDispatcher {
+        statuses: Mutex<Vec<u16>>,
+        bodies: Mutex<Vec<Vec<u8>>>,
+        requests: Arc<AtomicUsize>,
+        /// method and query parameter names of each request, eg. `PUT partNumber&uploadId`
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl DispatchSignedRequest for MockDispatcher {
+        fn dispatch(
+            &self,
+            request: SignedRequest,
+            _timeout: Option<Duration>,
+        ) -> DispatchSignedRequestFuture {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let params: Vec<&str> = request.params.keys().map(String::as_str).collect();
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", request.method, params.join("&")));
+            let mut bodies = self.bodies.lock().unwrap();
+            let body = if request.params.contains_key("uploads") {
+                b"<InitiateMultipartUploadResult><UploadId>upload</UploadId></InitiateMultipartUploadResult>".to_vec()
+            } else if bodies.is_empty() {
+                Vec::new()
+            } else {
+                bodies.remove(0)
+            };
+            let mut statuses = self.statuses.lock().unwrap();
+            let status = if statuses.is_empty() {
+                200
+            } else {
+                statuses.remove(0)
+            };
+            Box::pin(async move {
+                let mut headers = HeaderMap::default();
+                headers.insert("ETag", "\"etag\"".parse().unwrap());
+                Ok(HttpResponse {
+                    status: StatusCode::from_u16(status).unwrap(),
+                    body: ByteStream::from(body),
+                    headers,
+                })
+            })
+        }
+    }
+
+    /// Backend whose requests are answered by a [`MockDispatcher`], with the request counter
+    fn mock_backend(statuses: Vec<u16>) -> (S3Backend<NOOPProgress>, Arc<AtomicUsize>) {
+        let (backend, requests, _) = logging_mock_backend(statuses);
+        (backend, requests)
+    }
+
+    /// [`mock_backend`] with the log of the requests
+    fn logging_mock_backend(
+        statuses: Vec<u16>,
+    ) -> (
+        S3Backend<NOOPProgress>,
+        Arc<AtomicUsize>,
+        Arc<Mutex<Vec<String>>>,
+    ) {
+        replying_mock_backend(statuses, Vec::new())
+    }
+
+    /// [`logging_mock_backend`] answering the queued response bodies
+    fn replying_mock_backend(
+        statuses: Vec<u16>,
+        bodies: Vec<&str>,
+    ) -> (
+        S3Backend<NOOPProgress>,
+        Arc<AtomicUsize>,
+        Arc<Mutex<Vec<String>>>,
+    ) {
+        let opt = S3BackendOpt {
+            bucket: "bucket".to_string(),
+            region: "eu-west-3".to_string(),
+            ..Default::default()
+        };
+        let requests = Arc::new(AtomicUsize::new(0));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut backend = S3Backend::<NOOPProgress>::new(&opt).unwrap();
+        backend.s3client = S3Client::new_with(
+            MockDispatcher {
+                statuses: Mutex::new(statuses),
+                bodies: Mutex::new(bodies.into_iter().map(|body| body.into()).collect()),
+                requests: requests.clone(),
+                log: log.clone(),
+            },
+            StaticProvider::new_minimal("AKIDEXAMPLE".to_string(), "secret".to_string()),
+            Region::EuWest3,
+        );
+        backend.retry_base_delay = Duration::from_millis(1);
+        (backend, requests, log)
+    }
+
+    #[tokio::test]
+    async fn multipart_upload() {
+        use crate::backend::Backend;
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        // 3 parts of at most 5 MiB
+        std::fs::write(file.path(), vec![0u8; 11 * 1024 * 1024]).unwrap();
+        let (mut backend, _, log) = logging_mock_backend(vec![]);
+        backend.multipart_threshold = 0;
+        backend
+            .push_file(file.path().to_path_buf(), "a/1.0.0/file")
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![
+                "POST uploads",
+                "PUT partNumber&uploadId",
+                "PUT partNumber&uploadId",
+                "PUT partNumber&uploadId",
+                "POST uploadId",
+            ],
+            *log.lock().unwrap()
+        );
+
+        // a failed part is retried, then the upload is aborted
+        let (mut backend, _, log) = logging_mock_backend(vec![200, 200, 503, 403]);
+        backend.multipart_threshold = 0;
+        assert!(backend
+            .push_file(file.path().to_path_buf(), "a/1.0.0/file")
+            .await
+            .is_err());
+        assert_eq!(
+            vec![
+                "POST uploads",
+                "PUT partNumber&uploadId",
+                "PUT partNumber&uploadId",
+                "PUT partNumber&uploadId",
+                "DELETE uploadId",
+            ],
+            *log.lock().unwrap()
+        );
+
+        // small files are pushed in one request
+        let (mut backend, _, log) = logging_mock_backend(vec![]);
+        backend.multipart_threshold = 64 * 1024 * 1024;
+        backend
+            .push_file(file.path().to_path_buf(), "a/1.0.0/file")
+            .await
+            .unwrap();
+        assert_eq!(vec!["PUT "], *log.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn list_files_pages() {
+        use crate::backend::Backend;
+
+        let (mut backend, _, log) = replying_mock_backend(
+            vec![],
+            vec![
+                "<ListBucketResult><IsTruncated>true</IsTruncated>\
+                 <Contents><Key>a/1.0.0/file1</Key></Contents>\
+                 <Contents><Key>a/1.0.0/file2</Key></Contents>\
+                 <NextContinuationToken>page2</NextContinuationToken></ListBucketResult>",
+                "<ListBucketResult><IsTruncated>false</IsTruncated>\
+                 <Contents><Key>a/2.0.0/file1</Key></Contents></ListBucketResult>",
+            ],
+        );
+        assert_eq!(
+            vec!["a/1.0.0/file1", "a/1.0.0/file2", "a/2.0.0/file1"],
+            backend.list_files("a/").await.unwrap()
+        );
+        // the second page is requested with the continuation token of the first one
+        assert_eq!(
+            vec![
+                "GET list-type&prefix",
+                "GET continuation-token&list-type&prefix"
+            ],
+            *log.lock().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn retry() {
+        use crate::backend::Backend;
+
+        let (mut backend, requests) = mock_backend(vec![503, 500]);
+        assert!(backend.head_file("artifacts.sane").await.unwrap());
+        assert_eq!(3, requests.load(Ordering::SeqCst));
+
+        let (mut backend, requests) = mock_backend(vec![429, 503]);
+        backend
+            .create_file("artifacts.sane", "artifacts = []".to_string())
+            .await
+            .unwrap();
+        assert_eq!(3, requests.load(Ordering::SeqCst));
+
+        // gives up after 3 retries
+        let (mut backend, requests) = mock_backend(vec![503; 5]);
+        assert!(backend.head_file("artifacts.sane").await.is_err());
+        assert_eq!(4, requests.load(Ordering::SeqCst));
+
+        // missing keys and access denied are not retried
+        let (mut backend, requests) = mock_backend(vec![404]);
+        assert!(!backend.head_file("artifacts.sane").await.unwrap());
+        assert_eq!(1, requests.load(Ordering::SeqCst));
+        let (mut backend, requests) = mock_backend(vec![403]);
+        assert!(backend.head_file("artifacts.sane").await.is_err());
+        assert_eq!(1, requests.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn retry_delay() {
+        let base = Duration::from_millis(100);
+        for attempt in 0..4 {
+            let delay = super::retry_delay(base, attempt);
+            let max = base * 2u32.pow(attempt);
+            assert!(delay <= max && delay >= max / 2, "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn part_size() {
+        const MB: u64 = 1024 * 1024;
+        assert_eq!(64 * MB, super::part_size(100 * MB, 64 * MB));
+        // parts of at least 5MB
+        assert_eq!(5 * MB, super::part_size(100 * MB, MB));
+        // at most 10000 parts
+        let len = 1024 * 1024 * MB;
+        assert_eq!(len.div_ceil(10_000), super::part_size(len, 64 * MB));
+        assert!(super::part_size(len, 64 * MB) * 10_000 >= len);
+    }
+
+    #[test]
+    fn put_object_request() {
+        let mut opt = S3BackendOpt {
+            bucket: "bucket".to_string(),
+            region: "eu-west-3".to_string(),
+            prefix: Some("binrep".to_string()),
+            ..Default::default()
+        };
+        let req = S3Backend::<NOOPProgress>::new(&opt)
+            .unwrap()
+            .put_object_request("artifacts.sane");
+        assert_eq!("binrep/artifacts.sane", req.key);
+        assert_eq!(Some("bucket-owner-full-control"), req.acl.as_deref());
+        assert_eq!(None, req.server_side_encryption);
+        assert_eq!(None, req.ssekms_key_id);
+
+        opt.acl = Some("private".to_string());
+        opt.sse_kms_key_id = Some("alias/binrep".to_string());
+        let req = S3Backend::<NOOPProgress>::new(&opt)
+            .unwrap()
+            .put_object_request("artifacts.sane");
+        assert_eq!(Some("private"), req.acl.as_deref());
+        assert_eq!(Some("aws:kms"), req.server_side_encryption.as_deref());
+        assert_eq!(Some("alias/binrep"), req.ssekms_key_id.as_deref());
+
+        opt.server_side_encryption = Some("AES256".to_string());
+        opt.sse_kms_key_id = None;
+        let req = S3Backend::<NOOPProgress>::new(&opt)
+            .unwrap()
+            .put_object_request("artifacts.sane");
+        assert_eq!(Some("AES256"), req.server_side_encryption.as_deref());
+    }
+
+    #[test]
+    fn static_credentials() {
+        let mut opt = S3BackendOpt {
+            bucket: "bucket".to_string(),
+            region: "eu-west-3".to_string(),
+            profile: Some("ignored".to_string()),
+            ..Default::default()
+        };
+        assert!(super::static_credentials(&opt).unwrap().is_none());
+
+        opt.access_key_id = Some("AKIDEXAMPLE".to_string());
+        assert!(super::static_credentials(&opt).is_err());
+        assert!(S3Backend::<NOOPProgress>::new(&opt).is_err());
+
+        opt.secret_access_key = Some("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string());
+        opt.session_token = Some("token".to_string());
+        let provider = super::static_credentials(&opt).unwrap().unwrap();
+        assert_eq!("AKIDEXAMPLE", provider.get_aws_access_key_id());
+        assert_eq!(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            provider.get_aws_secret_access_key()
+        );
+        assert_eq!(&Some("token".to_string()), provider.get_token());
+        S3Backend::<NOOPProgress>::new(&opt).unwrap();
+    }
 }
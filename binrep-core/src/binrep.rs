@@ -1,47 +1,344 @@
 //! High level binrep API
-use crate::config::Config;
+pub use crate::backend::BackendError;
+use crate::clock::Clock;
+use crate::config::{BackendType, Config, ConfigValidationError};
 use crate::config_resolver::resolve_config as resolve_any_config;
+use crate::config_resolver::CONFIG_FILE_NAME;
+use crate::crypto;
+use crate::exec::{exec, ExecOptions};
+use crate::extended_exec::Line;
 use crate::file_utils;
 use crate::file_utils::{mkdirs, mv, path_concat2, LockFile};
 use crate::metadata::*;
-use crate::progress::ProgressReporter;
-use crate::repository::Repository;
+use crate::progress::{NOOPProgress, ProgressReporter};
+use crate::repository::{partial_download_dir, Repository};
+pub use crate::repository::{
+    FileFilter, FsckReport, MirrorFailure, MirrorOptions, MirrorReport, PullOptions, PushOptions,
+    RepositoryError, ResignReport, FSCK_GRACE_PERIOD_HOURS,
+};
 use anyhow::Error;
+use chrono::{DateTime, Utc};
 use fs2::FileExt;
 use semver::{Version, VersionReq};
 use serde::de::DeserializeOwned;
+use serde::Serialize;
 use slack_hook3::{AttachmentBuilder, Payload, PayloadBuilder, Slack};
+use std::fmt;
 use std::fs::metadata;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
-use tempfile::{tempdir, tempdir_in};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 pub struct Binrep<T: ProgressReporter> {
     repository: Repository<T>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SyncStatus {
     UpToDate,
     Updated,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct SyncResult {
+    pub artifact_name: String,
+    /// Version synced before this sync, if any
+    pub previous_version: Option<Version>,
     pub artifact: Artifact,
     pub status: SyncStatus,
+    /// Nothing was pulled nor installed, `status` is what a real sync would have done
+    pub dry_run: bool,
+}
+
+impl fmt::Display for SyncResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.status, &self.previous_version) {
+            (SyncStatus::UpToDate, _) => write!(
+                f,
+                "{} {} is up to date",
+                self.artifact_name, self.artifact.version
+            ),
+            (SyncStatus::Updated, Some(previous)) if *previous == self.artifact.version => write!(
+                f,
+                "{} {} reinstalled",
+                self.artifact_name, self.artifact.version
+            ),
+            (SyncStatus::Updated, Some(previous)) => write!(
+                f,
+                "{} updated from {} to {}",
+                self.artifact_name, previous, self.artifact.version
+            ),
+            (SyncStatus::Updated, None) => write!(
+                f,
+                "{} {} installed",
+                self.artifact_name, self.artifact.version
+            ),
+        }?;
+        if self.dry_run {
+            write!(f, " (dry run)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Options altering the behavior of [`Binrep::sync_with_options`]
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// Pull and install the matching version even if the sync metadata says it is up to date,
+    /// an unreadable sync metadata is ignored
+    pub force: bool,
+    /// Resolve the version and report whether it would be updated, without pulling anything nor
+    /// touching the destination directory
+    pub dry_run: bool,
+    /// Only install the selected files, recorded in the sync metadata: a sync with another
+    /// filter pulls the artifact again
+    pub file_filter: FileFilter,
+    /// Keep the previously installed files and sync metadata until the exec command succeeds,
+    /// see [`exec_after_sync`]
+    pub rollback_on_exec_failure: bool,
+    /// Archive the files of the replaced version in `.binrep/history` instead of deleting them,
+    /// keeping the last `keep_history` versions for [`Binrep::rollback`] (0 deletes them).
+    /// Only the versions remembered in the sync history are archived: `keep_history` is bounded
+    /// by the `history_size` sync parameter.
+    pub keep_history: usize,
+}
+
+/// Versions kept by [`Binrep::prune`]
+#[derive(Debug, Clone)]
+pub enum PruneRetention {
+    /// The N most recent versions, according to semver ordering
+    Newest(usize),
+    /// Versions pushed within this duration, versions without push timestamp are kept
+    PushedWithin(Duration),
+}
+
+/// Outcome of [`Binrep::prune`]
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    /// Deleted versions (or to delete with `dry_run`), oldest first
+    pub pruned: Vec<Version>,
+    /// Versions whose metadata cannot be verified or that cannot be deleted, with the error
+    pub failed: Vec<(Version, Error)>,
+}
+
+/// Result of the verification of a file by [`Binrep::verify_local`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LocalFileStatus {
+    Ok,
+    /// The file is not in the directory
+    Missing,
+    /// The content of the file does not match the checksum of the artifact
+    ChecksumMismatch,
+}
+
+#[derive(Debug)]
+pub struct VerifyReport {
+    pub artifact: Artifact,
+    /// Status of each file of the artifact, in the artifact order
+    pub files: Vec<(String, LocalFileStatus)>,
+}
+
+impl VerifyReport {
+    /// True if every file is present with the expected content
+    pub fn is_ok(&self) -> bool {
+        self.files
+            .iter()
+            .all(|(_, status)| *status == LocalFileStatus::Ok)
+    }
 }
 
+/// Lower bound of the delay between two polls of [`Binrep::wait_for_version`]
+pub const MIN_WAIT_INTERVAL: Duration = Duration::from_secs(1);
+/// Upper bound of the delay between two polls of [`Binrep::wait_for_version`]
+pub const MAX_WAIT_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(thiserror::Error, Debug)]
 #[error("No version is matching the requirement {version_req}")]
-struct NoVersionMatching {
-    version_req: VersionReq,
+pub struct NoVersionMatching {
+    pub version_req: VersionReq,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("No backup of the previous sync of {0}, sync with rollback_on_exec_failure")]
+pub struct NoSyncBackup(pub String);
+
+#[derive(thiserror::Error, Debug)]
+#[error("{artifact_name} {version} is not archived in the sync history")]
+pub struct VersionNotArchived {
+    pub artifact_name: String,
+    pub version: Version,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Archived files of {artifact_name} {version} are missing or have been modified")]
+pub struct ModifiedArchivedFiles {
+    pub artifact_name: String,
+    pub version: Version,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Installed files of {artifact_name} {version} have been modified")]
+pub struct ModifiedInstalledFiles {
+    pub artifact_name: String,
+    pub version: Version,
+}
+
+/// Error of [`Binrep::list_artifact_versions`], [`Binrep::artifact`] and [`Binrep::sync`]
+#[derive(thiserror::Error, Debug)]
+pub enum BinrepError {
+    /// The artifact, or this version of it, does not exist in the repository
+    #[error("{artifact}{} not found", version.as_ref().map(|v| format!(" {}", v)).unwrap_or_default())]
+    NotFound {
+        artifact: String,
+        version: Option<Version>,
+    },
+    #[error(transparent)]
+    Backend(BackendError),
+    #[error(transparent)]
+    Config(ConfigValidationError),
+    /// Wrong or missing signature of the metadata, or wrong checksum of a file
+    #[error(transparent)]
+    Signature(RepositoryError),
+    #[error(transparent)]
+    Other(Error),
+}
+
+impl BinrepError {
+    /// Report the not found errors of the backend or of the repository as a missing
+    /// `artifact` (`version`)
+    fn not_found_as(self, artifact: &str, version: Option<&Version>) -> Self {
+        match self {
+            BinrepError::Backend(BackendError::ResourceNotFound) => BinrepError::NotFound {
+                artifact: artifact.to_string(),
+                version: version.cloned(),
+            },
+            BinrepError::Other(e)
+                if matches!(e.downcast_ref(), Some(RepositoryError::ResourceNotFound(_))) =>
+            {
+                BinrepError::NotFound {
+                    artifact: artifact.to_string(),
+                    version: version.cloned(),
+                }
+            }
+            e => e,
+        }
+    }
+}
+
+impl From<BackendError> for BinrepError {
+    fn from(e: BackendError) -> Self {
+        BinrepError::Backend(e)
+    }
+}
+
+impl From<ConfigValidationError> for BinrepError {
+    fn from(e: ConfigValidationError) -> Self {
+        BinrepError::Config(e)
+    }
+}
+
+impl From<RepositoryError> for BinrepError {
+    fn from(e: RepositoryError) -> Self {
+        match e {
+            RepositoryError::WrongIndexSignature(_)
+            | RepositoryError::MissingIndexSignature(_)
+            | RepositoryError::WrongArtifactSignature
+            | RepositoryError::WrongFileChecksum(_) => BinrepError::Signature(e),
+            e => BinrepError::Other(e.into()),
+        }
+    }
+}
+
+impl From<Error> for BinrepError {
+    fn from(e: Error) -> Self {
+        let e = match e.downcast::<BinrepError>() {
+            Ok(e) => return e,
+            Err(e) => e,
+        };
+        let e = match e.downcast::<BackendError>() {
+            Ok(e) => return e.into(),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<ConfigValidationError>() {
+            Ok(e) => return e.into(),
+            Err(e) => e,
+        };
+        match e.downcast::<RepositoryError>() {
+            Ok(e) => e.into(),
+            Err(e) => BinrepError::Other(e),
+        }
+    }
+}
+
+pub use sync::{SyncHistoryEntry, SyncMetadata};
+
+/// Version selected by [`Binrep::sync`]: the latest version matching a requirement, or the
+/// version a channel currently points to (`@stable`, see [`parse_version_selector`])
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum VersionSelector {
+    Requirement(VersionReq),
+    Channel(String),
+}
+
+impl From<VersionReq> for VersionSelector {
+    fn from(version_req: VersionReq) -> Self {
+        VersionSelector::Requirement(version_req)
+    }
+}
+
+impl From<&VersionReq> for VersionSelector {
+    fn from(version_req: &VersionReq) -> Self {
+        VersionSelector::Requirement(version_req.clone())
+    }
+}
+
+impl From<&VersionSelector> for VersionSelector {
+    fn from(selector: &VersionSelector) -> Self {
+        selector.clone()
+    }
+}
+
+impl fmt::Display for VersionSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VersionSelector::Requirement(version_req) => write!(f, "{}", version_req),
+            VersionSelector::Channel(channel) => write!(f, "@{}", channel),
+        }
+    }
 }
 
 pub fn resolve_config<P: AsRef<Path>, D: DeserializeOwned>(
     config_path: &Option<P>,
 ) -> Result<D, Error> {
-    resolve_any_config(&config_path, "config.sane")
+    resolve_any_config(&config_path, CONFIG_FILE_NAME)
+}
+
+/// Problems of a configuration found without any network call: invalid keys or publish
+/// parameters, backend options the backend cannot be created with, unresolved S3 credentials
+pub async fn validate_config(config: &Config) -> Vec<Error> {
+    let mut errors: Vec<Error> = config
+        .validation_errors()
+        .into_iter()
+        .map(Error::from)
+        .collect();
+    if let Err(e) = Repository::<NOOPProgress>::new(config.clone()) {
+        errors.push(e);
+    } else if let (BackendType::S3, Some(s3)) =
+        (&config.backend.backend_type, &config.backend.s3_backend_opt)
+    {
+        if let Err(e) = crate::backend::s3_backend::check_credentials(s3).await {
+            errors.push(e.context("Unable to resolve S3 credentials"));
+        }
+    }
+    errors
+}
+
+/// Same as [`resolve_config`], also returning the path of the config file read
+pub fn resolve_config_with_path<P: AsRef<Path>, D: DeserializeOwned>(
+    config_path: &Option<P>,
+) -> Result<(PathBuf, D), Error> {
+    crate::config_resolver::resolve_config_with_path(config_path, CONFIG_FILE_NAME)
 }
 
 impl<T> Binrep<T>
@@ -54,11 +351,34 @@ where
         Self::from_config(config)
     }
 
-    pub fn from_config(config: Config) -> Result<Binrep<T>, Error> {
+    /// Create from `config`, after applying the environment overrides
+    /// (see [`Config::apply_env_overrides`])
+    pub fn from_config(mut config: Config) -> Result<Binrep<T>, Error> {
+        config.apply_env_overrides();
+        Self::from_overridden_config(config)
+    }
+
+    /// Create from `config` as is: the environment overrides must already be applied, eg. to
+    /// apply command line overrides after them
+    pub fn from_overridden_config(config: Config) -> Result<Binrep<T>, Error> {
         let repository = Repository::new(config)?;
         Ok(Self { repository })
     }
 
+    pub(crate) fn repository_mut(&mut self) -> &mut Repository<T> {
+        &mut self.repository
+    }
+
+    #[cfg(test)]
+    pub(crate) fn from_repository(repository: Repository<T>) -> Binrep<T> {
+        Self { repository }
+    }
+
+    /// Replace the time source used for timestamps (push, sync metadata...)
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.repository.set_clock(clock);
+    }
+
     pub async fn list_artifacts(&mut self) -> Result<Artifacts, Error> {
         self.repository.list_artifacts().await
     }
@@ -67,27 +387,120 @@ where
         &mut self,
         artifact_name: &str,
         version_req: &VersionReq,
-    ) -> Result<Vec<Version>, Error> {
+    ) -> Result<Vec<Version>, BinrepError> {
         Ok(self
             .repository
             .list_artifact_versions(artifact_name)
-            .await?
+            .await
+            .map_err(|e| BinrepError::from(e).not_found_as(artifact_name, None))?
             .versions
             .into_iter()
             .filter(|v| version_req.matches(v))
             .collect())
     }
 
+    /// Versions matching `version_req` whose artifact carries all the given labels.
+    ///
+    /// The metadata of every matching version is read, unless no label is given.
+    pub async fn list_artifact_versions_with_labels(
+        &mut self,
+        artifact_name: &str,
+        version_req: &VersionReq,
+        labels: &Labels,
+    ) -> Result<Vec<Version>, Error> {
+        if labels.is_empty() {
+            return Ok(self
+                .list_artifact_versions(artifact_name, version_req)
+                .await?);
+        }
+        let mut versions = Vec::new();
+        for version in self
+            .list_artifact_versions(artifact_name, version_req)
+            .await?
+        {
+            let artifact = self.artifact(artifact_name, &version).await?;
+            if labels
+                .iter()
+                .all(|(key, value)| artifact.labels.get(key) == Some(value))
+            {
+                versions.push(version);
+            }
+        }
+        Ok(versions)
+    }
+
+    /// Versions matching `version_req` whose artifact carries all the given labels, with their
+    /// push time: `None` if the artifact was pushed without a timestamp or with an invalid one.
+    ///
+    /// The metadata of every matching version is read.
+    pub async fn list_artifact_versions_detailed(
+        &mut self,
+        artifact_name: &str,
+        version_req: &VersionReq,
+        labels: &Labels,
+    ) -> Result<Vec<(Version, Option<DateTime<Utc>>)>, BinrepError> {
+        let mut versions = Vec::new();
+        for version in self
+            .list_artifact_versions(artifact_name, version_req)
+            .await?
+        {
+            let artifact = self.artifact(artifact_name, &version).await?;
+            if !labels
+                .iter()
+                .all(|(key, value)| artifact.labels.get(key) == Some(value))
+            {
+                continue;
+            }
+            let pushed_at = artifact
+                .pushed_at
+                .as_deref()
+                .and_then(|pushed_at| DateTime::parse_from_rfc3339(pushed_at).ok())
+                .map(|pushed_at| pushed_at.with_timezone(&Utc));
+            versions.push((version, pushed_at));
+        }
+        Ok(versions)
+    }
+
     pub async fn artifact(
         &mut self,
         artifact_name: &str,
         artifact_version: &Version,
-    ) -> Result<Artifact, Error> {
+    ) -> Result<Artifact, BinrepError> {
         self.repository
             .get_artifact(artifact_name, artifact_version)
             .await
+            .map_err(|e| BinrepError::from(e).not_found_as(artifact_name, Some(artifact_version)))
+    }
+
+    /// Write a single file of an artifact to `writer`, once its checksum is verified
+    pub async fn get_file<W: std::io::Write>(
+        &mut self,
+        artifact_name: &str,
+        artifact_version: &Version,
+        file_name: &str,
+        writer: &mut W,
+    ) -> Result<File, Error> {
+        self.repository
+            .get_file(artifact_name, artifact_version, file_name, writer)
+            .await
+    }
+
+    /// Tell whether a version of an artifact exists, much cheaper than [`Binrep::artifact`]: the
+    /// metadata is neither downloaded nor verified
+    pub async fn exists(
+        &mut self,
+        artifact_name: &str,
+        artifact_version: &Version,
+    ) -> Result<bool, Error> {
+        self.repository
+            .artifact_exists(artifact_name, artifact_version)
+            .await
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name, version = %artifact_version))
+    )]
     pub async fn push<P: AsRef<Path>>(
         &mut self,
         artifact_name: &str,
@@ -99,228 +512,1971 @@ where
             .await
     }
 
-    pub async fn pull<P: AsRef<Path>>(
-        &mut self,
-        artifact_name: &str,
-        artifact_version: &Version,
-        destination_dir: P,
-        overwrite_dest: bool,
-    ) -> Result<Artifact, Error> {
-        self.repository
-            .pull_artifact(
-                artifact_name,
-                artifact_version,
-                destination_dir,
-                overwrite_dest,
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name, version = %artifact_version))
+    )]
+    pub async fn push_with_options<P: AsRef<Path>>(
+        &mut self,
+        artifact_name: &str,
+        artifact_version: &Version,
+        files: &[P],
+        options: &PushOptions,
+    ) -> Result<Artifact, Error> {
+        self.repository
+            .push_artifact_with_options(artifact_name, artifact_version, files, options)
+            .await
+    }
+
+    /// Push local files under their path in the artifact, eg. `("./build/server", "bin/server")`
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name, version = %artifact_version))
+    )]
+    pub async fn push_with_paths<P: AsRef<Path>, N: AsRef<str>>(
+        &mut self,
+        artifact_name: &str,
+        artifact_version: &Version,
+        files: &[(P, N)],
+        options: &PushOptions,
+    ) -> Result<Artifact, Error> {
+        self.repository
+            .push_artifact_with_paths(artifact_name, artifact_version, files, options)
+            .await
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name, version = %artifact_version))
+    )]
+    pub async fn pull<P: AsRef<Path>>(
+        &mut self,
+        artifact_name: &str,
+        artifact_version: &Version,
+        destination_dir: P,
+        overwrite_dest: bool,
+    ) -> Result<Artifact, Error> {
+        let options = PullOptions {
+            overwrite_dest,
+            ..Default::default()
+        };
+        self.pull_with_options(artifact_name, artifact_version, destination_dir, &options)
+            .await
+    }
+
+    /// Pull the files of an artifact selected by `options`, see
+    /// [`Repository::pull_artifact_with_options`]
+    pub async fn pull_with_options<P: AsRef<Path>>(
+        &mut self,
+        artifact_name: &str,
+        artifact_version: &Version,
+        destination_dir: P,
+        options: &PullOptions,
+    ) -> Result<Artifact, Error> {
+        self.repository
+            .pull_artifact_with_options(artifact_name, artifact_version, destination_dir, options)
+            .await
+    }
+
+    /// Pull the latest version matching the given requirement
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name, version_req = %version_req))
+    )]
+    pub async fn pull_latest_matching<P: AsRef<Path>>(
+        &mut self,
+        artifact_name: &str,
+        version_req: &VersionReq,
+        destination_dir: P,
+        overwrite_dest: bool,
+    ) -> Result<Artifact, Error> {
+        let options = PullOptions {
+            overwrite_dest,
+            ..Default::default()
+        };
+        self.pull_latest_matching_with_options(
+            artifact_name,
+            version_req,
+            destination_dir,
+            &options,
+        )
+        .await
+    }
+
+    pub async fn pull_latest_matching_with_options<P: AsRef<Path>>(
+        &mut self,
+        artifact_name: &str,
+        version_req: &VersionReq,
+        destination_dir: P,
+        options: &PullOptions,
+    ) -> Result<Artifact, Error> {
+        let version = match self.last_version(artifact_name, version_req).await? {
+            Some(max_matching_version) => max_matching_version,
+            None => Err(NoVersionMatching {
+                version_req: version_req.clone(),
+            })?,
+        };
+        self.pull_with_options(artifact_name, &version, destination_dir, options)
+            .await
+    }
+
+    /// Check the files of an artifact previously pulled in `directory` against its metadata,
+    /// whose signature is verified first
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name, version = %artifact_version))
+    )]
+    pub async fn verify_local<P: AsRef<Path>>(
+        &mut self,
+        artifact_name: &str,
+        artifact_version: &Version,
+        directory: P,
+    ) -> Result<VerifyReport, Error> {
+        let artifact = self
+            .repository
+            .get_artifact(artifact_name, artifact_version)
+            .await?;
+        let buffer_size = self.repository.config().checksum_buffer_size();
+        let files = artifact
+            .files
+            .iter()
+            .map(|file| -> Result<_, Error> {
+                let path = path_concat2(&directory, &file.name);
+                let status = if !path.is_file() {
+                    LocalFileStatus::Missing
+                } else {
+                    let digest =
+                        crypto::digest_file(&path, file.checksum_method.algorithm(), buffer_size)?;
+                    if data_encoding::BASE64.encode(digest.as_ref()) == file.checksum {
+                        LocalFileStatus::Ok
+                    } else {
+                        LocalFileStatus::ChecksumMismatch
+                    }
+                };
+                Ok((file.name.clone(), status))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(VerifyReport { artifact, files })
+    }
+
+    pub async fn last_version(
+        &mut self,
+        artifact_name: &str,
+        version_req: &VersionReq,
+    ) -> Result<Option<Version>, Error> {
+        if *version_req == VersionReq::STAR {
+            return self.repository.get_latest(artifact_name).await;
+        }
+        let mut matching_versions = self
+            .list_artifact_versions(artifact_name, version_req)
+            .await?;
+        matching_versions.sort();
+        Ok(matching_versions.into_iter().last())
+    }
+
+    /// Poll the repository until a version matching `version_req` is published. The delay
+    /// between two polls starts at `interval` (at least [`MIN_WAIT_INTERVAL`]) and doubles up
+    /// to [`MAX_WAIT_INTERVAL`].
+    ///
+    /// Returns `None` if no matching version appeared before `timeout`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name, version_req = %version_req))
+    )]
+    pub async fn wait_for_version(
+        &mut self,
+        artifact_name: &str,
+        version_req: &VersionReq,
+        timeout: Duration,
+        interval: Duration,
+    ) -> Result<Option<Version>, Error> {
+        let deadline = Instant::now() + timeout;
+        let mut interval = interval.max(MIN_WAIT_INTERVAL);
+        loop {
+            match self.last_version(artifact_name, version_req).await {
+                Ok(Some(version)) => return Ok(Some(version)),
+                Ok(None) => {}
+                // the artifact may not have been pushed yet
+                Err(e) if matches!(e.downcast_ref(), Some(BackendError::ResourceNotFound)) => {}
+                Err(e) if matches!(e.downcast_ref(), Some(BinrepError::NotFound { .. })) => {}
+                Err(e) => return Err(e),
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            info!(
+                "No version of {} matching {} yet, waiting",
+                artifact_name, version_req
+            );
+            tokio::time::sleep(interval.min(deadline - now)).await;
+            interval = (interval * 2).min(MAX_WAIT_INTERVAL);
+        }
+    }
+
+    /// The version [`Binrep::sync`] would select, without pulling anything: the latest version
+    /// matching a requirement, the configured default version requirement replacing
+    /// `latest` / `*`, or the version a channel points to.
+    ///
+    /// Fails with [`NoVersionMatching`] if no version matches.
+    pub async fn resolve<S: Into<VersionSelector>>(
+        &mut self,
+        artifact_name: &str,
+        version: S,
+    ) -> Result<Version, Error> {
+        match self.default_version_selector(artifact_name, version.into())? {
+            VersionSelector::Requirement(version_req) => {
+                match self.last_version(artifact_name, &version_req).await? {
+                    Some(max_matching_version) => Ok(max_matching_version),
+                    None => Err(NoVersionMatching { version_req })?,
+                }
+            }
+            VersionSelector::Channel(channel) => {
+                self.repository.get_channel(artifact_name, &channel).await
+            }
+        }
+    }
+
+    /// Replace a `latest` / `*` requirement by the default version requirement (or channel)
+    /// configured for the artifact, if any
+    fn default_version_selector(
+        &self,
+        artifact_name: &str,
+        selector: VersionSelector,
+    ) -> Result<VersionSelector, Error> {
+        match (
+            self.repository.config().default_version(artifact_name),
+            &selector,
+        ) {
+            (Some(default_version), VersionSelector::Requirement(version_req))
+                if *version_req == VersionReq::STAR =>
+            {
+                info!(
+                    "Using default version requirement {} for {}",
+                    default_version, artifact_name
+                );
+                parse_version_selector(default_version)
+            }
+            _ => Ok(selector),
+        }
+    }
+
+    /// Point a channel of an artifact to one of its versions, see [`Repository::set_channel`]
+    pub async fn set_channel(
+        &mut self,
+        artifact_name: &str,
+        channel: &str,
+        version: &Version,
+    ) -> Result<(), Error> {
+        self.repository
+            .set_channel(artifact_name, channel, version)
+            .await
+    }
+
+    /// Re-sign all artifacts, see [`Repository::resign_all`]
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn resign_all(&mut self, key_id: Option<&str>) -> Result<ResignReport, Error> {
+        self.repository.resign_all(key_id).await
+    }
+
+    /// Delete a version of an artifact, see [`Repository::delete_artifact_version`]
+    pub async fn delete(
+        &mut self,
+        artifact_name: &str,
+        artifact_version: &Version,
+    ) -> Result<Artifact, Error> {
+        self.repository
+            .delete_artifact_version(artifact_name, artifact_version)
+            .await
+    }
+
+    /// Delete the versions of an artifact not retained by `retention`, or only list them if
+    /// `dry_run` is set. Versions a channel points to are always kept. A version that cannot be
+    /// verified or deleted is reported and the prune goes on with the next one.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name))
+    )]
+    pub async fn prune(
+        &mut self,
+        artifact_name: &str,
+        retention: &PruneRetention,
+        dry_run: bool,
+    ) -> Result<PruneReport, Error> {
+        if let PruneRetention::Newest(0) = retention {
+            Err(anyhow::anyhow!("at least one version must be kept"))?;
+        }
+        let mut report = PruneReport::default();
+        // unfiltered: `*` would not match prereleases
+        let mut versions = self
+            .repository
+            .list_artifact_versions(artifact_name)
+            .await?
+            .versions;
+        versions.sort();
+        let mut pruned = match retention {
+            PruneRetention::Newest(keep) => {
+                versions.truncate(versions.len().saturating_sub(*keep));
+                versions
+            }
+            PruneRetention::PushedWithin(duration) => {
+                let limit = self.repository.clock().now() - chrono::Duration::from_std(*duration)?;
+                let mut pruned = Vec::new();
+                for version in versions {
+                    let artifact = match self.artifact(artifact_name, &version).await {
+                        Ok(artifact) => artifact,
+                        Err(error) => {
+                            warn!("Cannot check {} {}: {}", artifact_name, version, error);
+                            report.failed.push((version, error.into()));
+                            continue;
+                        }
+                    };
+                    match artifact
+                        .pushed_at
+                        .as_deref()
+                        .map(chrono::DateTime::parse_from_rfc3339)
+                    {
+                        Some(Ok(pushed_at)) if pushed_at < limit => pruned.push(version),
+                        _ => {}
+                    }
+                }
+                pruned
+            }
+        };
+        for (channel, version) in self.repository.list_channels(artifact_name).await? {
+            if pruned.contains(&version) {
+                info!(
+                    "Keeping {} {}, channel {} points to it",
+                    artifact_name, version, channel
+                );
+                pruned.retain(|v| v != &version);
+            }
+        }
+        if dry_run {
+            report.pruned = pruned;
+            return Ok(report);
+        }
+        for version in pruned {
+            match self.delete(artifact_name, &version).await {
+                Ok(_) => report.pruned.push(version),
+                Err(error) => {
+                    warn!("Cannot delete {} {}: {}", artifact_name, version, error);
+                    report.failed.push((version, error));
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Copy the whole repository to the backend of `destination`, see [`Repository::mirror_to`]
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn mirror(
+        &mut self,
+        destination: Config,
+        options: &MirrorOptions,
+    ) -> Result<MirrorReport, Error> {
+        let mut destination = Repository::new(destination)?;
+        self.repository.mirror_to(&mut destination, options).await
+    }
+
+    /// Check the integrity of the whole repository, see [`Repository::fsck`]
+    pub async fn fsck(&mut self, repair: bool) -> Result<FsckReport, Error> {
+        self.repository.fsck(repair).await
+    }
+
+    /// Copy a single version to the backend of `destination`, see [`Repository::promote_to`]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name, version = %version))
+    )]
+    pub async fn promote(
+        &mut self,
+        destination: Config,
+        artifact_name: &str,
+        version: &Version,
+    ) -> Result<bool, Error> {
+        let mut destination = Repository::new(destination)?;
+        self.repository
+            .promote_to(&mut destination, artifact_name, version)
+            .await
+    }
+
+    /// Install the version selected by `version` (a requirement or a channel) in
+    /// `destination_dir`, unless already installed. The version is resolved against the
+    /// repository on each sync: a channel moved to another version is followed.
+    pub async fn sync<P: AsRef<Path>, S: Into<VersionSelector>>(
+        &mut self,
+        artifact_name: &str,
+        version: S,
+        destination_dir: P,
+    ) -> Result<SyncResult, BinrepError> {
+        self.sync_with_options(
+            artifact_name,
+            version,
+            destination_dir,
+            &SyncOptions::default(),
+        )
+        .await
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(artifact = artifact_name, version_req = tracing::field::Empty, force = options.force)
+        )
+    )]
+    pub async fn sync_with_options<P: AsRef<Path>, S: Into<VersionSelector>>(
+        &mut self,
+        artifact_name: &str,
+        version: S,
+        destination_dir: P,
+        options: &SyncOptions,
+    ) -> Result<SyncResult, BinrepError> {
+        // named like the requirement of the pull and wait spans
+        let version = version.into();
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("version_req", tracing::field::display(&version));
+        self.sync_artifact(artifact_name, version, destination_dir, options)
+            .await
+            .map_err(BinrepError::from)
+    }
+
+    async fn sync_artifact<P: AsRef<Path>, S: Into<VersionSelector>>(
+        &mut self,
+        artifact_name: &str,
+        version: S,
+        destination_dir: P,
+        options: &SyncOptions,
+    ) -> Result<SyncResult, Error> {
+        if !options.dry_run {
+            file_utils::mkdirs(&destination_dir)?;
+        }
+        let history_size = self.repository.config().sync_history_size();
+        if options.keep_history > history_size {
+            warn!(
+                "Only the {} versions of the sync history can be archived, not {}",
+                history_size, options.keep_history
+            );
+        }
+
+        // only a missing artifact is reported as not found, not a file missing during the pull
+        let latest = self
+            .resolve(artifact_name, version)
+            .await
+            .map_err(|e| BinrepError::from(e).not_found_as(artifact_name, None))?;
+
+        let lock_file = if options.dry_run {
+            None
+        } else {
+            let lock_file = self.lock_sync(artifact_name, &destination_dir).await?;
+            // left by a sync killed before dropping them (eg. Ctrl-C)
+            sync::remove_stale_temp_dirs(artifact_name, &destination_dir)?;
+            Some(lock_file)
+        };
+
+        let sync_meta = match sync::read_meta(artifact_name, &destination_dir) {
+            Err(e) if options.force => {
+                warn!("Ignoring the sync metadata of {}: {}", artifact_name, e);
+                None
+            }
+            meta => meta?,
+        };
+        let previous_version = sync_meta.as_ref().map(|meta| meta.artifact.version.clone());
+        let up_to_date = match &sync_meta {
+            Some(meta)
+                if meta.artifact.version == latest
+                    && meta.file_filter == options.file_filter
+                    && !options.force =>
+            {
+                let config = self.repository.config();
+                if sync::installed_files_missing(&meta.artifact, &destination_dir) {
+                    warn!(
+                        "Installed files of {} are missing, pulling again",
+                        meta.artifact
+                    );
+                    false
+                } else if config.sync_verify_installed_files()
+                    && !sync::installed_files_intact(
+                        &meta.artifact,
+                        &destination_dir,
+                        config.checksum_buffer_size(),
+                    )
+                {
+                    if config.sync_fail_on_modified_files() {
+                        Err(ModifiedInstalledFiles {
+                            artifact_name: artifact_name.to_string(),
+                            version: latest.clone(),
+                        })?;
+                    }
+                    warn!(
+                        "Installed files of {} are modified, pulling again",
+                        meta.artifact
+                    );
+                    false
+                } else {
+                    true
+                }
+            }
+            _ => false,
+        };
+        match &sync_meta {
+            Some(meta) if up_to_date => {
+                info!("Already the latest version");
+                Ok(SyncResult {
+                    artifact_name: artifact_name.to_string(),
+                    previous_version,
+                    artifact: meta.artifact.clone(), // this is a shitty clone!
+                    status: SyncStatus::UpToDate,
+                    dry_run: options.dry_run,
+                })
+            }
+            _ if options.dry_run => {
+                info!("Dry run, {} {} not pulled", artifact_name, latest);
+                Ok(SyncResult {
+                    artifact_name: artifact_name.to_string(),
+                    previous_version,
+                    artifact: self.repository.get_artifact(artifact_name, &latest).await?,
+                    status: SyncStatus::Updated,
+                    dry_run: true,
+                })
+            }
+            meta => {
+                // pull artifact to tempdir
+                let temp_sync_dir = sync::create_temp_dir(artifact_name, &destination_dir)?;
+                let pull_options = PullOptions {
+                    overwrite_dest: true,
+                    file_filter: options.file_filter.clone(),
+                    // the temporary sync dir is not reused by the next sync
+                    partial_dir: Some(partial_download_dir(artifact_name, &destination_dir)),
+                };
+                let artifact = self
+                    .repository
+                    .pull_artifact_with_options(
+                        artifact_name,
+                        &latest,
+                        &temp_sync_dir,
+                        &pull_options,
+                    )
+                    .await?;
+                // remove existing files if any, or keep them for a rollback
+                let backup_dir = sync::get_backup_path(artifact_name, &destination_dir);
+                sync::remove_dir_if_exists(&backup_dir)?;
+                if options.rollback_on_exec_failure {
+                    mkdirs(&backup_dir)?;
+                    if meta.is_some() {
+                        std::fs::copy(
+                            sync::get_meta_path(artifact_name, &destination_dir),
+                            sync::get_meta_path(artifact_name, &backup_dir),
+                        )?;
+                    }
+                }
+                let archive_dir = match meta {
+                    Some(meta) if options.keep_history > 0 => {
+                        Some(sync::create_archive(artifact_name, &destination_dir, meta)?)
+                    }
+                    _ => None,
+                };
+                meta.as_ref()
+                    .map(|meta| meta.artifact.files.clone())
+                    .iter()
+                    .flatten()
+                    .try_for_each(|file| -> Result<(), Error> {
+                        let file_path = path_concat2(&destination_dir, &file.name);
+                        if !file_path.is_file() {
+                            return Ok(());
+                        }
+                        let archive_path = archive_dir
+                            .as_ref()
+                            .map(|archive_dir| path_concat2(archive_dir, &file.name));
+                        if let Some(archive_path) = &archive_path {
+                            file_utils::mkdirs_parent(archive_path)?;
+                        }
+                        if options.rollback_on_exec_failure {
+                            let backup_path = path_concat2(&backup_dir, &file.name);
+                            file_utils::mkdirs_parent(&backup_path)?;
+                            mv(&file_path, &backup_path)?;
+                            if let Some(archive_path) = archive_path {
+                                std::fs::copy(backup_path, archive_path)?;
+                            }
+                        } else if let Some(archive_path) = archive_path {
+                            mv(&file_path, archive_path)?;
+                        } else {
+                            std::fs::remove_file(&file_path).or::<std::io::Error>(Ok(()))?;
+                        }
+                        Ok(())
+                    })?;
+                // move temp file to final destination
+                let installed_files: Vec<PathBuf> = artifact
+                    .files
+                    .iter()
+                    .map(|file| path_concat2(&destination_dir, &file.name))
+                    .collect();
+                artifact.files.iter().zip(&installed_files).try_for_each(
+                    |(file, dst)| -> Result<(), Error> {
+                        file_utils::mkdirs_parent(dst)?;
+                        Ok(mv(path_concat2(&temp_sync_dir, &file.name), dst)?)
+                    },
+                )?;
+
+                info!("Synced to {}", artifact);
+                let mut new_meta = sync::SyncMetadata::new(
+                    artifact,
+                    options.file_filter.clone(),
+                    meta.as_ref(),
+                    self.repository.config().sync_history_size(),
+                    self.repository.clock(),
+                );
+                sync::prune_archives(
+                    artifact_name,
+                    &destination_dir,
+                    &mut new_meta,
+                    options.keep_history,
+                )?;
+                sync::write_meta(artifact_name, &destination_dir, &new_meta)?;
+                if self.repository.config().fsync() {
+                    let mut files = installed_files;
+                    files.push(sync::get_meta_path(artifact_name, &destination_dir));
+                    file_utils::sync_to_disk(&files, &destination_dir)?;
+                }
+
+                Ok(SyncResult {
+                    artifact_name: artifact_name.to_string(),
+                    previous_version,
+                    artifact: new_meta.artifact,
+                    status: SyncStatus::Updated,
+                    dry_run: false,
+                })
+            }
+        }
+    }
+
+    /// Install again a version archived by a sync with [`SyncOptions::keep_history`], nothing is
+    /// downloaded. The replaced version is archived in turn.
+    pub async fn rollback<P: AsRef<Path>>(
+        &mut self,
+        artifact_name: &str,
+        destination_dir: P,
+        version: &Version,
+    ) -> Result<SyncResult, Error> {
+        let _lock_file = self.lock_sync(artifact_name, &destination_dir).await?;
+        let archive_dir = sync::get_archive_path(artifact_name, &destination_dir, version);
+        let archived = match sync::read_meta(artifact_name, &archive_dir)? {
+            Some(archived) => archived,
+            None => Err(VersionNotArchived {
+                artifact_name: artifact_name.to_string(),
+                version: version.clone(),
+            })?,
+        };
+        let meta = sync::read_meta(artifact_name, &destination_dir)?;
+        let previous_version = meta.as_ref().map(|meta| meta.artifact.version.clone());
+        if previous_version.as_ref() == Some(version) {
+            return Ok(SyncResult {
+                artifact_name: artifact_name.to_string(),
+                previous_version,
+                artifact: archived.artifact,
+                status: SyncStatus::UpToDate,
+                dry_run: false,
+            });
+        }
+        // the installed files are left untouched if the archive is incomplete
+        if !sync::installed_files_intact(
+            &archived.artifact,
+            &archive_dir,
+            self.repository.config().checksum_buffer_size(),
+        ) {
+            Err(ModifiedArchivedFiles {
+                artifact_name: artifact_name.to_string(),
+                version: version.clone(),
+            })?;
+        }
+        if let Some(meta) = &meta {
+            let current_archive_dir = sync::create_archive(artifact_name, &destination_dir, meta)?;
+            for file in &meta.artifact.files {
+                let file_path = path_concat2(&destination_dir, &file.name);
+                if file_path.is_file() {
+                    let archive_path = path_concat2(&current_archive_dir, &file.name);
+                    file_utils::mkdirs_parent(&archive_path)?;
+                    mv(file_path, archive_path)?;
+                }
+            }
+        }
+        for file in &archived.artifact.files {
+            let file_path = path_concat2(&destination_dir, &file.name);
+            file_utils::mkdirs_parent(&file_path)?;
+            mv(path_concat2(&archive_dir, &file.name), file_path)?;
+        }
+        sync::remove_dir_if_exists(&archive_dir)?;
+
+        info!("Rolled back to {}", archived.artifact);
+        let mut new_meta = sync::SyncMetadata::new(
+            archived.artifact,
+            archived.file_filter,
+            meta.as_ref(),
+            self.repository.config().sync_history_size(),
+            self.repository.clock(),
+        );
+        // the archives are pruned by the next sync
+        sync::prune_archives(artifact_name, &destination_dir, &mut new_meta, usize::MAX)?;
+        sync::write_meta(artifact_name, &destination_dir, &new_meta)?;
+        Ok(SyncResult {
+            artifact_name: artifact_name.to_string(),
+            previous_version,
+            artifact: new_meta.artifact,
+            status: SyncStatus::Updated,
+            dry_run: false,
+        })
+    }
+
+    /// Lock the sync of `artifact_name` in `destination_dir`, waiting for a concurrent sync at
+    /// most `sync_lock_timeout`
+    async fn lock_sync<P: AsRef<Path>>(
+        &self,
+        artifact_name: &str,
+        destination_dir: P,
+    ) -> Result<LockFile<PathBuf>, Error> {
+        mkdirs(&destination_dir)?;
+        let lock_file_path = path_concat2(
+            &destination_dir,
+            format!(".{}.binrep-sync.lock", artifact_name),
+        );
+        LockFile::create_and_lock_timeout(
+            lock_file_path,
+            self.repository.config().sync_lock_timeout(),
+        )
+        .await
+    }
+}
+
+mod sync {
+    use crate::clock::Clock;
+    use crate::crypto;
+    use crate::file_utils;
+    use crate::metadata::Artifact;
+    use crate::repository::FileFilter;
+    use anyhow::Error;
+    use chrono::prelude::*;
+    use semver::Version;
+    use serde::{Deserialize, Serialize};
+    use std::fs::File;
+    use std::io::{ErrorKind, Write};
+    use std::path::{Path, PathBuf};
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+    pub struct SyncMetadata {
+        pub last_updated: String,
+        /// previously synced versions, most recent first
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub history: Vec<SyncHistoryEntry>,
+        pub artifact: Artifact,
+        /// files installed by the sync, the files of `artifact` are the selected ones
+        #[serde(default, skip_serializing_if = "FileFilter::is_empty")]
+        pub file_filter: FileFilter,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+    pub struct SyncHistoryEntry {
+        pub version: Version,
+        pub last_updated: String,
+        /// The files of this version are archived in the destination directory, see
+        /// [`super::Binrep::rollback`]
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        pub archived: bool,
+    }
+
+    impl SyncMetadata {
+        /// Create the metadata of a new sync, the previous sync (if any) is pushed
+        /// in front of the history which is then trimmed to `history_size` entries.
+        pub fn new(
+            artifact: Artifact,
+            file_filter: FileFilter,
+            previous: Option<&SyncMetadata>,
+            history_size: usize,
+            clock: &dyn Clock,
+        ) -> Self {
+            let history = previous
+                .map(|previous| {
+                    std::iter::once(SyncHistoryEntry {
+                        version: previous.artifact.version.clone(),
+                        last_updated: previous.last_updated.clone(),
+                        archived: false,
+                    })
+                    .chain(previous.history.iter().cloned())
+                    .take(history_size)
+                    .collect()
+                })
+                .unwrap_or_default();
+            Self {
+                artifact,
+                last_updated: clock.now().to_rfc3339(),
+                history,
+                file_filter,
+            }
+        }
+    }
+
+    /// Tell whether a file of a synced artifact is missing from `dir`, without reading the
+    /// files present
+    pub fn installed_files_missing<P: AsRef<Path>>(artifact: &Artifact, dir: P) -> bool {
+        artifact
+            .files
+            .iter()
+            .any(|file| !file_utils::path_concat2(&dir, &file.name).is_file())
+    }
+
+    /// Check the files of a synced artifact are still present in `dir` with the expected
+    /// checksums
+    pub fn installed_files_intact<P: AsRef<Path>>(
+        artifact: &Artifact,
+        dir: P,
+        buffer_size: usize,
+    ) -> bool {
+        artifact.files.iter().all(|file| {
+            let file_path = file_utils::path_concat2(&dir, &file.name);
+            match crypto::digest_file(&file_path, file.checksum_method.algorithm(), buffer_size) {
+                Ok(digest) => data_encoding::BASE64.encode(digest.as_ref()) == file.checksum,
+                Err(e) => {
+                    info!("Unable to checksum {}: {}", file_path.display(), e);
+                    false
+                }
+            }
+        })
+    }
+
+    /// Directory of the archived versions of an artifact synced in `dir`
+    fn get_archives_path<P: AsRef<Path>>(artifact_name: &str, dir: P) -> PathBuf {
+        file_utils::path_concat2(dir, format!(".binrep/history/{}", artifact_name))
+    }
+
+    pub fn get_archive_path<P: AsRef<Path>>(
+        artifact_name: &str,
+        dir: P,
+        version: &Version,
+    ) -> PathBuf {
+        file_utils::path_concat2(get_archives_path(artifact_name, dir), version.to_string())
+    }
+
+    /// Create an empty archive of the version installed by `meta`, its files are to be moved
+    /// there. Returns the archive directory.
+    pub fn create_archive<P: AsRef<Path>>(
+        artifact_name: &str,
+        dir: P,
+        meta: &SyncMetadata,
+    ) -> Result<PathBuf, Error> {
+        let archive_dir = get_archive_path(artifact_name, dir, &meta.artifact.version);
+        remove_dir_if_exists(&archive_dir)?;
+        file_utils::mkdirs(&archive_dir)?;
+        write_meta(artifact_name, &archive_dir, meta)?;
+        Ok(archive_dir)
+    }
+
+    /// Keep the archives of the `keep` most recent versions of the history, delete the others
+    /// and flag the kept ones in the history
+    pub fn prune_archives<P: AsRef<Path>>(
+        artifact_name: &str,
+        dir: P,
+        meta: &mut SyncMetadata,
+        keep: usize,
+    ) -> Result<(), Error> {
+        let mut kept: Vec<String> = Vec::new();
+        for entry in &mut meta.history {
+            let version = entry.version.to_string();
+            entry.archived = !kept.contains(&version)
+                && kept.len() < keep
+                && get_archive_path(artifact_name, &dir, &entry.version).is_dir();
+            if entry.archived {
+                kept.push(version);
+            }
+        }
+        let archives_dir = get_archives_path(artifact_name, &dir);
+        if archives_dir.is_dir() {
+            for archive in std::fs::read_dir(&archives_dir)? {
+                let archive = archive?;
+                if !kept.contains(&archive.file_name().to_string_lossy().into_owned()) {
+                    std::fs::remove_dir_all(archive.path())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Directory keeping the previously installed files until the exec command succeeds
+    pub fn get_backup_path<P: AsRef<Path>>(artifact_name: &str, dir: P) -> PathBuf {
+        file_utils::path_concat2(dir, format!(".{}.binrep-backup", artifact_name))
+    }
+
+    /// Prefix of the temporary directories the files of `artifact_name` are pulled to
+    fn temp_dir_prefix(artifact_name: &str) -> String {
+        format!(".{}.binrep-sync-", artifact_name)
+    }
+
+    pub fn create_temp_dir<P: AsRef<Path>>(
+        artifact_name: &str,
+        dir: P,
+    ) -> Result<tempfile::TempDir, std::io::Error> {
+        tempfile::Builder::new()
+            .prefix(&temp_dir_prefix(artifact_name))
+            .tempdir_in(dir)
+    }
+
+    /// Remove the temporary directories of interrupted syncs of `artifact_name`, the sync lock
+    /// must be held
+    pub fn remove_stale_temp_dirs<P: AsRef<Path>>(
+        artifact_name: &str,
+        dir: P,
+    ) -> Result<(), std::io::Error> {
+        let prefix = temp_dir_prefix(artifact_name);
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with(&prefix)
+                && entry.file_type()?.is_dir()
+            {
+                warn!(
+                    "Removing {}, left by an interrupted sync",
+                    entry.path().display()
+                );
+                std::fs::remove_dir_all(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove_dir_if_exists(dir: &Path) -> Result<(), std::io::Error> {
+        match std::fs::remove_dir_all(dir) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn get_meta_path<P: AsRef<Path>>(artifact_name: &str, dir: P) -> PathBuf {
+        let mut ret = PathBuf::from(dir.as_ref());
+        let filename: String = vec![".", artifact_name, "_sync.sane"].into_iter().collect();
+        ret.push(filename);
+        ret
+    }
+
+    pub fn read_meta<P: AsRef<Path>>(
+        artifact_name: &str,
+        dir: P,
+    ) -> Result<Option<SyncMetadata>, Error> {
+        let meta_file_path = get_meta_path(artifact_name, dir);
+        match std::fs::metadata(&meta_file_path) {
+            Ok(_) => Ok(Some(file_utils::read_sane_from_file(&meta_file_path)?)),
+            Err(ioe) => match ioe.kind() {
+                ErrorKind::NotFound => Ok(None),
+                _ => Err(ioe)?,
+            },
+        }
+    }
+
+    pub fn write_meta<P: AsRef<Path>>(
+        artifact_name: &str,
+        dir: P,
+        meta: &SyncMetadata,
+    ) -> Result<(), Error> {
+        file_utils::write_sane_to_file(get_meta_path(artifact_name, dir), meta)
+    }
+}
+
+/// Read the sync metadata of an artifact synced in the given directory
+pub fn read_sync_metadata<P: AsRef<Path>>(
+    artifact_name: &str,
+    destination_dir: P,
+) -> Result<Option<SyncMetadata>, Error> {
+    sync::read_meta(artifact_name, destination_dir)
+}
+
+/// Run the exec command of an updated artifact (see [`exec`]). If the command fails and the
+/// sync kept a backup (see [`SyncOptions::rollback_on_exec_failure`]), the previously installed
+/// version is restored before returning the error.
+pub fn exec_after_sync<P: AsRef<Path>>(
+    sync: &SyncResult,
+    destination_dir: P,
+    command: &Option<String>,
+    options: &ExecOptions,
+) -> Result<Option<Vec<Line>>, Error> {
+    let result = exec(
+        &sync.artifact_name,
+        &sync.artifact,
+        &destination_dir,
+        command,
+        options,
+    );
+    let backup_dir = sync::get_backup_path(&sync.artifact_name, &destination_dir);
+    if !backup_dir.is_dir() {
+        return result;
+    }
+    match &result {
+        Ok(_) => sync::remove_dir_if_exists(&backup_dir)?,
+        Err(e) => match rollback_sync(&sync.artifact_name, &destination_dir) {
+            Ok(Some(version)) => warn!(
+                "{} failed, {} rolled back to {}",
+                e, sync.artifact_name, version
+            ),
+            Ok(None) => warn!("{} failed, {} uninstalled", e, sync.artifact_name),
+            Err(rollback_error) => error!(
+                "Unable to roll back {}: {}",
+                sync.artifact_name, rollback_error
+            ),
+        },
+    }
+    result
+}
+
+/// Restore the files and the sync metadata installed before the last sync, kept by a sync with
+/// [`SyncOptions::rollback_on_exec_failure`].
+///
+/// Returns the restored version, none if nothing was installed before: the files of the last
+/// sync are then only removed.
+pub fn rollback_sync<P: AsRef<Path>>(
+    artifact_name: &str,
+    destination_dir: P,
+) -> Result<Option<Version>, Error> {
+    let backup_dir = sync::get_backup_path(artifact_name, &destination_dir);
+    if !backup_dir.is_dir() {
+        Err(NoSyncBackup(artifact_name.to_string()))?;
+    }
+    if let Some(meta) = sync::read_meta(artifact_name, &destination_dir)? {
+        for file in &meta.artifact.files {
+            let file_path = path_concat2(&destination_dir, &file.name);
+            if file_path.is_file() {
+                std::fs::remove_file(&file_path)?;
+            }
+        }
+    }
+    let previous = sync::read_meta(artifact_name, &backup_dir)?;
+    match &previous {
+        Some(meta) => {
+            for file in &meta.artifact.files {
+                let backup_path = path_concat2(&backup_dir, &file.name);
+                if backup_path.is_file() {
+                    let file_path = path_concat2(&destination_dir, &file.name);
+                    file_utils::mkdirs_parent(&file_path)?;
+                    mv(backup_path, file_path)?;
+                }
+            }
+            sync::write_meta(artifact_name, &destination_dir, meta)?;
+        }
+        None => std::fs::remove_file(sync::get_meta_path(artifact_name, &destination_dir))?,
+    }
+    sync::remove_dir_if_exists(&backup_dir)?;
+    Ok(previous.map(|meta| meta.artifact.version))
+}
+
+pub fn parse_version_req(input: &str) -> Result<VersionReq, Error> {
+    Ok(match input {
+        v if v == "latest" || v == "any" => VersionReq::STAR,
+        v => VersionReq::parse(v)?,
+    })
+}
+
+/// Parse a version requirement (see [`parse_version_req`]) or a channel prefixed by `@`,
+/// eg. `@stable`
+pub fn parse_version_selector(input: &str) -> Result<VersionSelector, Error> {
+    Ok(match input.strip_prefix('@') {
+        Some(channel) => VersionSelector::Channel(channel.to_string()),
+        None => VersionSelector::Requirement(parse_version_req(input)?),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::config::RepositoryLayout;
+    use crate::file_utils::path_concat2;
+    use crate::progress::NOOPProgress;
+    use semver::Comparator;
+    use std::fs::metadata;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    static ANAME: &'static str = "binrep";
+
+    #[tokio::test]
+    async fn test_binrep() {
+        let mut br: Binrep<NOOPProgress> =
+            Binrep::from_config(Config::create_file_test_config()).unwrap();
+        let v1 = Version::parse("1.0.0").unwrap();
+        let v12 = Version::parse("1.2.0").unwrap();
+        let v2 = Version::parse("2.0.0").unwrap();
+
+        br.push(ANAME, &v1, &vec!["Cargo.toml"]).await.unwrap();
+
+        let dest_sync = tempfile::tempdir().unwrap();
+
+        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        assert_eq!(v1, sr.artifact.version);
+        assert_eq!(None, sr.previous_version);
+        assert_eq!(format!("{} 1.0.0 installed", ANAME), sr.to_string());
+
+        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert_eq!(SyncStatus::UpToDate, sr.status);
+        assert_eq!(v1, sr.artifact.version);
+        assert_eq!(format!("{} 1.0.0 is up to date", ANAME), sr.to_string());
+
+        br.push(ANAME, &v12, &vec!["Cargo.toml"]).await.unwrap();
+        br.push(ANAME, &v2, &vec!["Cargo.toml"]).await.unwrap();
+
+        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        assert_eq!(v2, sr.artifact.version);
+        assert_eq!(Some(v1), sr.previous_version);
+        assert_eq!(
+            format!("{} updated from 1.0.0 to 2.0.0", ANAME),
+            sr.to_string()
+        );
+
+        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert_eq!(SyncStatus::UpToDate, sr.status);
+        assert_eq!(v2, sr.artifact.version);
+
+        // try downgrading to 1.2.x
+        let sr = br
+            .sync(ANAME, &VersionReq::parse("~1").unwrap(), &dest_sync)
+            .await
+            .unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        assert_eq!(v12, sr.artifact.version);
+        let sr = br
+            .sync(ANAME, &VersionReq::parse("~1").unwrap(), &dest_sync)
+            .await
+            .unwrap();
+        assert_eq!(SyncStatus::UpToDate, sr.status);
+        assert_eq!(v12, sr.artifact.version);
+
+        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        assert_eq!(v2, sr.artifact.version);
+    }
+
+    #[tokio::test]
+    async fn test_sync_stale_temp_dirs() {
+        let mut br: Binrep<NOOPProgress> =
+            Binrep::from_config(Config::create_file_test_config()).unwrap();
+        br.push(ANAME, &Version::new(1, 0, 0), &["Cargo.toml"])
+            .await
+            .unwrap();
+        let dest_sync = tempfile::tempdir().unwrap();
+        let stale = dest_sync
+            .path()
+            .join(format!(".{}.binrep-sync-a1B2c3", ANAME));
+        // a concurrent sync of another artifact
+        let other = dest_sync.path().join(".other.binrep-sync-a1B2c3");
+        for dir in [&stale, &other] {
+            mkdirs(dir).unwrap();
+            std::fs::write(dir.join("Cargo.toml"), "partial").unwrap();
+        }
+
+        br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert!(!stale.exists());
+        assert!(other.exists());
+        assert!(dest_sync.path().join("Cargo.toml").exists());
+    }
+
+    #[tokio::test]
+    async fn test_sync_force() {
+        let mut config = Config::create_file_test_config();
+        // installed files verification would heal the drift by itself
+        config.sync_parameters = Some(crate::config::SyncParameters {
+            verify_installed_files: Some(false),
+            ..Default::default()
+        });
+        let mut br: Binrep<NOOPProgress> = Binrep::from_config(config).unwrap();
+        let v1 = Version::parse("1.0.0").unwrap();
+        br.push(ANAME, &v1, &["Cargo.toml"]).await.unwrap();
+
+        let dest_sync = tempfile::tempdir().unwrap();
+        let synced_file = dest_sync.path().join("Cargo.toml");
+        br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+
+        // drift the deployment: the metadata still says it is up to date
+        std::fs::write(&synced_file, "drifted").unwrap();
+        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert_eq!(SyncStatus::UpToDate, sr.status);
+        assert_eq!("drifted", std::fs::read_to_string(&synced_file).unwrap());
+
+        let options = super::SyncOptions {
+            force: true,
+            ..Default::default()
+        };
+        let sr = br
+            .sync_with_options(ANAME, &VersionReq::STAR, &dest_sync, &options)
+            .await
+            .unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        assert_eq!(v1, sr.artifact.version);
+        assert_eq!(
+            std::fs::read("Cargo.toml").unwrap(),
+            std::fs::read(&synced_file).unwrap()
+        );
+
+        // a broken metadata fails the sync, unless forced
+        let meta_path = super::sync::get_meta_path(ANAME, &dest_sync);
+        std::fs::write(&meta_path, "garbage").unwrap();
+        br.sync(ANAME, &VersionReq::STAR, &dest_sync)
+            .await
+            .unwrap_err();
+        let sr = br
+            .sync_with_options(ANAME, &VersionReq::STAR, &dest_sync, &options)
+            .await
+            .unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        assert_eq!(None, sr.previous_version);
+        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert_eq!(SyncStatus::UpToDate, sr.status);
+    }
+
+    #[tokio::test]
+    async fn test_validate_config() {
+        assert!(super::validate_config(&Config::create_file_test_config())
+            .await
+            .is_empty());
+        assert!(
+            super::validate_config(&Config::create_file_test_config_ed25519_publish())
+                .await
+                .is_empty()
+        );
+
+        let mut config = Config::create_file_test_config();
+        config
+            .hmac_keys
+            .get_or_insert_with(Default::default)
+            .insert("broken".to_string(), "not base64!".to_string());
+        config.backend.file_backend_opt = None;
+        let errors = super::validate_config(&config).await;
+        assert_eq!(2, errors.len(), "{:?}", errors);
+    }
+
+    #[tokio::test]
+    async fn test_sync_dry_run() {
+        let mut br: Binrep<NOOPProgress> =
+            Binrep::from_config(Config::create_file_test_config()).unwrap();
+        let v1 = Version::parse("1.0.0").unwrap();
+        let v2 = Version::parse("2.0.0").unwrap();
+        br.push(ANAME, &v1, &["Cargo.toml"]).await.unwrap();
+
+        let dest = tempfile::tempdir().unwrap();
+        let dest_sync = dest.path().join("not-created");
+        let options = super::SyncOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        let sr = br
+            .sync_with_options(ANAME, &VersionReq::STAR, &dest_sync, &options)
+            .await
+            .unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        assert_eq!(v1, sr.artifact.version);
+        assert!(sr.dry_run);
+        assert!(!dest_sync.exists());
+
+        br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        let sr = br
+            .sync_with_options(ANAME, &VersionReq::STAR, &dest_sync, &options)
+            .await
+            .unwrap();
+        assert_eq!(SyncStatus::UpToDate, sr.status);
+
+        br.push(ANAME, &v2, &["Cargo.toml"]).await.unwrap();
+        let sr = br
+            .sync_with_options(ANAME, &VersionReq::STAR, &dest_sync, &options)
+            .await
+            .unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        assert_eq!(v2, sr.artifact.version);
+        assert_eq!(Some(v1.clone()), sr.previous_version);
+        assert_eq!(
+            v1,
+            super::read_sync_metadata(ANAME, &dest_sync)
+                .unwrap()
+                .unwrap()
+                .artifact
+                .version
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sync_file_filter() {
+        let mut br: Binrep<NOOPProgress> =
+            Binrep::from_config(Config::create_file_test_config()).unwrap();
+        let v1 = Version::parse("1.0.0").unwrap();
+        br.push(ANAME, &v1, &["Cargo.toml", "src/lib.rs"])
+            .await
+            .unwrap();
+
+        let dest_sync = tempfile::tempdir().unwrap();
+        let options = super::SyncOptions {
+            file_filter: super::FileFilter::new(vec!["*.rs".to_string()], vec![]).unwrap(),
+            ..Default::default()
+        };
+        let sr = br
+            .sync_with_options(ANAME, &VersionReq::STAR, &dest_sync, &options)
+            .await
+            .unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        assert_eq!(1, sr.artifact.files.len());
+        assert!(dest_sync.path().join("lib.rs").is_file());
+        assert!(!dest_sync.path().join("Cargo.toml").exists());
+        let sr = br
+            .sync_with_options(ANAME, &VersionReq::STAR, &dest_sync, &options)
+            .await
+            .unwrap();
+        assert_eq!(SyncStatus::UpToDate, sr.status);
+
+        // a full sync after a partial one pulls everything
+        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        assert!(dest_sync.path().join("Cargo.toml").is_file());
+        let meta = super::read_sync_metadata(ANAME, &dest_sync)
+            .unwrap()
+            .unwrap();
+        assert!(meta.file_filter.is_empty());
+        assert_eq!(2, meta.artifact.files.len());
+    }
+
+    #[tokio::test]
+    async fn test_sync_rollback() {
+        let mut br: Binrep<NOOPProgress> =
+            Binrep::from_config(Config::create_file_test_config()).unwrap();
+        let v1 = Version::parse("1.0.0").unwrap();
+        let v2 = Version::parse("2.0.0").unwrap();
+        br.push(ANAME, &v1, &["Cargo.toml"]).await.unwrap();
+        br.push_with_paths(
+            ANAME,
+            &v2,
+            &[("src/lib.rs", "Cargo.toml"), ("src/binrep.rs", "binrep.rs")],
+            &PushOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let dest_sync = tempfile::tempdir().unwrap();
+        let synced_file = dest_sync.path().join("Cargo.toml");
+        let options = super::SyncOptions {
+            rollback_on_exec_failure: true,
+            ..Default::default()
+        };
+        br.sync_with_options(
+            ANAME,
+            &VersionReq::parse("~1").unwrap(),
+            &dest_sync,
+            &options,
+        )
+        .await
+        .unwrap();
+        let sr = br
+            .sync_with_options(ANAME, &VersionReq::STAR, &dest_sync, &options)
+            .await
+            .unwrap();
+        assert_eq!(v2, sr.artifact.version);
+        assert!(super::exec_after_sync(
+            &sr,
+            &dest_sync,
+            &Some("false".to_string()),
+            &Default::default()
+        )
+        .is_err());
+        assert_eq!(
+            std::fs::read("Cargo.toml").unwrap(),
+            std::fs::read(&synced_file).unwrap()
+        );
+        assert!(!dest_sync.path().join("binrep.rs").exists());
+        let meta = super::read_sync_metadata(ANAME, &dest_sync)
+            .unwrap()
+            .unwrap();
+        assert_eq!(v1, meta.artifact.version);
+        assert!(super::rollback_sync(ANAME, &dest_sync).is_err());
+
+        // the failed version is pulled again, and kept once the command succeeds
+        let sr = br
+            .sync_with_options(ANAME, &VersionReq::STAR, &dest_sync, &options)
+            .await
+            .unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        super::exec_after_sync(
+            &sr,
+            &dest_sync,
+            &Some("true".to_string()),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read("src/lib.rs").unwrap(),
+            std::fs::read(&synced_file).unwrap()
+        );
+        assert!(!super::sync::get_backup_path(ANAME, &dest_sync).exists());
+    }
+
+    #[tokio::test]
+    async fn test_sync_keep_history() {
+        let mut br: Binrep<NOOPProgress> =
+            Binrep::from_config(Config::create_file_test_config()).unwrap();
+        let versions: Vec<Version> = (1..=3).map(|major| Version::new(major, 0, 0)).collect();
+        let sources = ["Cargo.toml", "src/lib.rs", "src/binrep.rs"];
+        for (version, source) in versions.iter().zip(&sources) {
+            br.push_with_paths(ANAME, version, &[(source, "file")], &PushOptions::default())
+                .await
+                .unwrap();
+        }
+
+        let dest_sync = tempfile::tempdir().unwrap();
+        let synced_file = dest_sync.path().join("file");
+        let options = super::SyncOptions {
+            keep_history: 1,
+            ..Default::default()
+        };
+        for version in &versions {
+            let requirement = VersionReq::parse(&format!("={}", version)).unwrap();
+            br.sync_with_options(ANAME, &requirement, &dest_sync, &options)
+                .await
+                .unwrap();
+        }
+        let meta = super::read_sync_metadata(ANAME, &dest_sync)
+            .unwrap()
+            .unwrap();
+        assert!(meta.history[0].archived);
+        assert!(!meta.history[1].archived);
+        let v1_archive = super::sync::get_archive_path(ANAME, &dest_sync, &versions[0]);
+        assert!(!v1_archive.exists());
+        assert!(br
+            .rollback(ANAME, &dest_sync, &versions[0])
+            .await
+            .unwrap_err()
+            .downcast_ref::<super::VersionNotArchived>()
+            .is_some());
+
+        let sr = br.rollback(ANAME, &dest_sync, &versions[1]).await.unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        assert_eq!(Some(versions[2].clone()), sr.previous_version);
+        assert_eq!(
+            std::fs::read("src/lib.rs").unwrap(),
+            std::fs::read(&synced_file).unwrap()
+        );
+        let meta = super::read_sync_metadata(ANAME, &dest_sync)
+            .unwrap()
+            .unwrap();
+        assert_eq!(versions[1], meta.artifact.version);
+        assert_eq!(versions[2], meta.history[0].version);
+        assert!(meta.history[0].archived);
+
+        // a modified archive is not installed
+        let v3_archive = super::sync::get_archive_path(ANAME, &dest_sync, &versions[2]);
+        std::fs::write(v3_archive.join("file"), "modified").unwrap();
+        assert!(br
+            .rollback(ANAME, &dest_sync, &versions[2])
+            .await
+            .unwrap_err()
+            .downcast_ref::<super::ModifiedArchivedFiles>()
+            .is_some());
+        assert_eq!(
+            std::fs::read("src/lib.rs").unwrap(),
+            std::fs::read(&synced_file).unwrap()
+        );
+        std::fs::copy("src/binrep.rs", v3_archive.join("file")).unwrap();
+
+        // and forth
+        br.rollback(ANAME, &dest_sync, &versions[2]).await.unwrap();
+        assert_eq!(
+            std::fs::read("src/binrep.rs").unwrap(),
+            std::fs::read(&synced_file).unwrap()
+        );
+        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert_eq!(SyncStatus::UpToDate, sr.status);
+    }
+
+    #[tokio::test]
+    async fn test_sync_fsync() {
+        let mut config = Config::create_file_test_config();
+        config.transfer_parameters = Some(crate::config::TransferParameters {
+            fsync: Some(true),
+            ..Default::default()
+        });
+        let mut br: Binrep<NOOPProgress> = Binrep::from_config(config).unwrap();
+        let v1 = Version::parse("1.0.0").unwrap();
+        br.push(ANAME, &v1, &["Cargo.toml"]).await.unwrap();
+
+        let dest_sync = tempfile::tempdir().unwrap();
+        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        assert!(dest_sync.path().join("Cargo.toml").is_file());
+
+        let dest_pull = tempfile::tempdir().unwrap();
+        br.pull(ANAME, &v1, &dest_pull, false).await.unwrap();
+        assert!(dest_pull.path().join("Cargo.toml").is_file());
+    }
+
+    #[tokio::test]
+    async fn test_sync_self_heal() {
+        let mut br: Binrep<NOOPProgress> =
+            Binrep::from_config(Config::create_file_test_config()).unwrap();
+        let v1 = Version::parse("1.0.0").unwrap();
+        br.push(ANAME, &v1, &["Cargo.toml", "src/lib.rs"])
+            .await
+            .unwrap();
+
+        let dest_sync = tempfile::tempdir().unwrap();
+        br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert_eq!(SyncStatus::UpToDate, sr.status);
+
+        // missing file
+        let missing = dest_sync.path().join("Cargo.toml");
+        std::fs::remove_file(&missing).unwrap();
+        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        assert!(missing.is_file());
+
+        // corrupted file
+        let corrupted = dest_sync.path().join("lib.rs");
+        std::fs::write(&corrupted, "corrupted").unwrap();
+        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        assert_eq!(
+            std::fs::read(&corrupted).unwrap(),
+            std::fs::read("src/lib.rs").unwrap()
+        );
+
+        // verification disabled
+        let mut config = Config::create_file_test_config();
+        config.sync_parameters = Some(crate::config::SyncParameters {
+            verify_installed_files: Some(false),
+            ..Default::default()
+        });
+        let mut br: Binrep<NOOPProgress> = Binrep::from_config(config).unwrap();
+        br.push(ANAME, &v1, &["Cargo.toml"]).await.unwrap();
+        let dest_sync = tempfile::tempdir().unwrap();
+        br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        let synced_file = dest_sync.path().join("Cargo.toml");
+        std::fs::write(&synced_file, "drifted").unwrap();
+        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert_eq!(SyncStatus::UpToDate, sr.status);
+        // missing files are always pulled again
+        std::fs::remove_file(&synced_file).unwrap();
+        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        assert_eq!(SyncStatus::Updated, sr.status);
+        assert!(synced_file.is_file());
+
+        // modified files are an error
+        let mut config = Config::create_file_test_config();
+        config.sync_parameters = Some(crate::config::SyncParameters {
+            fail_on_modified_files: Some(true),
+            ..Default::default()
+        });
+        let mut br: Binrep<NOOPProgress> = Binrep::from_config(config).unwrap();
+        br.push(ANAME, &v1, &["Cargo.toml"]).await.unwrap();
+        let dest_sync = tempfile::tempdir().unwrap();
+        br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        std::fs::write(dest_sync.path().join("Cargo.toml"), "drifted").unwrap();
+        let e = br
+            .sync(ANAME, &VersionReq::STAR, &dest_sync)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(e, super::BinrepError::Other(e) if e.downcast_ref::<super::ModifiedInstalledFiles>().is_some())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sync_default_version() {
+        let mut config = Config::create_file_test_config();
+        config.default_versions = Some(
+            vec![(ANAME.to_string(), "~1".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        let mut br: Binrep<NOOPProgress> = Binrep::from_config(config).unwrap();
+        let v1 = Version::parse("1.2.0").unwrap();
+        let v2 = Version::parse("2.0.0").unwrap();
+        br.push(ANAME, &v1, &["Cargo.toml"]).await.unwrap();
+        br.push(ANAME, &v2, &["Cargo.toml"]).await.unwrap();
+
+        let dest_sync = tempdir().unwrap();
+        let latest = parse_version_req("latest").unwrap();
+        let sr = br.sync(ANAME, &latest, &dest_sync).await.unwrap();
+        assert_eq!(v1, sr.artifact.version);
+
+        // explicit requirements override the default one
+        let sr = br
+            .sync(ANAME, &VersionReq::parse("^2").unwrap(), &dest_sync)
+            .await
+            .unwrap();
+        assert_eq!(v2, sr.artifact.version);
+    }
+
+    #[tokio::test]
+    async fn test_sync_pulls_once() {
+        use crate::backend::file_backend::FileBackend;
+        use crate::backend::recording_backend::{BackendOperation, RecordingBackend};
+
+        let config = Config::create_file_test_config();
+        let backend = RecordingBackend::new(FileBackend::<NOOPProgress>::new(
+            &config.backend.file_backend_opt.as_ref().unwrap().root,
+        ));
+        let operations = backend.operations();
+        let mut br = Binrep::from_repository(Repository::with_backend(config, Box::new(backend)));
+        let v1 = Version::parse("1.0.0").unwrap();
+        br.push(ANAME, &v1, &["Cargo.toml"]).await.unwrap();
+        assert!(operations.lock().unwrap().contains(&BackendOperation::Push(
+            "binrep/1.0.0/Cargo.toml".to_string()
+        )));
+
+        let dest_sync = tempdir().unwrap();
+        br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        let pulls: Vec<BackendOperation> = operations
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|operation| matches!(operation, BackendOperation::Pull(_)))
+            .cloned()
+            .collect();
+        assert_eq!(
+            vec![BackendOperation::Pull(
+                "binrep/1.0.0/Cargo.toml".to_string()
+            )],
+            pulls
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_version() {
+        let mut br: Binrep<NOOPProgress> =
+            Binrep::from_config(Config::create_file_test_config()).unwrap();
+        let timeout = Duration::from_millis(50);
+        let interval = Duration::from_millis(10);
+        let req = VersionReq::parse("^1").unwrap();
+
+        // artifact does not exist
+        assert_eq!(
+            None,
+            br.wait_for_version(ANAME, &req, timeout, interval)
+                .await
+                .unwrap()
+        );
+        // no matching version
+        br.push(ANAME, &Version::new(2, 0, 0), &["Cargo.toml"])
+            .await
+            .unwrap();
+        assert_eq!(
+            None,
+            br.wait_for_version(ANAME, &req, timeout, interval)
+                .await
+                .unwrap()
+        );
+
+        let v1 = Version::new(1, 3, 0);
+        br.push(ANAME, &v1, &["Cargo.toml"]).await.unwrap();
+        assert_eq!(
+            Some(v1),
+            br.wait_for_version(ANAME, &req, timeout, interval)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_version_zero_interval() {
+        use crate::backend::file_backend::FileBackend;
+        use crate::backend::recording_backend::RecordingBackend;
+
+        let config = Config::create_file_test_config();
+        let backend = RecordingBackend::new(FileBackend::<NOOPProgress>::new(
+            &config.backend.file_backend_opt.as_ref().unwrap().root,
+        ));
+        let operations = backend.operations();
+        let mut br = Binrep::from_repository(Repository::with_backend(config, Box::new(backend)));
+        let req = VersionReq::parse("^1").unwrap();
+        assert_eq!(
+            None,
+            br.wait_for_version(ANAME, &req, Duration::from_millis(100), Duration::ZERO)
+                .await
+                .unwrap()
+        );
+        // no busy loop: polled at the start and at the deadline
+        assert!(operations.lock().unwrap().len() <= 4);
+    }
+
+    #[tokio::test]
+    async fn test_not_found() {
+        use super::BinrepError;
+
+        let mut br: Binrep<NOOPProgress> =
+            Binrep::from_config(Config::create_file_test_config()).unwrap();
+        let v1 = Version::new(1, 0, 0);
+        let v2 = Version::new(2, 0, 0);
+        let dest_sync = tempfile::tempdir().unwrap();
+
+        let e = br
+            .list_artifact_versions(ANAME, &VersionReq::STAR)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(e, BinrepError::NotFound { artifact, version: None } if artifact == ANAME)
+        );
+        let e = br
+            .sync(ANAME, &VersionReq::STAR, &dest_sync)
+            .await
+            .unwrap_err();
+        assert!(matches!(e, BinrepError::NotFound { version: None, .. }));
+
+        br.push(ANAME, &v1, &["Cargo.toml"]).await.unwrap();
+        let e = br.artifact(ANAME, &v2).await.unwrap_err();
+        assert_eq!(format!("{} 2.0.0 not found", ANAME), e.to_string());
+        assert!(matches!(e, BinrepError::NotFound { version: Some(v), .. } if v == v2));
+        // not a missing artifact
+        let e = br
+            .sync(ANAME, &VersionReq::parse("^2").unwrap(), &dest_sync)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(e, BinrepError::Other(e) if e.downcast_ref::<super::NoVersionMatching>().is_some())
+        );
+        // a data file missing from the repository
+        let root = PathBuf::from(
+            &br.repository
+                .config()
+                .backend
+                .file_backend_opt
+                .as_ref()
+                .unwrap()
+                .root,
+        );
+        std::fs::remove_file(root.join(ANAME).join("1.0.0").join("Cargo.toml")).unwrap();
+        let e = br
+            .sync(ANAME, &VersionReq::STAR, &dest_sync)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e,
+            BinrepError::Backend(BackendError::ResourceNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_list_artifact_versions_detailed() {
+        let mut br: Binrep<NOOPProgress> =
+            Binrep::from_config(Config::create_file_test_config()).unwrap();
+        let start = chrono::Utc::now();
+        let clock = Arc::new(FixedClock::new(start));
+        br.set_clock(clock.clone());
+        let versions: Vec<Version> = ["1.0.0", "2.0.0", "1.1.0"]
+            .iter()
+            .map(|v| Version::parse(v).unwrap())
+            .collect();
+        for v in &versions {
+            br.push(ANAME, v, &["Cargo.toml"]).await.unwrap();
+            clock.advance(chrono::Duration::hours(1));
+        }
+
+        let mut detailed = br
+            .list_artifact_versions_detailed(
+                ANAME,
+                &VersionReq::parse("^1").unwrap(),
+                &Labels::new(),
             )
             .await
-    }
+            .unwrap();
+        detailed.sort();
+        assert_eq!(
+            vec![
+                (versions[0].clone(), Some(start)),
+                (
+                    versions[2].clone(),
+                    Some(start + chrono::Duration::hours(2))
+                )
+            ],
+            detailed
+        );
 
-    pub async fn last_version(
-        &mut self,
-        artifact_name: &str,
-        version_req: &VersionReq,
-    ) -> Result<Option<Version>, Error> {
-        let mut matching_versions = self
-            .list_artifact_versions(artifact_name, version_req)
-            .await?;
-        matching_versions.sort();
-        Ok(matching_versions.into_iter().last())
+        let mut options = super::PushOptions::default();
+        options
+            .labels
+            .insert("branch".to_string(), "main".to_string());
+        let v3 = Version::new(1, 2, 0);
+        br.push_with_options(ANAME, &v3, &["Cargo.toml"], &options)
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![(v3, Some(start + chrono::Duration::hours(3)))],
+            br.list_artifact_versions_detailed(ANAME, &VersionReq::STAR, &options.labels)
+                .await
+                .unwrap()
+        );
     }
 
-    pub async fn sync<P: AsRef<Path>>(
-        &mut self,
-        artifact_name: &str,
-        version_req: &VersionReq,
-        destination_dir: P,
-    ) -> Result<SyncResult, Error> {
-        file_utils::mkdirs(&destination_dir)?;
+    #[tokio::test]
+    async fn test_artifact_builder() {
+        use crate::artifact_builder::ArtifactBuilder;
 
-        let latest = match self.last_version(artifact_name, version_req).await? {
-            Some(max_matching_version) => max_matching_version,
-            None => Err(NoVersionMatching {
-                version_req: version_req.clone(),
-            })?,
-        };
+        let mut br: Binrep<NOOPProgress> =
+            Binrep::from_config(Config::create_file_test_config()).unwrap();
+        let v1 = Version::new(1, 0, 0);
+        let artifact = ArtifactBuilder::new(ANAME, v1.clone())
+            .file("app.conf", "listen = 8080\n", Some(0o600))
+            .file("data.bin", vec![0u8, 1, 2, 3], None)
+            .push(&mut br)
+            .await
+            .unwrap();
+        assert_eq!(Some(4), artifact.files[1].size);
+        assert_eq!(Some(18), artifact.total_size());
+        let mut tampered = artifact.clone();
+        tampered.files[0].size = Some(u64::MAX);
+        assert_eq!(Some(u64::MAX), tampered.total_size());
+        tampered.files[1].size = None;
+        assert_eq!(None, tampered.total_size());
 
-        mkdirs(&destination_dir)?;
-        let lock_file_path = path_concat2(
-            &destination_dir,
-            format!(".{}.binrep-sync.lock", artifact_name),
+        let dest = tempdir().unwrap();
+        br.pull(ANAME, &v1, &dest, false).await.unwrap();
+        assert_eq!(
+            "listen = 8080\n",
+            std::fs::read_to_string(dest.path().join("app.conf")).unwrap()
+        );
+        assert_eq!(
+            vec![0u8, 1, 2, 3],
+            std::fs::read(dest.path().join("data.bin")).unwrap()
         );
-        let lock_file = LockFile::create_and_lock(lock_file_path)?;
-
-        let sync_meta = sync::read_meta(artifact_name, &destination_dir)?;
-        match &sync_meta {
-            Some(meta) if meta.artifact.version == latest => {
-                info!("Already the latest version");
-                Ok(SyncResult {
-                    artifact: meta.artifact.clone(), // this is a shitty clone!
-                    status: SyncStatus::UpToDate,
-                })
-            }
-            meta => {
-                // pull artifact to tempdir
-                let temp_sync_dir = tempdir_in(&destination_dir)?;
-                let artifact = self
-                    .repository
-                    .pull_artifact(artifact_name, &latest, &temp_sync_dir, true)
-                    .await?;
-                // remove existing files if any
-                meta.as_ref()
-                    .map(|meta| meta.artifact.files.clone())
-                    .iter()
-                    .flatten()
-                    .try_for_each(|file| {
-                        let file_path = path_concat2(&destination_dir, &file.name);
-                        std::fs::metadata(&file_path)
-                            .and_then(|_| std::fs::remove_file(&file_path))
-                            .or::<std::io::Error>(Ok(()))
-                    })?;
-                // move temp file to final destination
-                artifact.files.iter().try_for_each(|file| {
-                    let src = path_concat2(&temp_sync_dir, &file.name);
-                    let dst = path_concat2(&destination_dir, &file.name);
-                    mv(src, dst)
-                })?;
-
-                info!("Synced to {}", artifact);
-                let new_meta = sync::SyncMetadata::new(artifact);
-                sync::write_meta(artifact_name, &destination_dir, &new_meta)?;
 
-                Ok(SyncResult {
-                    artifact: new_meta.artifact,
-                    status: SyncStatus::Updated,
-                })
-            }
-        }
+        assert!(ArtifactBuilder::new(ANAME, Version::new(2, 0, 0))
+            .file("../escape", "", None)
+            .push(&mut br)
+            .await
+            .is_err());
     }
-}
 
-mod sync {
-    use crate::file_utils;
-    use crate::metadata::Artifact;
-    use anyhow::Error;
-    use chrono::prelude::*;
-    use semver::Version;
-    use serde::{Deserialize, Serialize};
-    use std::fs::File;
-    use std::io::{ErrorKind, Write};
-    use std::path::{Path, PathBuf};
+    #[tokio::test]
+    async fn test_labels() {
+        for layout in &[RepositoryLayout::Standard, RepositoryLayout::Flat] {
+            let mut config = Config::create_file_test_config();
+            config.backend.layout = Some(*layout);
+            let mut br: Binrep<NOOPProgress> = Binrep::from_config(config).unwrap();
+            let v1 = Version::new(1, 0, 0);
+            let v2 = Version::new(2, 0, 0);
+            let mut options = super::PushOptions::default();
+            options
+                .labels
+                .insert("branch".to_string(), "main".to_string());
+            options
+                .labels
+                .insert("git_sha".to_string(), "ab12".to_string());
+            br.push_with_options(ANAME, &v1, &["Cargo.toml"], &options)
+                .await
+                .unwrap();
+            options
+                .labels
+                .insert("branch".to_string(), "feature".to_string());
+            br.push_with_options(ANAME, &v2, &["Cargo.toml"], &options)
+                .await
+                .unwrap();
 
-    #[derive(Serialize, Deserialize, Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
-    pub struct SyncMetadata {
-        last_updated: String,
-        pub artifact: Artifact,
-    }
+            assert_eq!(
+                options.labels,
+                br.artifact(ANAME, &v2).await.unwrap().labels
+            );
+            let mut query = Labels::new();
+            query.insert("branch".to_string(), "main".to_string());
+            assert_eq!(
+                vec![v1.clone()],
+                br.list_artifact_versions_with_labels(ANAME, &VersionReq::STAR, &query)
+                    .await
+                    .unwrap()
+            );
+            query.insert("git_sha".to_string(), "ab12".to_string());
+            query.remove("branch");
+            assert_eq!(
+                vec![v1.clone(), v2.clone()],
+                br.list_artifact_versions_with_labels(ANAME, &VersionReq::STAR, &query)
+                    .await
+                    .unwrap()
+            );
+            query.insert("git_sha".to_string(), "cd34".to_string());
+            assert!(br
+                .list_artifact_versions_with_labels(ANAME, &VersionReq::STAR, &query)
+                .await
+                .unwrap()
+                .is_empty());
 
-    impl SyncMetadata {
-        pub fn new(artifact: Artifact) -> Self {
-            Self {
-                artifact,
-                last_updated: Utc::now().to_rfc3339(),
-            }
+            options
+                .labels
+                .insert("not valid".to_string(), String::new());
+            assert!(br
+                .push_with_options(ANAME, &Version::new(3, 0, 0), &["Cargo.toml"], &options)
+                .await
+                .is_err());
         }
     }
 
-    fn get_meta_path<P: AsRef<Path>>(artifact_name: &str, dir: P) -> PathBuf {
-        let mut ret = PathBuf::from(dir.as_ref());
-        let filename: String = vec![".", artifact_name, "_sync.sane"].into_iter().collect();
-        ret.push(filename);
-        ret
-    }
-
-    pub fn read_meta<P: AsRef<Path>>(
-        artifact_name: &str,
-        dir: P,
-    ) -> Result<Option<SyncMetadata>, Error> {
-        let meta_file_path = get_meta_path(artifact_name, dir);
-        match std::fs::metadata(&meta_file_path) {
-            Ok(_) => Ok(Some(file_utils::read_sane_from_file(&meta_file_path)?)),
-            Err(ioe) => match ioe.kind() {
-                ErrorKind::NotFound => Ok(None),
-                _ => Err(ioe)?,
-            },
+    #[tokio::test]
+    async fn test_resolve() {
+        let mut config = Config::create_file_test_config();
+        let mut default_versions = std::collections::HashMap::new();
+        default_versions.insert(ANAME.to_string(), "~1".to_string());
+        config.default_versions = Some(default_versions);
+        let mut br: Binrep<NOOPProgress> = Binrep::from_config(config).unwrap();
+        for version in &["1.0.0", "1.2.0", "2.0.0"] {
+            br.push(ANAME, &Version::parse(version).unwrap(), &["Cargo.toml"])
+                .await
+                .unwrap();
         }
-    }
 
-    pub fn write_meta<P: AsRef<Path>>(
-        artifact_name: &str,
-        dir: P,
-        meta: &SyncMetadata,
-    ) -> Result<(), Error> {
-        file_utils::write_sane_to_file(get_meta_path(artifact_name, dir), meta)
+        assert_eq!(
+            Version::new(2, 0, 0),
+            br.resolve(ANAME, &VersionReq::parse(">=2").unwrap())
+                .await
+                .unwrap()
+        );
+        // the default version requirement applies, as with sync
+        assert_eq!(
+            Version::new(1, 2, 0),
+            br.resolve(ANAME, &VersionReq::STAR).await.unwrap()
+        );
+        let err = br
+            .resolve(ANAME, &VersionReq::parse("3").unwrap())
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<super::NoVersionMatching>().is_some());
     }
-}
-
-pub fn parse_version_req(input: &str) -> Result<VersionReq, Error> {
-    Ok(match input {
-        v if v == "latest" || v == "any" => VersionReq::STAR,
-        v => VersionReq::parse(v)?,
-    })
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::file_utils::path_concat2;
-    use crate::progress::NOOPProgress;
-    use semver::Comparator;
-    use std::fs::metadata;
-    use std::path::PathBuf;
-    use tempfile::tempdir;
-
-    static ANAME: &'static str = "binrep";
 
     #[tokio::test]
-    async fn test_binrep() {
+    async fn test_sync_channel() {
         let mut br: Binrep<NOOPProgress> =
             Binrep::from_config(Config::create_file_test_config()).unwrap();
-        let v1 = Version::parse("1.0.0").unwrap();
-        let v12 = Version::parse("1.2.0").unwrap();
-        let v2 = Version::parse("2.0.0").unwrap();
-
-        br.push(ANAME, &v1, &vec!["Cargo.toml"]).await.unwrap();
+        let v1 = Version::new(1, 0, 0);
+        let v2 = Version::new(2, 0, 0);
+        br.push(ANAME, &v1, &["Cargo.toml"]).await.unwrap();
+        br.push(ANAME, &v2, &["Cargo.toml"]).await.unwrap();
+        assert!(br
+            .set_channel(ANAME, "stable", &Version::new(3, 0, 0))
+            .await
+            .is_err());
+        br.set_channel(ANAME, "stable", &v1).await.unwrap();
 
+        let stable = super::parse_version_selector("@stable").unwrap();
+        assert_eq!(VersionSelector::Channel("stable".to_string()), stable);
         let dest_sync = tempfile::tempdir().unwrap();
-
-        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        let sr = br.sync(ANAME, &stable, &dest_sync).await.unwrap();
         assert_eq!(SyncStatus::Updated, sr.status);
         assert_eq!(v1, sr.artifact.version);
-
-        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        let sr = br.sync(ANAME, &stable, &dest_sync).await.unwrap();
         assert_eq!(SyncStatus::UpToDate, sr.status);
-        assert_eq!(v1, sr.artifact.version);
-
-        br.push(ANAME, &v12, &vec!["Cargo.toml"]).await.unwrap();
-        br.push(ANAME, &v2, &vec!["Cargo.toml"]).await.unwrap();
 
-        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+        // the channel is followed when promoting another version
+        br.set_channel(ANAME, "stable", &v2).await.unwrap();
+        let sr = br.sync(ANAME, &stable, &dest_sync).await.unwrap();
         assert_eq!(SyncStatus::Updated, sr.status);
         assert_eq!(v2, sr.artifact.version);
 
-        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
-        assert_eq!(SyncStatus::UpToDate, sr.status);
-        assert_eq!(v2, sr.artifact.version);
-
-        // try downgrading to 1.2.x
-        let sr = br
-            .sync(ANAME, &VersionReq::parse("~1").unwrap(), &dest_sync)
-            .await
-            .unwrap();
-        assert_eq!(SyncStatus::Updated, sr.status);
-        assert_eq!(v12, sr.artifact.version);
-        let sr = br
-            .sync(ANAME, &VersionReq::parse("~1").unwrap(), &dest_sync)
+        assert!(br
+            .sync(
+                ANAME,
+                &super::parse_version_selector("@beta").unwrap(),
+                &dest_sync
+            )
             .await
-            .unwrap();
-        assert_eq!(SyncStatus::UpToDate, sr.status);
-        assert_eq!(v12, sr.artifact.version);
-
-        let sr = br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
-        assert_eq!(SyncStatus::Updated, sr.status);
-        assert_eq!(v2, sr.artifact.version);
+            .is_err());
     }
+
     #[tokio::test]
     async fn test_alpha() {
         let mut br: Binrep<NOOPProgress> =
@@ -424,6 +2580,179 @@ mod test {
         assert_path(PathAssertion::Absent, &synced_path_v1);
         assert_path(PathAssertion::File, &synced_path_v2);
     }
+    #[tokio::test]
+    async fn test_pull_latest_matching() {
+        let mut br: Binrep<NOOPProgress> =
+            Binrep::from_config(Config::create_file_test_config()).unwrap();
+        for v in &["1.0.0", "1.2.0", "2.0.0"] {
+            br.push(ANAME, &Version::parse(v).unwrap(), &["Cargo.toml"])
+                .await
+                .unwrap();
+        }
+        let dest = tempdir().unwrap();
+        let pulled = br
+            .pull_latest_matching(ANAME, &VersionReq::parse("~1").unwrap(), &dest, true)
+            .await
+            .unwrap();
+        assert_eq!(Version::parse("1.2.0").unwrap(), pulled.version);
+        assert_path(PathAssertion::File, path_concat2(&dest, "Cargo.toml"));
+        // no sync metadata is written
+        assert!(read_sync_metadata(ANAME, &dest).unwrap().is_none());
+
+        br.pull_latest_matching(ANAME, &VersionReq::parse("^3").unwrap(), &dest, true)
+            .await
+            .expect_err("no version matching ^3");
+    }
+
+    #[tokio::test]
+    async fn test_sync_history() {
+        let mut config = Config::create_file_test_config();
+        config.sync_parameters = Some(crate::config::SyncParameters {
+            history_size: Some(2),
+            ..Default::default()
+        });
+        let mut br: Binrep<NOOPProgress> = Binrep::from_config(config).unwrap();
+        let start = chrono::Utc::now();
+        let clock = Arc::new(FixedClock::new(start));
+        br.set_clock(clock.clone());
+        let dest_sync = tempdir().unwrap();
+        let versions: Vec<Version> = ["1.0.0", "1.1.0", "1.2.0", "1.3.0"]
+            .iter()
+            .map(|v| Version::parse(v).unwrap())
+            .collect();
+        for v in &versions {
+            br.push(ANAME, v, &["Cargo.toml"]).await.unwrap();
+            br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+            clock.advance(chrono::Duration::hours(1));
+        }
+        // up to date syncs do not touch the history
+        br.sync(ANAME, &VersionReq::STAR, &dest_sync).await.unwrap();
+
+        let meta = read_sync_metadata(ANAME, &dest_sync).unwrap().unwrap();
+        assert_eq!(versions[3], meta.artifact.version);
+        assert_eq!(
+            (start + chrono::Duration::hours(3)).to_rfc3339(),
+            meta.last_updated
+        );
+        assert_eq!(
+            vec![
+                (versions[2].clone(), start + chrono::Duration::hours(2)),
+                (versions[1].clone(), start + chrono::Duration::hours(1))
+            ],
+            meta.history
+                .into_iter()
+                .map(|entry| (
+                    entry.version,
+                    chrono::DateTime::parse_from_rfc3339(&entry.last_updated)
+                        .unwrap()
+                        .with_timezone(&chrono::Utc)
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_local() {
+        let mut br: Binrep<NOOPProgress> =
+            Binrep::from_config(Config::create_file_test_config()).unwrap();
+        let version = Version::new(1, 0, 0);
+        br.push(ANAME, &version, &["Cargo.toml", "src/lib.rs"])
+            .await
+            .unwrap();
+        let dir = tempdir().unwrap();
+        br.pull(ANAME, &version, &dir, false).await.unwrap();
+        let report = br.verify_local(ANAME, &version, &dir).await.unwrap();
+        assert!(report.is_ok());
+        assert_eq!(2, report.files.len());
+
+        std::fs::write(dir.path().join("Cargo.toml"), "corrupted").unwrap();
+        std::fs::remove_file(dir.path().join("lib.rs")).unwrap();
+        let report = br.verify_local(ANAME, &version, &dir).await.unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(
+            vec![
+                ("Cargo.toml".to_string(), LocalFileStatus::ChecksumMismatch),
+                ("lib.rs".to_string(), LocalFileStatus::Missing)
+            ],
+            report.files
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prune() {
+        let config = Config::create_file_test_config();
+        let root = PathBuf::from(&config.backend.file_backend_opt.as_ref().unwrap().root);
+        let mut br: Binrep<NOOPProgress> = Binrep::from_config(config).unwrap();
+        let start = chrono::Utc::now();
+        let clock = Arc::new(FixedClock::new(start));
+        br.set_clock(clock.clone());
+        let versions: Vec<Version> = ["1.0.0", "1.10.0", "1.1.0", "2.0.0-rc.1", "2.0.0"]
+            .iter()
+            .map(|v| Version::parse(v).unwrap())
+            .collect();
+        for v in &versions {
+            br.push(ANAME, v, &["Cargo.toml"]).await.unwrap();
+            clock.advance(chrono::Duration::hours(1));
+        }
+
+        // semver ordering, prereleases before releases
+        let pruned = br
+            .prune(ANAME, &super::PruneRetention::Newest(3), true)
+            .await
+            .unwrap()
+            .pruned;
+        assert_eq!(vec![versions[0].clone(), versions[2].clone()], pruned);
+        assert_eq!(
+            5,
+            br.repository
+                .list_artifact_versions(ANAME)
+                .await
+                .unwrap()
+                .versions
+                .len()
+        );
+
+        // pushed more than 2.5 hours ago, but a channel points to 1.1.0 and the signature of
+        // 1.10.0 is wrong
+        br.repository
+            .set_channel(ANAME, "legacy", &versions[2])
+            .await
+            .unwrap();
+        let artifact_path = root.join(ANAME).join("1.10.0").join("artifact.sane");
+        let tampered = std::fs::read_to_string(&artifact_path)
+            .unwrap()
+            .replace("Cargo.toml", "Cargo.tom1");
+        std::fs::write(&artifact_path, tampered).unwrap();
+        let retention = super::PruneRetention::PushedWithin(Duration::from_secs(150 * 60));
+        let report = br.prune(ANAME, &retention, false).await.unwrap();
+        assert_eq!(vec![versions[0].clone()], report.pruned);
+        assert_eq!(1, report.failed.len());
+        assert_eq!(versions[1], report.failed[0].0);
+        assert_eq!(
+            vec![
+                versions[1].clone(),
+                versions[2].clone(),
+                versions[3].clone(),
+                versions[4].clone()
+            ],
+            br.repository
+                .list_artifact_versions(ANAME)
+                .await
+                .unwrap()
+                .versions
+        );
+        assert!(br
+            .prune(ANAME, &super::PruneRetention::Newest(5), false)
+            .await
+            .unwrap()
+            .pruned
+            .is_empty());
+        assert!(br
+            .prune(ANAME, &super::PruneRetention::Newest(0), true)
+            .await
+            .is_err());
+    }
+
     #[derive(Eq, PartialEq, Debug)]
     enum PathAssertion {
         Absent, // absent or do not have the right to read meta
@@ -2,11 +2,14 @@ use crate::file_utils;
 use crate::metadata::{ChecksumMethod, SignatureMethod};
 use anyhow::Error;
 use rusoto_core::Region;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum BackendType {
@@ -14,16 +17,253 @@ pub enum BackendType {
     File,
     #[serde(rename = "s3")]
     S3,
+    /// read only
+    #[serde(rename = "http")]
+    Http,
+    #[serde(rename = "gcs")]
+    Gcs,
+}
+
+/// How artifacts metadata are laid out in the backend
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RepositoryLayout {
+    /// `versions.sane` per artifact and `artifact.sane` per version
+    #[serde(rename = "standard")]
+    Standard,
+    /// a single `<artifact_name>.sane` per artifact holding the metadata of every version,
+    /// reduce the number of objects to fetch for small repositories
+    #[serde(rename = "flat")]
+    Flat,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(from = "RawBackend")]
 pub struct Backend {
     #[serde(rename = "type")]
     pub backend_type: BackendType,
+    pub layout: Option<RepositoryLayout>,
+    /// Maximum size in bytes of a metadata file read from the backend (default to 16MiB)
+    pub max_metadata_size: Option<u64>,
     #[serde(flatten)]
     pub file_backend_opt: Option<FileBackendOpt>,
     #[serde(flatten)]
     pub s3_backend_opt: Option<S3BackendOpt>,
+    #[serde(flatten)]
+    pub http_backend_opt: Option<HttpBackendOpt>,
+    #[serde(flatten)]
+    pub gcs_backend_opt: Option<GcsBackendOpt>,
+}
+
+/// Deserialized form of a [`Backend`]: a flattened struct consumes the fields it reads, the
+/// options of every backend type are instead read from the same fields (eg. `bucket` for S3 and
+/// GCS).
+#[derive(Deserialize)]
+struct RawBackend {
+    #[serde(rename = "type")]
+    backend_type: BackendType,
+    layout: Option<RepositoryLayout>,
+    max_metadata_size: Option<u64>,
+    #[serde(flatten)]
+    options: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Options of a backend type, none if the fields do not match
+fn backend_opt<T: DeserializeOwned>(
+    options: &serde_json::Map<String, serde_json::Value>,
+) -> Option<T> {
+    serde_json::from_value(serde_json::Value::Object(options.clone())).ok()
+}
+
+impl From<RawBackend> for Backend {
+    /// Only the options of the backend type are read: the options of other types sharing the
+    /// same fields would be serialized again (eg. `bucket` by `config show`)
+    fn from(raw: RawBackend) -> Self {
+        let backend_type = &raw.backend_type;
+        let options = &raw.options;
+        Self {
+            layout: raw.layout,
+            max_metadata_size: raw.max_metadata_size,
+            file_backend_opt: backend_opt(options)
+                .filter(|_| matches!(backend_type, BackendType::File)),
+            s3_backend_opt: backend_opt(options)
+                .filter(|_| matches!(backend_type, BackendType::S3)),
+            http_backend_opt: backend_opt(options)
+                .filter(|_| matches!(backend_type, BackendType::Http)),
+            gcs_backend_opt: backend_opt(options)
+                .filter(|_| matches!(backend_type, BackendType::Gcs)),
+            backend_type: raw.backend_type,
+        }
+    }
+}
+
+impl Backend {
+    pub fn layout(&self) -> RepositoryLayout {
+        self.layout.unwrap_or(RepositoryLayout::Standard)
+    }
+
+    pub fn max_metadata_size(&self) -> u64 {
+        self.max_metadata_size.unwrap_or(DEFAULT_MAX_METADATA_SIZE)
+    }
+}
+
+/// Parse a backend from an url, eg. `file:/srv/repo`,
+/// `s3://bucket/prefix?region=eu-west-1&profile=ci`, `gs://bucket/prefix` or
+/// `https://mirror.example.com/binrep`
+///
+/// Supported query parameters: `layout` for all backends, `region` (mandatory), `endpoint`,
+/// `path_style`, `profile`, `request_timeout_secs`, `read_timeout_secs` and `storage_class` for
+/// S3, `service_account_key` and `request_timeout_secs` for GCS, `netrc` and
+/// `request_timeout_secs` for HTTP.
+impl FromStr for Backend {
+    type Err = ConfigValidationError;
+
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| ConfigValidationError::InvalidBackendUrl {
+            url: url.to_string(),
+            reason: reason.to_string(),
+        };
+        let (location, query) = match url.split_once('?') {
+            Some((location, query)) => (location, query),
+            None => (url, ""),
+        };
+        let mut params = HashMap::new();
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = param
+                .split_once('=')
+                .ok_or_else(|| invalid("query parameters must be key=value"))?;
+            params.insert(key, value.to_string());
+        }
+        let layout = match params.remove("layout").as_deref() {
+            None => None,
+            Some("standard") => Some(RepositoryLayout::Standard),
+            Some("flat") => Some(RepositoryLayout::Flat),
+            Some(_) => Err(invalid("layout must be standard or flat"))?,
+        };
+        let request_timeout_secs =
+            |params: &mut HashMap<&str, String>| match params.remove("request_timeout_secs") {
+                Some(timeout) => timeout
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| invalid("request_timeout_secs must be a number")),
+                None => Ok(None),
+            };
+        let bucket_and_prefix = |location: &'_ str| {
+            let (bucket, prefix) = match location.split_once('/') {
+                Some((bucket, prefix)) => (bucket, Some(prefix).filter(|p| !p.is_empty())),
+                None => (location, None),
+            };
+            if bucket.is_empty() {
+                Err(invalid("missing bucket"))
+            } else {
+                Ok((bucket.to_string(), prefix.map(str::to_string)))
+            }
+        };
+        let backend = if let Some(location) = location.strip_prefix("s3://") {
+            let (bucket, prefix) = bucket_and_prefix(location)?;
+            let request_timeout_secs = request_timeout_secs(&mut params)?;
+            Backend {
+                backend_type: BackendType::S3,
+                layout,
+                max_metadata_size: None,
+                file_backend_opt: None,
+                http_backend_opt: None,
+                gcs_backend_opt: None,
+                s3_backend_opt: Some(S3BackendOpt {
+                    bucket,
+                    region: params
+                        .remove("region")
+                        .ok_or_else(|| invalid("missing region parameter"))?,
+                    endpoint: params.remove("endpoint"),
+                    path_style: match params.remove("path_style").as_deref() {
+                        None => None,
+                        Some("true") => Some(true),
+                        Some("false") => Some(false),
+                        Some(_) => Err(invalid("path_style must be true or false"))?,
+                    },
+                    profile: params.remove("profile"),
+                    // secrets are not accepted in urls, which end up in shell histories
+                    access_key_id: None,
+                    secret_access_key: None,
+                    session_token: None,
+                    request_timeout_secs,
+                    read_timeout_secs: match params.remove("read_timeout_secs") {
+                        Some(timeout) => Some(
+                            timeout
+                                .parse()
+                                .map_err(|_| invalid("read_timeout_secs must be a number"))?,
+                        ),
+                        None => None,
+                    },
+                    prefix,
+                    storage_class: params.remove("storage_class"),
+                    acl: None,
+                    server_side_encryption: None,
+                    sse_kms_key_id: None,
+                    multipart_threshold_mb: None,
+                    max_retries: None,
+                    resume_downloads: None,
+                }),
+            }
+        } else if let Some(root) = location.strip_prefix("file:") {
+            // file:///srv/repo and file:/srv/repo are both accepted
+            let root = root.strip_prefix("//").unwrap_or(root);
+            if root.is_empty() {
+                Err(invalid("missing root directory"))?;
+            }
+            Backend {
+                backend_type: BackendType::File,
+                layout,
+                max_metadata_size: None,
+                file_backend_opt: Some(FileBackendOpt {
+                    root: root.to_string(),
+                }),
+                s3_backend_opt: None,
+                http_backend_opt: None,
+                gcs_backend_opt: None,
+            }
+        } else if location.starts_with("http://") || location.starts_with("https://") {
+            Backend {
+                backend_type: BackendType::Http,
+                layout,
+                max_metadata_size: None,
+                file_backend_opt: None,
+                s3_backend_opt: None,
+                http_backend_opt: Some(HttpBackendOpt {
+                    url: location.to_string(),
+                    username: None,
+                    password: None,
+                    netrc: params.remove("netrc"),
+                    request_timeout_secs: request_timeout_secs(&mut params)?,
+                }),
+                gcs_backend_opt: None,
+            }
+        } else if let Some(location) = location.strip_prefix("gs://") {
+            let (bucket, prefix) = bucket_and_prefix(location)?;
+            Backend {
+                backend_type: BackendType::Gcs,
+                layout,
+                max_metadata_size: None,
+                file_backend_opt: None,
+                s3_backend_opt: None,
+                http_backend_opt: None,
+                gcs_backend_opt: Some(GcsBackendOpt {
+                    bucket,
+                    prefix,
+                    service_account_key: params.remove("service_account_key"),
+                    request_timeout_secs: request_timeout_secs(&mut params)?,
+                    read_timeout_secs: None,
+                }),
+            }
+        } else {
+            Err(invalid(
+                "unsupported scheme, expected file:, s3://, gs://, http:// or https://",
+            ))?
+        };
+        if let Some(key) = params.keys().next() {
+            Err(invalid(&format!("unknown parameter {}", key)))?;
+        }
+        Ok(backend)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -31,11 +271,73 @@ pub struct FileBackendOpt {
     pub root: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct S3BackendOpt {
     pub bucket: String,
     pub region: String,
+    /// Endpoint of a non AWS S3 service (eg. https://minio.example.com), `region` is then
+    /// only used to sign requests and may be any name
+    pub endpoint: Option<String>,
+    /// Address buckets in the request path (https://endpoint/bucket/key), the default and the
+    /// only supported addressing style, as required by MinIO
+    pub path_style: Option<bool>,
     pub profile: Option<String>,
+    /// Static credentials, used instead of the profile when both the key id and the secret are
+    /// set (eg. in containers without ~/.aws/credentials)
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    /// Session token of temporary static credentials
+    pub session_token: Option<String>,
+    pub request_timeout_secs: Option<u64>,
+    /// Maximum delay between two reads of a downloaded file, 30s by default
+    pub read_timeout_secs: Option<u64>,
+    /// Key prefix under which the repository is stored in the bucket
+    pub prefix: Option<String>,
+    /// Storage class of pushed artifact files (eg. STANDARD_IA, GLACIER_IR), metadata files
+    /// always use the bucket default
+    pub storage_class: Option<String>,
+    /// Canned ACL of pushed objects, default to bucket-owner-full-control
+    pub acl: Option<String>,
+    /// Server side encryption of pushed objects (AES256 or aws:kms), default to the bucket
+    /// encryption
+    pub server_side_encryption: Option<String>,
+    /// KMS key of the aws:kms server side encryption, implies `server_side_encryption = "aws:kms"`
+    pub sse_kms_key_id: Option<String>,
+    /// Artifact files bigger than this size in MB are pushed with a multipart upload, 64MB by
+    /// default
+    pub multipart_threshold_mb: Option<u64>,
+    /// Number of times a request failing with a transient error (connection error, throttling,
+    /// server error or timeout) is retried, 3 by default
+    pub max_retries: Option<u32>,
+    /// Resume interrupted downloads of artifact files with range requests instead of
+    /// restarting them, false by default
+    pub resume_downloads: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GcsBackendOpt {
+    pub bucket: String,
+    /// Object name prefix under which the repository is stored in the bucket
+    pub prefix: Option<String>,
+    /// Service account key file (json), default to the file pointed by
+    /// GOOGLE_APPLICATION_CREDENTIALS
+    pub service_account_key: Option<String>,
+    /// Maximum delay to send a request and receive the response headers, 120s by default. File
+    /// uploads are only bounded by TCP keepalive: they may take longer on slow links.
+    pub request_timeout_secs: Option<u64>,
+    /// Maximum delay between two reads of a downloaded file, 30s by default
+    pub read_timeout_secs: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpBackendOpt {
+    /// Base url of the repository, eg. https://mirror.example.com/binrep/
+    pub url: String,
+    /// Basic authentication login, takes precedence over `netrc`
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// netrc file providing the login and password of the url host, eg. ~/.netrc
+    pub netrc: Option<String>,
     pub request_timeout_secs: Option<u64>,
 }
 
@@ -45,14 +347,97 @@ pub struct PublishParameters {
     pub checksum_method: ChecksumMethod,
     pub hmac_signing_key: Option<String>,
     pub ed25519_signing_key: Option<String>,
+    /// Other signatures added to published artifacts
+    pub additional_signatures: Option<Vec<AdditionalSignature>>,
+}
+
+/// Signature added to published artifacts, besides the one of the publish parameters
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdditionalSignature {
+    pub signature_method: SignatureMethod,
+    pub hmac_signing_key: Option<String>,
+    pub ed25519_signing_key: Option<String>,
+}
+
+/// Parameters used when syncing artifacts to a local directory
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SyncParameters {
+    /// Number of previously synced versions kept in the sync metadata (default to 5)
+    pub history_size: Option<usize>,
+    /// Verify checksums of installed files when already in sync, re-pull if any file is
+    /// modified (default to true). Missing files are always pulled again.
+    #[serde(alias = "verify_on_sync")]
+    pub verify_installed_files: Option<bool>,
+    /// Fail the sync instead of pulling again modified installed files (default to false)
+    pub fail_on_modified_files: Option<bool>,
+    /// Wait for a concurrent sync of the same artifact in the same directory to release its
+    /// lock, at most this number of seconds (default to 60, 0 fails immediately)
+    pub lock_timeout_secs: Option<u64>,
+}
+
+/// Parameters used when verifying artifacts and the repository indexes
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct VerifyParameters {
+    /// Tolerance applied when checking artifact timestamps are not in the future (default to 300)
+    pub max_clock_skew_secs: Option<u64>,
+    /// Reject artifacts with an invalid timestamp instead of only logging a warning
+    pub reject_invalid_timestamps: Option<bool>,
+    /// Number of valid signatures required to accept an artifact (default to all signatures
+    /// of the artifact)
+    pub signature_quorum: Option<usize>,
+    /// Verify the signature of the artifacts and versions lists, written by publishers along
+    /// the lists since 0.16 (default to false)
+    pub verify_indexes: Option<bool>,
+    /// Keys accepted when verifying signatures, eg. the current key and retired ones during a
+    /// key rotation. Signatures made with any other key are invalid. Default to every
+    /// configured key
+    pub trusted_key_ids: Option<Vec<String>>,
+}
+
+/// Parameters used when pushing and pulling files
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TransferParameters {
+    /// Check the destination has enough free space before pulling (default to true)
+    pub check_disk_space: Option<bool>,
+    /// Size in bytes of the buffer used to read files when computing checksums (default to 1MiB,
+    /// at most 64MiB)
+    pub checksum_buffer_size: Option<usize>,
+    /// Number of files of an artifact downloaded at the same time when pulling (default to 4)
+    pub max_concurrent_downloads: Option<usize>,
+    /// Number of files of an artifact uploaded at the same time when pushing (default to 4)
+    pub max_concurrent_uploads: Option<usize>,
+    /// Report the progress of a pull for the whole artifact instead of file by file (default
+    /// to true)
+    pub aggregate_progress: Option<bool>,
+    /// Flush pulled files and the destination directory to disk once installed, so that they
+    /// survive a crash right after a pull or a sync (default to false)
+    pub fsync: Option<bool>,
 }
 
+const DEFAULT_SYNC_HISTORY_SIZE: usize = 5;
+const DEFAULT_SYNC_LOCK_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_MAX_CLOCK_SKEW_SECS: u64 = 300;
+pub const DEFAULT_CHECKSUM_BUFFER_SIZE: usize = 1024 * 1024;
+pub const MAX_CHECKSUM_BUFFER_SIZE: usize = 64 * 1024 * 1024;
+const DEFAULT_MAX_METADATA_SIZE: u64 = 16 * 1024 * 1024;
+const DEFAULT_MAX_CONCURRENT_DOWNLOADS: usize = 4;
+const DEFAULT_MAX_CONCURRENT_UPLOADS: usize = 4;
+/// Replaces secrets in [`Config::redacted`]
+const REDACTED: &str = "<redacted>";
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
     pub backend: Backend,
     pub publish_parameters: Option<PublishParameters>,
+    pub sync_parameters: Option<SyncParameters>,
+    pub verify_parameters: Option<VerifyParameters>,
+    pub transfer_parameters: Option<TransferParameters>,
+    /// Version requirement used by sync for an artifact when `latest` or `*` is requested
+    pub default_versions: Option<HashMap<String, String>>,
     pub hmac_keys: Option<HashMap<String, String>>,
     pub ed25519_keys: Option<HashMap<String, ED25519Key>>,
+    /// ASCII armored OpenPGP public keys, verifying `PGP` signatures
+    pub pgp_keys: Option<HashMap<String, String>>,
 }
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 #[serde(untagged)]
@@ -73,6 +458,15 @@ pub enum ConfigValidationError {
     #[error("Malformed ED25519 key '{cause}'")]
     MalformedED25519Key { cause: String },
 
+    #[error("PGP key reference '{key_id}' not found")]
+    PgpKeyNotFound { key_id: String },
+    #[error("no PGP keys configured!")]
+    NoPgpKeysConfigured,
+    #[error("Malformed PGP key '{cause}'")]
+    MalformedPgpKey { cause: String },
+    #[error("PGP signatures are verified only, signing is not supported")]
+    PgpSigningUnsupported,
+
     #[error("hmac key reference '{key_id}' not found")]
     HmacSigningKeyNotFound { key_id: String },
     #[error("no hmac keys configured!")]
@@ -81,25 +475,221 @@ pub enum ConfigValidationError {
     NoHmacSigningKeysConfigured,
     #[error("no publish parameters")]
     NoPublishParameters,
-    #[error("found invalid hmac key (needs to be 32/48/64 bytes long base64 encoded) {0}")]
+    #[error("invalid hmac key '{0}' (needs to be 32/48/64 bytes long base64 encoded)")]
     InvalidHmacKey(String),
     #[error("invalid base 64 encoded string: {0}")]
     InvalidBase64Encoding(String),
+    #[error("key '{key_id}' is not in the trusted keys")]
+    UntrustedKey { key_id: String },
+    #[error("invalid backend url '{url}': {reason}")]
+    InvalidBackendUrl { url: String, reason: String },
 }
 
 impl Config {
     pub fn read_from_file<P: AsRef<Path>>(file: P) -> Result<Config, Error> {
-        file_utils::read_sane_from_file(file)
+        crate::config_resolver::read_config_file(file)
+    }
+
+    pub fn sync_history_size(&self) -> usize {
+        self.sync_parameters
+            .as_ref()
+            .and_then(|params| params.history_size)
+            .unwrap_or(DEFAULT_SYNC_HISTORY_SIZE)
+    }
+
+    pub fn sync_verify_installed_files(&self) -> bool {
+        self.sync_parameters
+            .as_ref()
+            .and_then(|params| params.verify_installed_files)
+            .unwrap_or(true)
+    }
+
+    pub fn sync_fail_on_modified_files(&self) -> bool {
+        self.sync_parameters
+            .as_ref()
+            .and_then(|params| params.fail_on_modified_files)
+            .unwrap_or(false)
+    }
+
+    pub fn sync_lock_timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.sync_parameters
+                .as_ref()
+                .and_then(|params| params.lock_timeout_secs)
+                .unwrap_or(DEFAULT_SYNC_LOCK_TIMEOUT_SECS),
+        )
+    }
+
+    pub fn default_version(&self, artifact_name: &str) -> Option<&str> {
+        self.default_versions
+            .as_ref()
+            .and_then(|versions| versions.get(artifact_name))
+            .map(String::as_str)
+    }
+
+    /// Override the backend location from the environment, see [`Config::apply_overrides`]
+    pub fn apply_env_overrides(&mut self) {
+        self.apply_overrides(|name| std::env::var(name).ok())
+    }
+
+    /// Override the backend location with values given by `lookup`, applied in this order:
+    /// `BINREP_BACKEND_ROOT` (file backend root), `BINREP_S3_BUCKET` then `BINREP_S3_PREFIX`.
+    /// S3 overrides are ignored if the configuration has no S3 section.
+    pub fn apply_overrides<F: Fn(&str) -> Option<String>>(&mut self, lookup: F) {
+        if let Some(root) = lookup("BINREP_BACKEND_ROOT") {
+            debug!("Backend root overridden by BINREP_BACKEND_ROOT: {}", root);
+            self.backend.file_backend_opt = Some(FileBackendOpt { root });
+        }
+        if let Some(bucket) = lookup("BINREP_S3_BUCKET") {
+            match &mut self.backend.s3_backend_opt {
+                Some(s3) => {
+                    debug!("S3 bucket overridden by BINREP_S3_BUCKET: {}", bucket);
+                    s3.bucket = bucket;
+                }
+                None => debug!("BINREP_S3_BUCKET ignored, no S3 backend configured"),
+            }
+        }
+        if let Some(prefix) = lookup("BINREP_S3_PREFIX") {
+            match &mut self.backend.s3_backend_opt {
+                Some(s3) => {
+                    debug!("S3 prefix overridden by BINREP_S3_PREFIX: {}", prefix);
+                    s3.prefix = Some(prefix);
+                }
+                None => debug!("BINREP_S3_PREFIX ignored, no S3 backend configured"),
+            }
+        }
+    }
+
+    /// Copy of the configuration safe to print: hmac keys, ed25519 private keys and backend
+    /// secrets are replaced
+    pub fn redacted(&self) -> Config {
+        let redact = |secret: &mut Option<String>| {
+            if secret.is_some() {
+                *secret = Some(REDACTED.to_string());
+            }
+        };
+        let mut config = self.clone();
+        if let Some(s3) = &mut config.backend.s3_backend_opt {
+            redact(&mut s3.secret_access_key);
+            redact(&mut s3.session_token);
+        }
+        if let Some(http) = &mut config.backend.http_backend_opt {
+            redact(&mut http.password);
+        }
+        config.hmac_keys.iter_mut().flatten().for_each(|(_, key)| {
+            *key = REDACTED.to_string();
+        });
+        config
+            .ed25519_keys
+            .iter_mut()
+            .flatten()
+            .for_each(|(_, key)| {
+                if let ED25519Key::SignAndVerify { pkcs8 } = key {
+                    *pkcs8 = REDACTED.to_string();
+                }
+            });
+        config
+    }
+
+    /// Number of valid signatures required for an artifact carrying `signature_count` signatures
+    pub fn signature_quorum(&self, signature_count: usize) -> usize {
+        self.verify_parameters
+            .as_ref()
+            .and_then(|params| params.signature_quorum)
+            .unwrap_or(signature_count)
+            .max(1)
+    }
+
+    /// Whether signatures made with `key_id` are accepted, see `trusted_key_ids`
+    pub fn is_trusted_key(&self, key_id: &str) -> bool {
+        match self
+            .verify_parameters
+            .as_ref()
+            .and_then(|params| params.trusted_key_ids.as_ref())
+        {
+            Some(trusted_key_ids) => trusted_key_ids.iter().any(|trusted| trusted == key_id),
+            None => true,
+        }
+    }
+
+    pub fn verify_indexes(&self) -> bool {
+        self.verify_parameters
+            .as_ref()
+            .and_then(|params| params.verify_indexes)
+            .unwrap_or(false)
+    }
+
+    pub fn checksum_buffer_size(&self) -> usize {
+        self.transfer_parameters
+            .as_ref()
+            .and_then(|params| params.checksum_buffer_size)
+            .unwrap_or(DEFAULT_CHECKSUM_BUFFER_SIZE)
+            .clamp(1, MAX_CHECKSUM_BUFFER_SIZE)
+    }
+
+    pub fn max_concurrent_downloads(&self) -> usize {
+        self.transfer_parameters
+            .as_ref()
+            .and_then(|params| params.max_concurrent_downloads)
+            .unwrap_or(DEFAULT_MAX_CONCURRENT_DOWNLOADS)
+            .max(1)
+    }
+
+    pub fn max_concurrent_uploads(&self) -> usize {
+        self.transfer_parameters
+            .as_ref()
+            .and_then(|params| params.max_concurrent_uploads)
+            .unwrap_or(DEFAULT_MAX_CONCURRENT_UPLOADS)
+            .max(1)
+    }
+
+    pub fn aggregate_progress(&self) -> bool {
+        self.transfer_parameters
+            .as_ref()
+            .and_then(|params| params.aggregate_progress)
+            .unwrap_or(true)
+    }
+
+    pub fn check_disk_space(&self) -> bool {
+        self.transfer_parameters
+            .as_ref()
+            .and_then(|params| params.check_disk_space)
+            .unwrap_or(true)
+    }
+
+    pub fn fsync(&self) -> bool {
+        self.transfer_parameters
+            .as_ref()
+            .and_then(|params| params.fsync)
+            .unwrap_or(false)
+    }
+
+    pub fn max_clock_skew_secs(&self) -> u64 {
+        self.verify_parameters
+            .as_ref()
+            .and_then(|params| params.max_clock_skew_secs)
+            .unwrap_or(DEFAULT_MAX_CLOCK_SKEW_SECS)
+    }
+
+    pub fn reject_invalid_timestamps(&self) -> bool {
+        self.verify_parameters
+            .as_ref()
+            .and_then(|params| params.reject_invalid_timestamps)
+            .unwrap_or(false)
     }
     #[cfg(test)]
     pub fn create_file_test_config() -> Config {
         let dir = tempfile::tempdir().unwrap();
         let backend = Backend {
             backend_type: BackendType::File,
+            layout: None,
+            max_metadata_size: None,
             file_backend_opt: Some(FileBackendOpt {
                 root: dir.into_path().to_string_lossy().into(),
             }),
             s3_backend_opt: None,
+            http_backend_opt: None,
+            gcs_backend_opt: None,
         };
         let mut hmac_keys = HashMap::new();
         hmac_keys.insert(
@@ -112,12 +702,18 @@ impl Config {
             checksum_method: ChecksumMethod::Sha384,
             hmac_signing_key: Some("test".to_string()),
             ed25519_signing_key: None,
+            additional_signatures: None,
         });
         Config {
             backend,
             publish_parameters,
+            sync_parameters: None,
+            verify_parameters: None,
+            transfer_parameters: None,
+            default_versions: None,
             hmac_keys: Some(hmac_keys),
             ed25519_keys: None,
+            pgp_keys: None,
         }
     }
 
@@ -125,10 +721,14 @@ impl Config {
         let dir = tempfile::tempdir().unwrap();
         let backend = Backend {
             backend_type: BackendType::File,
+            layout: None,
+            max_metadata_size: None,
             file_backend_opt: Some(FileBackendOpt {
                 root: dir.into_path().to_string_lossy().into(),
             }),
             s3_backend_opt: None,
+            http_backend_opt: None,
+            gcs_backend_opt: None,
         };
         let mut ed25519_keys = HashMap::new();
         ed25519_keys.insert(
@@ -143,12 +743,18 @@ impl Config {
             checksum_method: ChecksumMethod::Sha384,
             hmac_signing_key: None,
             ed25519_signing_key: Some("test".to_string()),
+            additional_signatures: None,
         });
         Config {
             backend,
             publish_parameters,
+            sync_parameters: None,
+            verify_parameters: None,
+            transfer_parameters: None,
+            default_versions: None,
             hmac_keys: None,
             ed25519_keys: Some(ed25519_keys),
+            pgp_keys: None,
         }
     }
 }
@@ -165,4 +771,196 @@ mod test {
             .s3_backend_opt
             .unwrap();
     }
+
+    #[test]
+    fn redacted() {
+        let config = super::Config::read_from_file("config.sane").unwrap();
+        let redacted = config.redacted();
+        assert!(redacted
+            .hmac_keys
+            .unwrap()
+            .values()
+            .all(|key| key == super::REDACTED));
+        let config = super::Config::create_file_test_config_ed25519_publish().redacted();
+        assert!(config.ed25519_keys.unwrap().values().all(|key| *key
+            == super::ED25519Key::SignAndVerify {
+                pkcs8: super::REDACTED.to_string()
+            }));
+    }
+
+    #[test]
+    fn parameter_sections() {
+        let config: super::Config = sane::from_str(
+            r#"
+[backend]
+type = "file"
+root = "/srv/repo"
+
+[verify_parameters]
+signature_quorum = 1
+trusted_key_ids = ["new-key"]
+
+[transfer_parameters]
+max_concurrent_downloads = 8
+fsync = true
+"#,
+        )
+        .unwrap();
+        assert_eq!(1, config.signature_quorum(2));
+        assert!(config.is_trusted_key("new-key"));
+        assert!(!config.is_trusted_key("old-key"));
+        assert_eq!(8, config.max_concurrent_downloads());
+        assert!(config.fsync());
+        // defaults of the unset keys
+        assert!(!config.verify_indexes());
+        assert!(config.check_disk_space());
+        assert_eq!(4, config.max_concurrent_uploads());
+    }
+
+    #[test]
+    fn checksum_buffer_size_bounds() {
+        let mut config = super::Config::create_file_test_config();
+        assert_eq!(
+            super::DEFAULT_CHECKSUM_BUFFER_SIZE,
+            config.checksum_buffer_size()
+        );
+        for (configured, expected) in &[
+            (0, 1),
+            (4096, 4096),
+            (usize::MAX, super::MAX_CHECKSUM_BUFFER_SIZE),
+        ] {
+            config.transfer_parameters = Some(super::TransferParameters {
+                checksum_buffer_size: Some(*configured),
+                ..Default::default()
+            });
+            assert_eq!(*expected, config.checksum_buffer_size());
+        }
+    }
+
+    #[test]
+    fn backend_options() {
+        // fields shared by several backend types
+        let backend: super::Backend =
+            sane::from_str("type = \"gcs\"\nbucket = \"b\"\nprefix = \"binrep\"").unwrap();
+        let gcs = backend.gcs_backend_opt.unwrap();
+        assert_eq!("b", gcs.bucket);
+        assert_eq!(Some("binrep".to_string()), gcs.prefix);
+        assert!(backend.s3_backend_opt.is_none());
+
+        let backend: super::Backend = sane::from_str(
+            "type = \"s3\"\nbucket = \"b\"\nregion = \"eu-west-3\"\nrequest_timeout_secs = 10",
+        )
+        .unwrap();
+        assert!(backend.gcs_backend_opt.is_none());
+        // `bucket` is written once, eg. by `config show`
+        assert_eq!(
+            1,
+            sane::to_string(&backend).unwrap().matches("bucket").count()
+        );
+        assert_eq!("b", backend.s3_backend_opt.unwrap().bucket);
+
+        let backend: super::Backend = sane::from_str(
+            "type = \"http\"\nurl = \"https://mirror.example.com\"\nrequest_timeout_secs = 10",
+        )
+        .unwrap();
+        assert_eq!(
+            Some(10),
+            backend.http_backend_opt.unwrap().request_timeout_secs
+        );
+    }
+
+    #[test]
+    fn overrides() {
+        let lookup = |name: &str| match name {
+            "BINREP_BACKEND_ROOT" => Some("/srv/staging-repo".to_string()),
+            "BINREP_S3_BUCKET" => Some("staging-bucket".to_string()),
+            "BINREP_S3_PREFIX" => Some("staging/".to_string()),
+            _ => None,
+        };
+        let mut config = super::Config::read_from_file("config.sane").unwrap();
+        config.apply_overrides(lookup);
+        assert_eq!(
+            "/srv/staging-repo",
+            config.backend.file_backend_opt.unwrap().root
+        );
+        assert!(config.backend.s3_backend_opt.is_none());
+
+        let mut config = super::Config::read_from_file("config-s3.sane").unwrap();
+        config.apply_overrides(lookup);
+        let s3 = config.backend.s3_backend_opt.unwrap();
+        assert_eq!("staging-bucket", s3.bucket);
+        assert_eq!(Some("staging/".to_string()), s3.prefix);
+
+        let mut config = super::Config::read_from_file("config-s3.sane").unwrap();
+        let bucket = config
+            .backend
+            .s3_backend_opt
+            .as_ref()
+            .unwrap()
+            .bucket
+            .clone();
+        config.apply_overrides(|_| None);
+        assert_eq!(bucket, config.backend.s3_backend_opt.unwrap().bucket);
+    }
+
+    #[test]
+    fn backend_url() {
+        use super::{Backend, RepositoryLayout};
+        let backend: Backend = "file:/srv/repo".parse().unwrap();
+        assert_eq!("/srv/repo", backend.file_backend_opt.unwrap().root);
+        assert!(backend.s3_backend_opt.is_none());
+        let backend: Backend = "file:///srv/repo?layout=flat".parse().unwrap();
+        assert_eq!(RepositoryLayout::Flat, backend.layout());
+        assert_eq!("/srv/repo", backend.file_backend_opt.unwrap().root);
+
+        let backend: Backend = "s3://my-bucket/some/prefix?region=eu-west-1&profile=ci"
+            .parse()
+            .unwrap();
+        assert!(backend.file_backend_opt.is_none());
+        let s3 = backend.s3_backend_opt.unwrap();
+        assert_eq!("my-bucket", s3.bucket);
+        assert_eq!(Some("some/prefix".to_string()), s3.prefix);
+        assert_eq!("eu-west-1", s3.region);
+        assert_eq!(Some("ci".to_string()), s3.profile);
+        let backend: Backend = "s3://my-bucket?region=eu-west-1".parse().unwrap();
+        assert_eq!(None, backend.s3_backend_opt.unwrap().prefix);
+        let backend: Backend =
+            "s3://my-bucket?region=minio&endpoint=http://localhost:9000&path_style=true"
+                .parse()
+                .unwrap();
+        let s3 = backend.s3_backend_opt.unwrap();
+        assert_eq!(Some("http://localhost:9000".to_string()), s3.endpoint);
+        assert_eq!(Some(true), s3.path_style);
+
+        assert!("s3://my-bucket/prefix".parse::<Backend>().is_err());
+        assert!("s3://bucket?region=minio&path_style=yes"
+            .parse::<Backend>()
+            .is_err());
+        assert!("s3://?region=eu-west-1".parse::<Backend>().is_err());
+        assert!("s3://bucket?region=eu-west-1&foo=bar"
+            .parse::<Backend>()
+            .is_err());
+        assert!("file:".parse::<Backend>().is_err());
+        assert!("file:/srv/repo?layout=nested".parse::<Backend>().is_err());
+        assert!("ftp://example.com/repo".parse::<Backend>().is_err());
+
+        let backend: Backend = "https://mirror.example.com/binrep?netrc=~/.netrc"
+            .parse()
+            .unwrap();
+        let http = backend.http_backend_opt.unwrap();
+        assert_eq!("https://mirror.example.com/binrep", http.url);
+        assert_eq!(Some("~/.netrc".to_string()), http.netrc);
+
+        let backend: Backend = "gs://my-bucket/binrep?service_account_key=/etc/binrep/sa.json"
+            .parse()
+            .unwrap();
+        let gcs = backend.gcs_backend_opt.unwrap();
+        assert_eq!("my-bucket", gcs.bucket);
+        assert_eq!(Some("binrep".to_string()), gcs.prefix);
+        assert_eq!(
+            Some("/etc/binrep/sa.json".to_string()),
+            gcs.service_account_key
+        );
+        assert!("gs://?layout=flat".parse::<Backend>().is_err());
+    }
 }
@@ -5,31 +5,284 @@ use serde::de::DeserializeOwned;
 use std::path::{Path, PathBuf};
 use std::string::ToString;
 
+/// Searched after `$XDG_CONFIG_HOME/binrep/`
 const DEFAULT_CONFIG_LOCATION: &[&str] = &["~/.binrep/", "/etc/binrep/"];
 
+/// Name of the binrep configuration file
+pub const CONFIG_FILE_NAME: &str = "config.sane";
+/// Extensions of the configuration files searched in the default locations after the `.sane`
+/// one, see [`file_utils::read_config_from_file`]
+const ALTERNATIVE_CONFIG_EXTENSIONS: &[&str] = &["toml", "yaml", "yml"];
+/// Keys of shell commands, left as is: `${VAR}` is expanded by the shell when the command runs
+const UNINTERPOLATED_KEYS: &[&str] = &["exec", "command"];
+/// Environment variable pointing to the binrep configuration file, takes precedence over the
+/// provided path when resolving [`CONFIG_FILE_NAME`]
+pub const CONFIG_ENV_VAR: &str = "BINREP_CONFIG";
+
 #[derive(thiserror::Error, Debug)]
 #[error("No config file provided nor {0} file found in default locations")]
 pub struct NoConfigFileError(String);
 
+#[derive(thiserror::Error, Debug)]
+#[error("Environment variable {name} referenced in {file} is not set")]
+pub struct UnsetVariableError {
+    pub name: String,
+    pub file: String,
+}
+
 pub fn resolve_config<P: AsRef<Path>, T: AsRef<Path>, D: DeserializeOwned>(
     provided_config: &Option<P>,
     name: T,
 ) -> Result<D, Error> {
-    provided_config
-        .as_ref()
-        .map(|path| PathBuf::from(path.as_ref()))
+    resolve_config_with_path(provided_config, name).map(|(_, config)| config)
+}
+
+/// Same as [`resolve_config`], also returning the path of the file read
+pub fn resolve_config_with_path<P: AsRef<Path>, T: AsRef<Path>, D: DeserializeOwned>(
+    provided_config: &Option<P>,
+    name: T,
+) -> Result<(PathBuf, D), Error> {
+    let path = resolve_config_path(provided_config, name)?;
+    let config = read_config_file(&path)?;
+    Ok((path, config))
+}
+
+/// Read a configuration file (sane, TOML or YAML), `${VAR}` references in its string values
+/// being replaced by the value of the environment variable `VAR`, except in shell commands
+/// (`exec` and `command` keys). `$${` is a literal `${`.
+pub fn read_config_file<P: AsRef<Path>, D: DeserializeOwned>(file: P) -> Result<D, Error> {
+    let mut value: sane::Value = file_utils::read_config_from_file(&file)?;
+    interpolate(&mut value, &|name| std::env::var(name).ok()).map_err(|name| {
+        UnsetVariableError {
+            name,
+            file: file.as_ref().to_string_lossy().into(),
+        }
+    })?;
+    Ok(value.try_into()?)
+}
+
+/// Replace `${VAR}` references in the string values of `value` with the values given by
+/// `lookup`, fails with the name of the first variable without value. `$${` is unescaped to
+/// `${`, the values of [`UNINTERPOLATED_KEYS`] are skipped.
+fn interpolate<F: Fn(&str) -> Option<String>>(
+    value: &mut sane::Value,
+    lookup: &F,
+) -> Result<(), String> {
+    match value {
+        sane::Value::String(s) => {
+            let mut interpolated = String::with_capacity(s.len());
+            let mut rest = s.as_str();
+            while let Some(start) = rest.find("${") {
+                if rest[..start].ends_with('$') {
+                    interpolated.push_str(&rest[..start]);
+                    interpolated.push('{');
+                    rest = &rest[start + 2..];
+                    continue;
+                }
+                let end = match rest[start..].find('}') {
+                    Some(end) => start + end,
+                    None => break,
+                };
+                let name = &rest[start + 2..end];
+                interpolated.push_str(&rest[..start]);
+                interpolated.push_str(&lookup(name).ok_or_else(|| name.to_string())?);
+                rest = &rest[end + 1..];
+            }
+            interpolated.push_str(rest);
+            *s = interpolated;
+        }
+        sane::Value::List(list) => {
+            for value in list {
+                interpolate(value, lookup)?;
+            }
+        }
+        sane::Value::Map(map) => {
+            for (key, value) in map.iter_mut() {
+                if !UNINTERPOLATED_KEYS.contains(&key.as_str()) {
+                    interpolate(value, lookup)?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Path of the config file: the first existing one of `BINREP_CONFIG` (binrep config only),
+/// the provided one, then the `name` file in the default locations, or the same file with a
+/// TOML or YAML extension
+pub fn resolve_config_path<P: AsRef<Path>, T: AsRef<Path>>(
+    provided_config: &Option<P>,
+    name: T,
+) -> Result<PathBuf, Error> {
+    config_candidates(provided_config, &name, |var| std::env::var(var).ok())
         .into_iter()
+        .find(|loc| loc.exists())
+        .ok_or_else(|| NoConfigFileError(name.as_ref().to_string_lossy().into()).into())
+}
+
+/// Paths a config file is searched at, in order, environment variables being read with `lookup`
+fn config_candidates<P: AsRef<Path>, T: AsRef<Path>, F: Fn(&str) -> Option<String>>(
+    provided_config: &Option<P>,
+    name: T,
+    lookup: F,
+) -> Vec<PathBuf> {
+    let env_config = if name.as_ref() == Path::new(CONFIG_FILE_NAME) {
+        lookup(CONFIG_ENV_VAR).map(PathBuf::from)
+    } else {
+        None
+    };
+    let xdg_config_home = lookup("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .unwrap_or_else(|| shellexpand::tilde("~/.config").into_owned());
+    env_config
+        .into_iter()
+        .chain(
+            provided_config
+                .as_ref()
+                .map(|path| PathBuf::from(path.as_ref())),
+        )
         .chain(
-            DEFAULT_CONFIG_LOCATION
-                .iter()
-                .map(|loc| shellexpand::tilde(*loc))
-                .map(|loc| file_utils::path_concat2(loc.into_owned(), &name)),
+            std::iter::once(file_utils::path_concat2(xdg_config_home, "binrep"))
+                .chain(
+                    DEFAULT_CONFIG_LOCATION
+                        .iter()
+                        .map(|loc| PathBuf::from(shellexpand::tilde(*loc).into_owned())),
+                )
+                .flat_map(|loc| {
+                    let path = file_utils::path_concat2(loc, &name);
+                    let alternatives = ALTERNATIVE_CONFIG_EXTENSIONS
+                        .iter()
+                        .map(|ext| path.with_extension(ext))
+                        .collect::<Vec<_>>();
+                    std::iter::once(path).chain(alternatives)
+                }),
         )
-        .filter(|loc| loc.exists())
-        .nth(0)
-        .map(|loc| file_utils::read_sane_from_file(loc))
-        .unwrap_or(Err(NoConfigFileError(
-            name.as_ref().to_string_lossy().into(),
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{config_candidates, interpolate, read_config_file};
+    use std::path::PathBuf;
+
+    /// `path` followed by the same file with the alternative extensions
+    fn with_alternatives(path: String) -> Vec<PathBuf> {
+        ["sane", "toml", "yaml", "yml"]
+            .iter()
+            .map(|ext| PathBuf::from(&path).with_extension(ext))
+            .collect()
+    }
+
+    #[test]
+    fn candidates() {
+        let home = shellexpand::tilde("~").into_owned();
+        let lookup = |var: &str| match var {
+            "BINREP_CONFIG" => Some("/run/secrets/binrep.sane".to_string()),
+            "XDG_CONFIG_HOME" => Some("/xdg".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            [
+                vec![
+                    PathBuf::from("/run/secrets/binrep.sane"),
+                    PathBuf::from("./config.sane"),
+                ],
+                with_alternatives("/xdg/binrep/config.sane".to_string()),
+                with_alternatives(format!("{}/.binrep/config.sane", home)),
+                with_alternatives("/etc/binrep/config.sane".to_string()),
+            ]
+            .concat(),
+            config_candidates(&Some("./config.sane"), "config.sane", lookup)
+        );
+        // BINREP_CONFIG only points to the binrep config
+        assert_eq!(
+            [
+                with_alternatives(format!("{}/.config/binrep/batch.sane", home)),
+                with_alternatives(format!("{}/.binrep/batch.sane", home)),
+                with_alternatives("/etc/binrep/batch.sane".to_string()),
+            ]
+            .concat(),
+            config_candidates(&None::<PathBuf>, "batch.sane", |var| match var {
+                "BINREP_CONFIG" => Some("/run/secrets/binrep.sane".to_string()),
+                _ => None,
+            })
+        );
+    }
+
+    #[test]
+    fn interpolation() {
+        let lookup = |name: &str| match name {
+            "SLACK_URL" => Some("https://hooks.slack.com/services/ABC".to_string()),
+            "BUCKET" => Some("artifacts".to_string()),
+            _ => None,
+        };
+        let mut value: sane::Value = sane::from_str(
+            r#"webhook_url = "${SLACK_URL}"
+            backend = { bucket = "${BUCKET}-${BUCKET}", prefix = "$HOME ${ unterminated" }
+            keys = ["${BUCKET}"]
+            retries = 3"#,
+        )
+        .unwrap();
+        interpolate(&mut value, &lookup).unwrap();
+        let expected: sane::Value = sane::from_str(
+            r#"webhook_url = "https://hooks.slack.com/services/ABC"
+            backend = { bucket = "artifacts-artifacts", prefix = "$HOME ${ unterminated" }
+            keys = ["artifacts"]
+            retries = 3"#,
+        )
+        .unwrap();
+        assert_eq!(expected, value);
+
+        let mut value: sane::Value = sane::from_str(r#"key = "${UNSET}""#).unwrap();
+        assert_eq!(Err("UNSET".to_string()), interpolate(&mut value, &lookup));
+
+        // escaped, or in shell commands
+        let mut value: sane::Value = sane::from_str(
+            r#"url = "$${SLACK_URL}/${BUCKET}"
+            operations = [{ exec = "echo ${UNSET}", command = "echo $${UNSET}" }]"#,
+        )
+        .unwrap();
+        interpolate(&mut value, &lookup).unwrap();
+        let expected: sane::Value = sane::from_str(
+            r#"url = "${SLACK_URL}/artifacts"
+            operations = [{ exec = "echo ${UNSET}", command = "echo $${UNSET}" }]"#,
+        )
+        .unwrap();
+        assert_eq!(expected, value);
+    }
+
+    #[test]
+    fn formats() {
+        std::env::set_var("BINREP_TEST_FORMATS_BUCKET", "artifacts");
+        let expected: sane::Value = sane::from_str(
+            r#"backend = { bucket = "artifacts", prefix = "binrep" }
+            keys = ["a", "b"]
+            retries = 3
+            verify = true"#,
         )
-        .into()))
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in [
+            (
+                "config.toml",
+                r#"keys = ["a", "b"]
+                retries = 3
+                verify = true
+                [backend]
+                bucket = "${BINREP_TEST_FORMATS_BUCKET}"
+                prefix = "binrep""#,
+            ),
+            (
+                "config.yaml",
+                "backend:\n  bucket: ${BINREP_TEST_FORMATS_BUCKET}\n  prefix: binrep\n\
+                 keys: [a, b]\nretries: 3\nverify: true\n",
+            ),
+        ] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            let value: sane::Value = read_config_file(&path).unwrap();
+            assert_eq!(expected, value, "{}", name);
+        }
+    }
 }
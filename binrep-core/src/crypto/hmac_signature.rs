@@ -136,13 +136,11 @@ impl Config {
         // decode key & validate key length
         data_encoding::BASE64
             .decode(key.as_bytes())
-            .map_err(|e| ConfigValidationError::HmacSigningKeyNotFound {
-                key_id: key.clone(),
-            })
+            .map_err(|e| ConfigValidationError::InvalidHmacKey(key_id.to_string()))
             .and_then(|key_bytes| {
                 // validate key length
                 if key_bytes.len() != hmac_signature_method.key_len() {
-                    Err(ConfigValidationError::InvalidHmacKey(key.clone()))
+                    Err(ConfigValidationError::InvalidHmacKey(key_id.to_string()))
                 } else {
                     Ok(key_bytes)
                 }
@@ -1,15 +1,21 @@
 use crate::config::Config;
 use crate::config::ConfigValidationError;
 use crate::config::PublishParameters;
-use crate::metadata::{Artifact, ChecksumMethod, SignatureMethod};
+use crate::metadata::{self, Artifact, ChecksumMethod, Labels, Signature, SignatureMethod};
 use anyhow::Error;
+use pin_project::pin_project;
 use ring::hmac::sign;
 use ring::{digest, hmac, rand};
+use std::collections::BTreeSet;
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
 
 mod hmac_signature;
+mod pgp_signature;
 use hmac_signature::*;
 
 mod ed25519_signature;
@@ -26,16 +32,17 @@ pub trait Verifier {
     fn verify(&self, msg: &[u8], signature: Vec<u8>) -> bool;
 }
 
+/// Compute the digest of a file, reading it by chunks of `buffer_size` bytes
 pub fn digest_file<P: AsRef<Path>>(
     file: P,
     algorithm: &'static digest::Algorithm,
+    buffer_size: usize,
 ) -> Result<digest::Digest, Error> {
-    let file = File::open(file)?;
+    let mut file = File::open(file)?;
     let mut hash_context = digest::Context::new(algorithm);
-    let mut reader = BufReader::new(file);
-    let mut buf: Vec<u8> = vec![0; 4096];
+    let mut buf: Vec<u8> = vec![0; buffer_size];
     loop {
-        let bytes_read = reader.read(buf.as_mut_slice())?;
+        let bytes_read = file.read(buf.as_mut_slice())?;
         if bytes_read == 0 {
             break;
         }
@@ -44,11 +51,65 @@ pub fn digest_file<P: AsRef<Path>>(
     Ok(hash_context.finish())
 }
 
+/// Compute the digest of the bytes read through an async reader, eg. a download being written
+/// to a local file, so that the file does not have to be read back
+#[pin_project]
+pub struct DigestReaderAsyncAdapter<R: AsyncRead> {
+    #[pin]
+    reader: R,
+    hash_context: digest::Context,
+}
+
+impl<R: AsyncRead> DigestReaderAsyncAdapter<R> {
+    pub fn new(reader: R, algorithm: &'static digest::Algorithm) -> Self {
+        Self {
+            reader,
+            hash_context: digest::Context::new(algorithm),
+        }
+    }
+
+    /// Digest of the bytes read so far
+    pub fn digest(&self) -> digest::Digest {
+        self.hash_context.clone().finish()
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for DigestReaderAsyncAdapter<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.project();
+        let already_filled = buf.filled().len();
+        let poll = this.reader.poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = &poll {
+            this.hash_context.update(&buf.filled()[already_filled..]);
+        }
+        poll
+    }
+}
+
 pub struct PublishAlgorithms {
     pub signer: Box<dyn Signer>,
+    pub additional_signers: Vec<Box<dyn Signer>>,
     pub checksum_method: ChecksumMethod,
 }
 
+impl PublishAlgorithms {
+    /// Sign the files and labels with every signer, the primary signature first
+    pub(crate) fn sign(
+        &self,
+        files: &[metadata::File],
+        labels: &Labels,
+    ) -> Result<Vec<Signature>, Error> {
+        std::iter::once(&self.signer)
+            .chain(self.additional_signers.iter())
+            .map(|signer| sign_files(files, labels, signer.as_ref()))
+            .collect()
+    }
+}
+
 impl ChecksumMethod {
     pub(crate) fn algorithm(&self) -> &'static digest::Algorithm {
         match self {
@@ -70,15 +131,61 @@ impl Config {
             Some(params) => Ok(PublishAlgorithms {
                 checksum_method: params.checksum_method,
                 signer: self.get_signer(params)?,
+                additional_signers: params
+                    .additional_signatures
+                    .iter()
+                    .flatten()
+                    .map(|additional| {
+                        self.get_signer(&PublishParameters {
+                            signature_method: additional.signature_method,
+                            checksum_method: params.checksum_method,
+                            hmac_signing_key: additional.hmac_signing_key.clone(),
+                            ed25519_signing_key: additional.ed25519_signing_key.clone(),
+                            additional_signatures: None,
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
             }),
         }
     }
 
+    /// Problems of the configured keys and publish parameters, missing publish parameters are
+    /// not a problem: the configuration is then only used to pull
+    pub fn validation_errors(&self) -> Vec<ConfigValidationError> {
+        let mut errors = Vec::new();
+        if self.publish_parameters.is_some() {
+            errors.extend(self.get_publish_algorithm().err());
+        }
+        // the length of a hmac key depends on the algorithm it is used with
+        for key_id in self.hmac_keys.iter().flat_map(|keys| keys.keys()) {
+            if let Err(e) = self.get_hmac_verifier(&SignatureMethod::HmacSha256, key_id) {
+                if [SignatureMethod::HmacSha384, SignatureMethod::HmacSha512]
+                    .iter()
+                    .all(|method| self.get_hmac_verifier(method, key_id).is_err())
+                {
+                    errors.push(e);
+                }
+            }
+        }
+        for key_id in self.ed25519_keys.iter().flat_map(|keys| keys.keys()) {
+            errors.extend(self.get_ed25519_verifier(key_id).err());
+        }
+        for key_id in self.pgp_keys.iter().flat_map(|keys| keys.keys()) {
+            errors.extend(self.get_pgp_verifier(key_id).err());
+        }
+        errors
+    }
+
     pub(crate) fn get_verifier(
         &self,
         signature_method: &SignatureMethod,
         key_id: &str,
     ) -> Result<Box<dyn Verifier>, ConfigValidationError> {
+        if !self.is_trusted_key(key_id) {
+            Err(ConfigValidationError::UntrustedKey {
+                key_id: key_id.to_string(),
+            })?;
+        }
         match signature_method {
             SignatureMethod::HmacSha256
             | SignatureMethod::HmacSha384
@@ -86,7 +193,27 @@ impl Config {
                 Ok(Box::new(self.get_hmac_verifier(signature_method, key_id)?))
             }
             SignatureMethod::ED25519 => Ok(Box::new(self.get_ed25519_verifier(key_id)?)),
+            SignatureMethod::Pgp => Ok(Box::new(self.get_pgp_verifier(key_id)?)),
+        }
+    }
+
+    /// Signer of the publish parameters, using `key_id` instead of the configured signing key
+    pub(crate) fn get_signer_with_key(
+        &self,
+        key_id: &str,
+    ) -> Result<Box<dyn Signer>, ConfigValidationError> {
+        let mut params = self
+            .publish_parameters
+            .clone()
+            .ok_or(ConfigValidationError::NoPublishParameters)?;
+        match params.signature_method {
+            SignatureMethod::HmacSha256
+            | SignatureMethod::HmacSha384
+            | SignatureMethod::HmacSha512 => params.hmac_signing_key = Some(key_id.to_string()),
+            SignatureMethod::ED25519 => params.ed25519_signing_key = Some(key_id.to_string()),
+            SignatureMethod::Pgp => Err(ConfigValidationError::PgpSigningUnsupported)?,
         }
+        self.get_signer(&params)
     }
 
     pub(crate) fn get_signer(
@@ -102,31 +229,637 @@ impl Config {
                 Ok(Box::new(self.get_hmac_signer(publish_parameters)?))
             }
             SignatureMethod::ED25519 => Ok(Box::new(self.get_ed25519_signer(publish_parameters)?)),
+            SignatureMethod::Pgp => Err(ConfigValidationError::PgpSigningUnsupported),
         }
     }
 }
 
-impl Artifact {
-    pub(crate) fn verify_signature(&self, config: &Config) -> Result<bool, Error> {
-        let msg: Vec<u8> = self
-            .files
+/// Version of the signed message format used when signing
+pub(crate) const SIGNATURE_SCHEME_VERSION: u32 = 3;
+
+/// Message covered by the signature.
+///
+/// - version 1: the name and checksum of each file concatenated, in the artifact order. Kept to
+///   verify existing artifacts, it is ambiguous (eg. `ab`+`c` and `a`+`bc`).
+/// - version 2: a header with the number of files, then the length prefixed name and checksum
+///   of each file, files sorted by name. If the artifact has labels, a `labels` header with
+///   their number follows, then the length prefixed key and value of each label, sorted by key.
+/// - version 3: as version 2, the unix mode of each file (octal, empty if unknown) is appended
+///   length prefixed after its checksum.
+///
+/// Labels are not covered by version 1 signatures, unix modes by version 1 and 2 signatures.
+pub(crate) fn signed_message(
+    files: &[metadata::File],
+    labels: &Labels,
+    scheme_version: u32,
+) -> Vec<u8> {
+    if scheme_version < 2 {
+        return files
             .iter()
-            .map(|file| {
+            .flat_map(|file| {
                 file.name
                     .as_bytes()
                     .iter()
                     .chain(file.checksum.as_bytes().iter())
             })
-            .flatten()
-            .map(|c| *c)
+            .copied()
             .collect();
+    }
+    let mut sorted_files: Vec<&metadata::File> = files.iter().collect();
+    sorted_files.sort_by(|a, b| (&a.name, &a.checksum).cmp(&(&b.name, &b.checksum)));
+    let mut msg = format!("binrep-v{}\n{}\n", scheme_version, files.len()).into_bytes();
+    for file in sorted_files {
+        let mut fields = vec![file.name.clone(), file.checksum.clone()];
+        if scheme_version >= 3 {
+            fields.push(
+                file.unix_mode
+                    .map(|unix_mode| format!("{:o}", unix_mode))
+                    .unwrap_or_default(),
+            );
+        }
+        for field in &fields {
+            msg.extend_from_slice(format!("{}:", field.len()).as_bytes());
+            msg.extend_from_slice(field.as_bytes());
+        }
+    }
+    if !labels.is_empty() {
+        msg.extend_from_slice(format!("labels\n{}\n", labels.len()).as_bytes());
+        for (key, value) in labels {
+            for field in &[key, value] {
+                msg.extend_from_slice(format!("{}:", field.len()).as_bytes());
+                msg.extend_from_slice(field.as_bytes());
+            }
+        }
+    }
+    msg
+}
+
+pub(crate) fn sign_files(
+    files: &[metadata::File],
+    labels: &Labels,
+    signer: &dyn Signer,
+) -> Result<Signature, Error> {
+    let msg = signed_message(files, labels, SIGNATURE_SCHEME_VERSION);
+    Ok(Signature {
+        key_id: signer.key_id(),
+        signature_method: signer.signature_method(),
+        signature: data_encoding::BASE64.encode(&signer.sign(&msg)?),
+        scheme_version: Some(SIGNATURE_SCHEME_VERSION),
+    })
+}
+
+/// Message covered by the detached signature of an index file: its path, so that the index of
+/// an artifact cannot be swapped with another one, then its content
+fn index_signed_message(path: &str, content: &str) -> Vec<u8> {
+    format!("binrep-index\n{}:{}\n{}", path.len(), path, content).into_bytes()
+}
+
+pub(crate) fn sign_index(
+    path: &str,
+    content: &str,
+    signer: &dyn Signer,
+) -> Result<Signature, Error> {
+    Ok(Signature {
+        key_id: signer.key_id(),
+        signature_method: signer.signature_method(),
+        signature: data_encoding::BASE64
+            .encode(&signer.sign(&index_signed_message(path, content))?),
+        scheme_version: None,
+    })
+}
+
+pub(crate) fn verify_index(
+    path: &str,
+    content: &str,
+    signature: &Signature,
+    config: &Config,
+) -> Result<bool, Error> {
+    verify(&index_signed_message(path, content), signature, config)
+}
+
+impl Artifact {
+    /// Verify the signatures of the artifact: the number of valid signatures must reach the
+    /// configured quorum (all signatures by default).
+    ///
+    /// A signature that cannot be checked (eg. unknown key) is not valid; if the quorum is not
+    /// reached, the first such error is returned. Each key counts once: the other signatures
+    /// made with the same key are ignored.
+    pub(crate) fn verify_signature(&self, config: &Config) -> Result<bool, Error> {
+        let mut signing_keys = BTreeSet::new();
+        let mut valid_signatures = 0;
+        let mut first_error = None;
+        for signature in &self.signatures {
+            if !signing_keys.insert((signature.signature_method, signature.key_id.as_str())) {
+                warn!(
+                    "Duplicate {:?} signature with key {} for {} ignored",
+                    signature.signature_method, signature.key_id, self.version
+                );
+                continue;
+            }
+            let scheme_version = signature.scheme_version.unwrap_or(1);
+            if scheme_version < 2 && !self.labels.is_empty() {
+                warn!(
+                    "Signature with key {} for {} does not cover labels",
+                    signature.key_id, self.version
+                );
+                continue;
+            }
+            let msg = signed_message(&self.files, &self.labels, scheme_version);
+            match verify(&msg, signature, config) {
+                Ok(true) => valid_signatures += 1,
+                Ok(false) => warn!(
+                    "Invalid {:?} signature with key {} for {}",
+                    signature.signature_method, signature.key_id, self.version
+                ),
+                Err(e) => {
+                    warn!(
+                        "Cannot verify {:?} signature with key {} for {}: {}",
+                        signature.signature_method, signature.key_id, self.version, e
+                    );
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        if valid_signatures >= config.signature_quorum(signing_keys.len()) {
+            Ok(true)
+        } else if let Some(e) = first_error {
+            Err(e)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+fn verify(msg: &[u8], signature: &Signature, config: &Config) -> Result<bool, Error> {
+    let verifier = config.get_verifier(&signature.signature_method, &signature.key_id)?;
+    Ok(verifier.verify(
+        msg,
+        data_encoding::BASE64.decode(signature.signature.as_bytes())?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{signed_message, DigestReaderAsyncAdapter, Verifier};
+    use crate::config::Config;
+    use crate::metadata::{ChecksumMethod, File, Labels, Signature, SignatureMethod};
+
+    fn file(name: &str, checksum: &str) -> File {
+        File {
+            name: name.to_string(),
+            checksum: checksum.to_string(),
+            checksum_method: ChecksumMethod::Sha256,
+            unix_mode: None,
+            size: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn digest_reader() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        // small reads: the digest is updated many times
+        let reader = tokio::io::BufReader::with_capacity(1000, data.as_slice());
+        let mut reader = DigestReaderAsyncAdapter::new(reader, &ring::digest::SHA256);
+        let mut copy = Vec::new();
+        tokio::io::copy(&mut reader, &mut copy).await.unwrap();
+        assert_eq!(data, copy);
+        assert_eq!(
+            ring::digest::digest(&ring::digest::SHA256, &data).as_ref(),
+            reader.digest().as_ref()
+        );
+    }
+
+    #[test]
+    fn signed_message_v2_is_unambiguous() {
+        let no_labels = Labels::new();
+        let files = vec![file("ab", "c")];
+        let other_files = vec![file("a", "bc")];
+        assert_eq!(
+            signed_message(&files, &no_labels, 1),
+            signed_message(&other_files, &no_labels, 1)
+        );
+        assert_ne!(
+            signed_message(&files, &no_labels, 2),
+            signed_message(&other_files, &no_labels, 2)
+        );
+
+        let files = vec![file("a", "x"), file("b", "y")];
+        let reversed: Vec<File> = files.iter().rev().cloned().collect();
+        assert_eq!(
+            signed_message(&files, &no_labels, 2),
+            signed_message(&reversed, &no_labels, 2)
+        );
+        assert_ne!(
+            signed_message(&files, &no_labels, 2),
+            signed_message(&files[..1], &no_labels, 2)
+        );
+    }
+
+    #[test]
+    fn unix_mode_is_signed() {
+        let config = Config::create_file_test_config();
+        let signer = config.get_publish_algorithm().unwrap().signer;
+        let mut files = vec![file("a", "x")];
+        files[0].unix_mode = Some(0o755);
+        let mut setuid_files = files.clone();
+        setuid_files[0].unix_mode = Some(0o4755);
+        assert_eq!(
+            signed_message(&files, &Labels::new(), 2),
+            signed_message(&setuid_files, &Labels::new(), 2)
+        );
+        assert_ne!(
+            signed_message(&files, &Labels::new(), 3),
+            signed_message(&setuid_files, &Labels::new(), 3)
+        );
+
+        // version 2 signatures are still verified
+        let mut signature = super::sign_files(&files, &Labels::new(), signer.as_ref()).unwrap();
+        signature.signature = data_encoding::BASE64.encode(
+            &signer
+                .sign(&signed_message(&files, &Labels::new(), 2))
+                .unwrap(),
+        );
+        signature.scheme_version = Some(2);
+        let artifact = crate::metadata::Artifact {
+            version: semver::Version::new(1, 0, 0),
+            pushed_at: None,
+            signatures: vec![signature],
+            files,
+            labels: Labels::new(),
+        };
+        assert!(artifact.verify_signature(&config).unwrap());
+    }
+
+    #[test]
+    fn verify_v1_signature() {
+        let no_labels = Labels::new();
+        let config = Config::create_file_test_config();
+        let signer = config.get_publish_algorithm().unwrap().signer;
+        let files = vec![file("a", "x"), file("b", "y")];
+        let mut signature = super::sign_files(&files, &no_labels, signer.as_ref()).unwrap();
+        let mut artifact = crate::metadata::Artifact {
+            version: semver::Version::new(1, 0, 0),
+            pushed_at: None,
+            signatures: vec![signature.clone()],
+            files: files.clone(),
+            labels: Labels::new(),
+        };
+        assert!(artifact.verify_signature(&config).unwrap());
+
+        // an artifact signed before scheme versions existed
+        signature.signature = data_encoding::BASE64
+            .encode(&signer.sign(&signed_message(&files, &no_labels, 1)).unwrap());
+        signature.scheme_version = None;
+        artifact.signatures = vec![signature];
+        assert!(artifact.verify_signature(&config).unwrap());
+
+        // files reordered
+        artifact.files.reverse();
+        assert!(!artifact.verify_signature(&config).unwrap());
+    }
+
+    #[test]
+    fn labels_are_signed() {
+        let config = Config::create_file_test_config();
+        let signer = config.get_publish_algorithm().unwrap().signer;
+        let files = vec![file("a", "x")];
+        let mut labels = Labels::new();
+        labels.insert("branch".to_string(), "main".to_string());
+        assert_ne!(
+            signed_message(&files, &Labels::new(), 2),
+            signed_message(&files, &labels, 2)
+        );
+
+        let signature = super::sign_files(&files, &labels, signer.as_ref()).unwrap();
+        let mut artifact = crate::metadata::Artifact {
+            version: semver::Version::new(1, 0, 0),
+            pushed_at: None,
+            signatures: vec![signature],
+            files: files.clone(),
+            labels: labels.clone(),
+        };
+        assert!(artifact.verify_signature(&config).unwrap());
 
-        let verifier =
-            config.get_verifier(&self.signature.signature_method, &self.signature.key_id)?;
+        artifact
+            .labels
+            .insert("branch".to_string(), "release".to_string());
+        assert!(!artifact.verify_signature(&config).unwrap());
+        artifact.labels.clear();
+        assert!(!artifact.verify_signature(&config).unwrap());
 
-        Ok(verifier.verify(
-            &msg,
-            data_encoding::BASE64.decode(self.signature.signature.as_bytes())?,
-        ))
+        // version 1 signatures do not cover labels
+        let mut v1_signature = super::sign_files(&files, &Labels::new(), signer.as_ref()).unwrap();
+        v1_signature.signature = data_encoding::BASE64
+            .encode(&signer.sign(&signed_message(&files, &labels, 1)).unwrap());
+        v1_signature.scheme_version = None;
+        artifact.signatures = vec![v1_signature];
+        assert!(artifact.verify_signature(&config).unwrap());
+        artifact.labels = labels;
+        assert!(!artifact.verify_signature(&config).unwrap());
+    }
+
+    #[test]
+    fn duplicate_signatures() {
+        let mut config = Config::create_file_test_config();
+        let signer = config.get_publish_algorithm().unwrap().signer;
+        let files = vec![file("a", "x")];
+        let signature = super::sign_files(&files, &Labels::new(), signer.as_ref()).unwrap();
+        let artifact = crate::metadata::Artifact {
+            version: semver::Version::new(1, 0, 0),
+            pushed_at: None,
+            signatures: vec![signature.clone(), signature],
+            files,
+            labels: Labels::new(),
+        };
+        // all signatures by default: the key signed
+        assert!(artifact.verify_signature(&config).unwrap());
+        // a single key cannot reach a quorum of 2
+        config.verify_parameters = Some(crate::config::VerifyParameters {
+            signature_quorum: Some(2),
+            ..Default::default()
+        });
+        assert!(!artifact.verify_signature(&config).unwrap());
+    }
+
+    #[test]
+    fn trusted_key_ids() {
+        let mut config = Config::create_file_test_config();
+        let signer = config.get_publish_algorithm().unwrap().signer;
+        let files = vec![file("a", "x")];
+        let signature = super::sign_files(&files, &Labels::new(), signer.as_ref()).unwrap();
+        let artifact = crate::metadata::Artifact {
+            version: semver::Version::new(1, 0, 0),
+            pushed_at: None,
+            signatures: vec![signature],
+            files,
+            labels: Labels::new(),
+        };
+        config.verify_parameters = Some(crate::config::VerifyParameters {
+            trusted_key_ids: Some(vec!["new-key".to_string(), "test".to_string()]),
+            ..Default::default()
+        });
+        assert!(artifact.verify_signature(&config).unwrap());
+
+        // the key is still configured but no longer trusted
+        config.verify_parameters = Some(crate::config::VerifyParameters {
+            trusted_key_ids: Some(vec!["new-key".to_string()]),
+            ..Default::default()
+        });
+        let error = artifact.verify_signature(&config).unwrap_err();
+        assert!(error.to_string().contains("not in the trusted keys"));
+    }
+
+    /// `gpg --quick-gen-key "binrep test <test@example.com>" ed25519 sign`
+    const PGP_ED25519_PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEatJJ9RYJKwYBBAHaRw8BAQdAYaVav0ba1qun2LZtNU1QE/8wAJJcGn2J2Hpp
+l5nstra0HmJpbnJlcCB0ZXN0IDx0ZXN0QGV4YW1wbGUuY29tPoiQBBMWCAA4FiEE
+iIG7fhpmZFAwBEtMR04Y330Ez14FAmrSSfUCGwMFCwkIBwIGFQoJCAsCBBYCAwEC
+HgECF4AACgkQR04Y330Ez14flQD/e2Uy2ZLZZ14f8lG1qS+mRkL/Dag6DvAYVCvI
+hUllKWQBAI0z9PwC9Zj5+xnf/VR+7bbt+cOUA+sQ93wqfwJnnUwI
+=swnb
+-----END PGP PUBLIC KEY BLOCK-----
+";
+    /// `gpg --quick-gen-key "binrep rsa <rsa@example.com>" rsa2048 sign`
+    const PGP_RSA_PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mQENBGrSSfUBCACvVXVgo6tehtggTvydcQXd83g3IsBQ+2x6HbO0xhMNKBNG4TD0
+aQul9ihEbqqb4IhrZVCwD915sx06DvZo3rBSh9/PI3Jf0gaFy/gWqCS3FmUsQsuT
+ThttlnWm3nE9MV47dIVdtOwx62SsAePiDPt5OlNR2t7nDqLhaU0WV8DEGlnwB1gP
+kCynFxvRN5lHpx9S6ZOVHofjD0KNxMd/s5sdt68EJmq08Z+dQWQeu5IO90JPp5uW
+Qy5d17J7kQ+e6atjCaZFrkEelyET4ZIkk3f3Vp7IHg4uxZLh+ko6mNJAWzxTUjZQ
+xifl+exX/JPkfxXxIpsOBbxqh7+Ffh9XU8KpABEBAAG0HGJpbnJlcCByc2EgPHJz
+YUBleGFtcGxlLmNvbT6JAU4EEwEKADgWIQSt93yeHiRDfreFo39doc0pOb1U9wUC
+atJJ9QIbAwULCQgHAgYVCgkICwIEFgIDAQIeAQIXgAAKCRBdoc0pOb1U9wPdB/9Y
+D3CgGQ2guBhTKwkax3uhZFIso7q4MWj9xnfEVlgTjmcF4o7hO/ORHBfTeO4O9mNt
+VrLc2p7Jlhhw3v3mdgRJAEq65ppv6fZDf8bARJPPbUcKdktIqae2cg2CtImINv4A
+b23S4A+BkRPTpczkZWDC9d2rHjfF1xRUmYSjp3c/CI1gAzHxjcqPxyRKXnziaHj7
+D5yg46CFUZ+B6WLGYiszJhZsDSa2U+A1KQ90ki3JgLaKZutbeh3NzpPHCLPqssTv
+Hy3vhM32IQn6Otdg0cjBMFvsC7SgQj/H4oBqaUmLi/+TsuDO/BtIc5Wz8N+itXjT
+tOe2Gs/MhWIztbChscRc
+=5hXL
+-----END PGP PUBLIC KEY BLOCK-----
+";
+
+    /// `gpg --quick-gen-key "binrep revoked <revoked@example.com>" ed25519 sign never`, then
+    /// its revocation certificate imported
+    const PGP_REVOKED_PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEatJtshYJKwYBBAHaRw8BAQdAorQSXdnvD1IKbyakRMjef/FFF+53bn16ZIS0
+YETKm4eIeAQgFggAIBYhBCRrpRe4gTOs2UE1JkYy1upUJS8PBQJq0m2yAh0AAAoJ
+EEYy1upUJS8PUnYA/3ExSLzhjhCcaS7g4x/Zlm6oO26CZqFywb2N8shACJADAP0Z
+ngU8D/l6usrJ0MkUoIcO32lmgO+RrExbP/v9TmO2AbQkYmlucmVwIHJldm9rZWQg
+PHJldm9rZWRAZXhhbXBsZS5jb20+iJAEExYIADgWIQQka6UXuIEzrNlBNSZGMtbq
+VCUvDwUCatJtsgIbAwULCQgHAgYVCgkICwIEFgIDAQIeAQIXgAAKCRBGMtbqVCUv
+D5l7AQDKkn33sscV0imaxPH0Ch+gwKvcX7Rk8htoD24h2VT46gEAm8prscS/UxKF
+Z1Ae+D0umAxIK5mmGu1BDdamF5qH/w0=
+=X8oo
+-----END PGP PUBLIC KEY BLOCK-----
+";
+    /// `gpg --quick-gen-key "binrep default <default@example.com>" default default`: a RSA
+    /// primary key expiring in 2 years with an encryption subkey
+    const PGP_DEFAULT_PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mQGNBGrSd38BDADP8GXFNa3Udcx4/Jk+aZ/GsSMNb0AOFc2gpS+hXIXzmpYpxVwN
+2vPENmLYbxybQ04FxONLlY7PA2yW0t3pDuPTxMwFKGi893GcK0a/iVEze7pzbKLm
+9874tmHvTqJV7rAgyV3D02oyDrIx/uq/fdXvMncC/zh+TP6KRtOOxUUA37Ak8FBp
+iQIsMkpOfziQeSbxCwEkYO+IbMxp+WPp3vg/wZlwy/LOpjrpvGmcwvmzFyX/oqNT
+1PMUoSmAlfdzz2a7oCipzkiU7jH3cUfGe2vyn+7RKwSfcVxxmcayHLtwf9GkCazf
+EJgMPNAZtZIZLVvjzoO7zfodIu802+3rk3KYvqLjSzDIr5lVsNTwBr3S+vKC6AoW
+0SmOLAr0plWBusEuB1DFh3Hu71J3DiNyekpgMkzj0gdctEOJ16qPmCdwTeyfzhYl
+0FeKWGO4ybQqGpSm4ko0yv/qufROfsU+1IYOgYBaJkT6oCL+Qe8H+uOXjkVzpB9J
+eDqJiWEsOXRS//EAEQEAAbQkYmlucmVwIGRlZmF1bHQgPGRlZmF1bHRAZXhhbXBs
+ZS5jb20+iQHUBBMBCgA+FiEE1024dP/1gUHb59Usg/8Ud6tH1JkFAmrSd38CGwMF
+CQPCZwAFCwkIBwIGFQoJCAsCBBYCAwECHgECF4AACgkQg/8Ud6tH1JkWCwv/YuXg
+0YJXdtnT9JTlfMlR0kLvbw+gcm6/ZD+9KB4xW9D3q1HgBVPqJ+SN3jNHeggSudN6
+Rll8Y4RmUPnIadWSbhsFXCZtV4FLFP72f5fBVkGSMJo17kM1t5zHN5XUv/v1eG1Y
+VXesAt1jDX8w/BTM4JY5/2IzJIu8OJp4JTJOY3mRzORX87TPpbLKS4HnvjXvxuKh
+b6J07wzj7Z98UMothipE5SrsM5UxJ7E5DY8KwzLgCqKgGBwidLFPGYu5wdDgjiQj
+9AIvn3Le+zv/LDcHeaR+jWanEDLg4Qx+idzTDkIzJySi8/Iqiqu/PqwI1YYAW70o
+jqfbeRb8WOdMc7trkkvAXflq4xmqj6f6NAKgG+8Qvl535C3u0tnz+5n7mqG7/fqN
+HDLEs5Eyk8Zg1DjiETH/LJBcUljH6uNWH6fD0f9Ppwut5GRqLLYT6q4Q89fNCUzP
+IxxbevW6u4HDxqv+Nl1Qj+cPE/MX2yIHxMjzkHv3oM6obkyaWjQJVQBI8cl6uQGN
+BGrSd38BDACggD4a6tU0SMJP0da0zN9kQSkIvLd6ooZjlv6EFkGmU/K2eHeSQFLA
+GMMJYzZAGQgbOHdlHk4dQ2G2luHv2NwErq02NJ8QYYcpKNspwbsGdxXcPpnJK6bF
+/U9ZnDtMjoq5XbBKmlV+hThAfenxjGwfOo6L8JiwavQJWP8OR8bloDCN6qL8vyLn
+3cKa9CXN2YnC/xSvrft89qZiLaYAIsjkzZzNZHa64JEtLGvZmplD0rRZnw1jwpNn
+cW66VAlIJTCu3h8U9a5ZhXB0Nmul6P6/wAfrdUa1AWLeRmOw8hDCIESawuo3RqKq
+TBJDL0x6vbxId60n5N3hRRhiPkJWwtVBN2tIpSrFAZiFXiy/8b8xdhXTSmkl5pSh
+Sm9lvVIHDmDHoqJudcqqq0SADftkMYS2WQ493w1apHpVGngKZ9W7dkZxoLggzvKX
+Gljkr/BicW6dXGNqSmYAGWaC8FCtnIjU4iijApffXQI36QMzUUSF0I9REI2q5QsK
+RQ58ZPD+AOEAEQEAAYkBtgQYAQoAIBYhBNdNuHT/9YFB2+fVLIP/FHerR9SZBQJq
+0nd/AhsMAAoJEIP/FHerR9SZSPcL/1VLobj+EbBWo5tQ7mbeMbrzwwptHpYpW7Vz
+N7VCoxAT2gdWXtbwZQo6I27S2KeBlaLrPC64F+qaV2S/NredPIWUJHqM5L1KTj0S
+qWKmLCaNfefcAJr8JPmy3Z68lcUa2MnqppY4zfj01bZrGoTdIcU8m+InJd5GrZZQ
+ULvfbynaMRTBidDEdaagr9hhRQD9s0rFJMry1jv037slzEqrQFYFnS1BpL5OpAu/
+Ke6zbPxQEuJ/AtUSjZxUcCnme73/SfGvM/ak6RGMaz3m6F+ZVZKAIR0reC/P+Io6
+MVOo4mlgbmjKgKTcEp5G6OJCo9MX9BrZXrMHkyrqO5GTLt3v7x1BSPOQOFjmT4du
+JeDuXOPNpgNAGmhVKN0AB/Nzcxbh/h7Vd/gJVXp5kjzlLY8o7nf0u4hp8VFckL2j
+Nk/W2jBM82ToXUXCZN47JP/7d5xDmop14XFTMI2azmhthyRvo8W8EMqCUckFgt8y
+m1m1IydaArV5pd6Iv/VTNzAcFS9oKg==
+=fVXu
+-----END PGP PUBLIC KEY BLOCK-----
+";
+    /// `gpg --quick-gen-key "binrep signing subkey <signsub@example.com>" ed25519 cert never`,
+    /// then `gpg --quick-add-key <fingerprint> ed25519 sign never`
+    const PGP_SIGNING_SUBKEY_PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEatJ3fxYJKwYBBAHaRw8BAQdA2gyvFq10rMkfBltAUVXU0tbw9UeShmn8Lnt8
+/qvpKYK0K2JpbnJlcCBzaWduaW5nIHN1YmtleSA8c2lnbnN1YkBleGFtcGxlLmNv
+bT6IkAQTFggAOBYhBIdC74AX336yX0JLbOsmJi0otCwzBQJq0nd/AhsBBQsJCAcC
+BhUKCQgLAgQWAgMBAh4BAheAAAoJEOsmJi0otCwzf/ABAJXMBkVWs/vRo4L9ec9v
+aY4LAC9xZ9j7V6jQZH59gHWFAP0WZZzskf4FUcs6v0h0xWk126Noq+HTmk3V27xK
+XslwDrgzBGrSd38WCSsGAQQB2kcPAQEHQOzZdCsAZslTVvczA2xMUMymSXdvF2v8
+YF950rkoyEO4iO8EGBYIACAWIQSHQu+AF99+sl9CS2zrJiYtKLQsMwUCatJ3fwIb
+AgCBCRDrJiYtKLQsM3YgBBkWCAAdFiEEu69O6qdr3D2Ju43H0nUPirm3QZ8FAmrS
+d38ACgkQ0nUPirm3QZ+4bQEAifWYSjDf4T5cKOArPyJrbBNa5G0LDXJ9zGuKwUoM
+LjkBALnWEOJh6+2OqrFUMupQnR6k3MIZAbYfWcqjV0XHo3UDAcMBANruBDOcpxK/
+IG4+nv/XfN+SDyBQKJ4BCArniWEEl0a8AQCWo7ZcER74gArVdVEiua0pGDS/Mspy
+ZFmZQaxtH44+BA==
+=0K65
+-----END PGP PUBLIC KEY BLOCK-----
+";
+    /// `gpg --faked-system-time 20200101T000000 --quick-gen-key
+    /// "binrep expired <expired@example.com>" ed25519 sign 1d`
+    const PGP_EXPIRED_PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEXgvhABYJKwYBBAHaRw8BAQdA38VXoEjC3vPLQzPlEfv7mR9UJeRHpZBOvjvc
+zCLzfFW0JGJpbnJlcCBleHBpcmVkIDxleHBpcmVkQGV4YW1wbGUuY29tPoiWBBMW
+CAA+FiEE6UFPnH7jrADDVsaS9btQot4aSt0FAl4L4QACGwMFCQABUYAFCwkIBwIG
+FQoJCAsCBBYCAwECHgECF4AACgkQ9btQot4aSt1tnAD8Cv6LKaMeERGVxgk1VFg5
+meMch8roTqcAf24vDzaAEREA/iHUEsxvc844PtsH+4XE4cdzDEEu9+kx0wohbVNs
+2yQP
+=G2QD
+-----END PGP PUBLIC KEY BLOCK-----
+";
+    /// Created on 2020-01-01 without expiration, then expired after one day with
+    /// `gpg --faked-system-time 20200101T000100 --quick-set-expire <fingerprint> 1d`
+    const PGP_LATE_EXPIRED_PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEXgvhABYJKwYBBAHaRw8BAQdA+MkYIgEVU15NjHY5RG0hl//NJnjt17/mJKmx
+Cn5ITji0IWJpbnJlcCBleHBpcmVkIDxsYXRlQGV4YW1wbGUuY29tPoiWBBMWCAA+
+AhsDBQsJCAcCBhUKCQgLAgQWAgMBAh4BAheAFiEElU3Oimk6iL0P7XIyUUJXlw3z
+CrEFAl4L4TwFCQABUbwACgkQUUJXlw3zCrH3sgEAmEQrz/XpBTwVMmS9c0Awh8Nk
+8wmWKiRDCfCLGcv08R8BAK6v3xFLVNlgviOnFW07MyV7q95sLx1vtA+uJNsEW7AA
+=+xVl
+-----END PGP PUBLIC KEY BLOCK-----
+";
+    /// Signed message of the `pgp_keys` signatures
+    const PGP_MESSAGE: &[u8] = b"binrep signed message";
+
+    #[test]
+    fn pgp_keys() {
+        let mut config = Config::create_file_test_config();
+        let mut pgp_keys = std::collections::HashMap::new();
+        pgp_keys.insert("revoked".to_string(), PGP_REVOKED_PUBLIC_KEY.to_string());
+        pgp_keys.insert("default".to_string(), PGP_DEFAULT_PUBLIC_KEY.to_string());
+        pgp_keys.insert(
+            "subkey".to_string(),
+            PGP_SIGNING_SUBKEY_PUBLIC_KEY.to_string(),
+        );
+        pgp_keys.insert("expired".to_string(), PGP_EXPIRED_PUBLIC_KEY.to_string());
+        pgp_keys.insert("late".to_string(), PGP_LATE_EXPIRED_PUBLIC_KEY.to_string());
+        config.pgp_keys = Some(pgp_keys);
+        let error = config.get_pgp_verifier("revoked").err().unwrap();
+        assert!(error.to_string().contains("revoked"), "{}", error);
+
+        // `gpg --detach-sign` of PGP_MESSAGE, base64 encoded
+        let signatures = [
+            ("default", "iQHIBAABCgAyFiEE1024dP/1gUHb59Usg/8Ud6tH1JkFAmrSd4oUHGRlZmF1bHRAZXhhbXBsZS5jb20ACgkQg/8Ud6tH1JlbGQv7BotEJn3lslwvA5M6MjubivuSulw/pkOzdpmglFZcOVMnyLx0qPZwDoyK+v1vYj9hYznEa7slPa0n/KxI2dh9bd5dAdTUDeTV7fl2yTvD353NU+z7y4xsfcg3resFdmz6Yv4Q/On6WY3LDbYnHR5uWcsywcUTbzshU7qHI0qdIs+pHutfiZu6Ac5ceq0UbQeig/U7got8tCoPilnA+XjN7vwvFPuzPYZh2dpYs0pkmmgce6rdfli2m+a+RcHgyJ+i5V4C4XIl8YQ2SFWApOhAMW61xIanX9u9HHf1RjD07hV+PIFrDTl/DLTU8Wq2PseztAlOTekb0+2zaMQvGnC5Ym24mSJJEsidJi8PmWcW++J+7z4rrLXf9UBYSSQZlhPGdvpFquB7nSq7CgHoltikr3HdD/2UfRnEuCH9XKNpmasDDngsp/a+V/oc60B5Sd7A2gYdU+cbHTUxSZNO1ttbau1ZvXT7kNr4uiW6iJYr3xJy4d2Mcy8O0t2qlwfuSbrR", true),
+            // made by the signing subkey
+            ("subkey", "iIoEABYIADIWIQS7r07qp2vcPYm7jcfSdQ+KubdBnwUCatJ3ihQcc2lnbnN1YkBleGFtcGxlLmNvbQAKCRDSdQ+KubdBn9XHAPsFgQ7VoNhKPOHxV1uY4kRUxxN8dKyKS/4pKfEK4KmizgD9FzWyuXmYvcDbvOrP+mjNIKE7HoEdqBpacl1wuJoqIgc=", true),
+            // made on 2020-01-01 while the key was valid
+            ("expired", "iIoEABYIADIWIQTpQU+cfuOsAMNWxpL1u1Ci3hpK3QUCXgyJwBQcZXhwaXJlZEBleGFtcGxlLmNvbQAKCRD1u1Ci3hpK3WzmAP9roXq8y6t9n3X/z3Knp4pdAQFj2R0mGSQ6zTZdhn/mOgD+NDzg1A36y0cheahrujbl2jmPLq4gDWap4+rIxxCNKgU=", true),
+            // made on 2020-01-01 with `--default-sig-expire 1d`
+            ("expired", "iJAEABYIADgWIQTpQU+cfuOsAMNWxpL1u1Ci3hpK3QUCXgyJwAWDAAFRgBQcZXhwaXJlZEBleGFtcGxlLmNvbQAKCRD1u1Ci3hpK3cDeAP9vQIuKdnulE2Sw6tyQNCBe3lLrOrQsR53NiexpO1UdKQD/cW+fXFgd7iBePryf2F5RSLJvZAwS5R/RMnq5LIvv/Ak=", false),
+            // made on 2020-01-05, after the expiration of the key
+            ("late", "iIcEABYIAC8WIQSVTc6KaTqIvQ/tcjJRQleXDfMKsQUCXhEnABEcbGF0ZUBleGFtcGxlLmNvbQAKCRBRQleXDfMKse2PAQCA9iBnXwDSuxW+24ZW251zhjEa9d61rwfCFdOQrI8h6AD/cMatJ1cXFzFmzRH57ucpsWtTlLOrXVXPQvlfHVAPvAc=", false),
+            // made by another key
+            ("subkey", "iQHIBAABCgAyFiEE1024dP/1gUHb59Usg/8Ud6tH1JkFAmrSd4oUHGRlZmF1bHRAZXhhbXBsZS5jb20ACgkQg/8Ud6tH1JlbGQv7BotEJn3lslwvA5M6MjubivuSulw/pkOzdpmglFZcOVMnyLx0qPZwDoyK+v1vYj9hYznEa7slPa0n/KxI2dh9bd5dAdTUDeTV7fl2yTvD353NU+z7y4xsfcg3resFdmz6Yv4Q/On6WY3LDbYnHR5uWcsywcUTbzshU7qHI0qdIs+pHutfiZu6Ac5ceq0UbQeig/U7got8tCoPilnA+XjN7vwvFPuzPYZh2dpYs0pkmmgce6rdfli2m+a+RcHgyJ+i5V4C4XIl8YQ2SFWApOhAMW61xIanX9u9HHf1RjD07hV+PIFrDTl/DLTU8Wq2PseztAlOTekb0+2zaMQvGnC5Ym24mSJJEsidJi8PmWcW++J+7z4rrLXf9UBYSSQZlhPGdvpFquB7nSq7CgHoltikr3HdD/2UfRnEuCH9XKNpmasDDngsp/a+V/oc60B5Sd7A2gYdU+cbHTUxSZNO1ttbau1ZvXT7kNr4uiW6iJYr3xJy4d2Mcy8O0t2qlwfuSbrR", false),
+        ];
+        for (key_id, signature, valid) in &signatures {
+            let verifier = config.get_pgp_verifier(key_id).unwrap();
+            let signature = data_encoding::BASE64.decode(signature.as_bytes()).unwrap();
+            assert_eq!(
+                *valid,
+                verifier.verify(PGP_MESSAGE, signature.clone()),
+                "{}",
+                key_id
+            );
+            if *valid {
+                assert!(!verifier.verify(b"another message", signature));
+            }
+        }
+    }
+
+    #[test]
+    fn pgp_signature() {
+        let mut config = Config::create_file_test_config();
+        let mut pgp_keys = std::collections::HashMap::new();
+        pgp_keys.insert("ed".to_string(), PGP_ED25519_PUBLIC_KEY.to_string());
+        pgp_keys.insert("rsa".to_string(), PGP_RSA_PUBLIC_KEY.to_string());
+        config.pgp_keys = Some(pgp_keys);
+        // `gpg --detach-sign` of the signed message of the files, base64 encoded
+        let signatures = [
+            ("ed", "iIcEABYIAC8WIQSIgbt+GmZkUDAES0xHThjffQTPXgUCatJJ9REcdGVzdEBleGFtcGxlLmNvbQAKCRBHThjffQTPXtU/AP9WeTiLpoBh0UtfY/OibuyAl13OgmZN7oSYJSeVp+LhmgD8DofB+1JwbMqtIWNZinSztCEruPVUcfKGL4iYzyi4VwM="),
+            ("rsa", "iQFEBAABCgAuFiEErfd8nh4kQ363haN/XaHNKTm9VPcFAmrSSfUQHHJzYUBleGFtcGxlLmNvbQAKCRBdoc0pOb1U98sCCACqXrvMGd7Twixma8nKLjFC/0Snv9qZk26eB8s9RLD6C1G5uwX++Pw15wljCXD5DAanObCDMmc78/ZwODWzCyjns+VM0SkNQrTzCNPzIj/u9lvWOB8NqkAg5bEnnKkknbSl5TbRDq3ySVya5DUQJ2cqmb9TNwHoBNYvax/HCk46aQ41Tpn0IYvj6zTPiDh/WwoFDKojeCXKMjSv1nc/E0kEabAlbgUU8RV6I2pYKl2PKKAxa+2CA+vfzulbkhr17Qn5qO9sDtgGd1dLkuUhYmRMC31xgQ2CRw6KG3lpZ+WHRdcsvInVUVZKBs+rk1OhTPAL6T+ZkE5MdH8BiGVN9xGS"),
+        ];
+        let files = vec![file("a", "x")];
+        for (key_id, signature) in &signatures {
+            let mut artifact = crate::metadata::Artifact {
+                version: semver::Version::new(1, 0, 0),
+                pushed_at: None,
+                signatures: vec![Signature {
+                    key_id: key_id.to_string(),
+                    signature_method: SignatureMethod::Pgp,
+                    signature: signature.to_string(),
+                    scheme_version: Some(3),
+                }],
+                files: files.clone(),
+                labels: Labels::new(),
+            };
+            assert!(artifact.verify_signature(&config).unwrap());
+            artifact.files[0].checksum = "y".to_string();
+            assert!(!artifact.verify_signature(&config).unwrap());
+        }
+
+        // signed with the other key
+        let artifact = crate::metadata::Artifact {
+            version: semver::Version::new(1, 0, 0),
+            pushed_at: None,
+            signatures: vec![Signature {
+                key_id: "rsa".to_string(),
+                signature_method: SignatureMethod::Pgp,
+                signature: signatures[0].1.to_string(),
+                scheme_version: Some(3),
+            }],
+            files,
+            labels: Labels::new(),
+        };
+        assert!(!artifact.verify_signature(&config).unwrap());
+
+        config
+            .pgp_keys
+            .as_mut()
+            .unwrap()
+            .insert("rsa".to_string(), "not a key".to_string());
+        assert!(artifact.verify_signature(&config).is_err());
+
+        // verification only
+        config.publish_parameters.as_mut().unwrap().signature_method = SignatureMethod::Pgp;
+        assert!(config.get_publish_algorithm().is_err());
+    }
+
+    #[test]
+    fn digest_file_buffer_size() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), &data).unwrap();
+        let expected = ring::digest::digest(&ring::digest::SHA256, &data);
+        for buffer_size in &[1, 4096, 10_000, 1024 * 1024] {
+            let digest =
+                super::digest_file(file.path(), &ring::digest::SHA256, *buffer_size).unwrap();
+            assert_eq!(expected.as_ref(), digest.as_ref());
+        }
     }
 }
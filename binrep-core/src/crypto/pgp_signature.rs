@@ -0,0 +1,196 @@
+//! Verification of OpenPGP detached signatures, eg. made out of band with
+//! `gpg --detach-sign`. Signing is not supported.
+//!
+//! The self-signatures and subkey bindings of the configured keys are verified when the keys are
+//! loaded, revoked keys are rejected. A signature is accepted if it was made by the primary key
+//! or a signing subkey that was not expired at the time, and if it has not expired itself.
+use crate::config::{Config, ConfigValidationError};
+use crate::crypto::Verifier;
+use chrono::{DateTime, Utc};
+use log::debug;
+use pgp::packet::{self, SignatureType};
+use pgp::types::PublicKeyTrait;
+use pgp::{Deserializable, SignedPublicKey, StandaloneSignature};
+
+#[derive(thiserror::Error, Debug)]
+enum PgpError {
+    #[error(transparent)]
+    Pgp(#[from] pgp::errors::Error),
+    #[error("{0}")]
+    Rejected(&'static str),
+}
+
+/// A key able to sign, with the end of its validity
+struct SigningKey<K> {
+    key: K,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// Verify signatures with the primary key or the signing subkeys of an OpenPGP public key block
+pub struct PgpVerifier {
+    primary_key: Option<SigningKey<packet::PublicKey>>,
+    subkeys: Vec<SigningKey<packet::PublicSubkey>>,
+}
+
+impl Verifier for PgpVerifier {
+    fn verify(&self, msg: &[u8], signature: Vec<u8>) -> bool {
+        match StandaloneSignature::from_bytes(signature.as_slice()) {
+            Ok(signature) => match self.check(&signature.signature, msg, Utc::now()) {
+                Ok(()) => true,
+                Err(e) => {
+                    debug!("Invalid PGP signature: {}", e);
+                    false
+                }
+            },
+            Err(e) => {
+                debug!("Cannot parse PGP signature: {}", e);
+                false
+            }
+        }
+    }
+}
+
+impl Config {
+    pub(crate) fn get_pgp_verifier(
+        &self,
+        key_id: &str,
+    ) -> Result<PgpVerifier, ConfigValidationError> {
+        let armored_key = self
+            .pgp_keys
+            .as_ref()
+            .ok_or(ConfigValidationError::NoPgpKeysConfigured)?
+            .get(key_id)
+            .ok_or(ConfigValidationError::PgpKeyNotFound {
+                key_id: key_id.to_string(),
+            })?;
+        PgpVerifier::new(armored_key).map_err(|e| ConfigValidationError::MalformedPgpKey {
+            cause: e.to_string(),
+        })
+    }
+}
+
+impl PgpVerifier {
+    fn new(armored_key: &str) -> Result<Self, PgpError> {
+        let (key, _) = SignedPublicKey::from_string(armored_key)?;
+        // self-signatures, subkey bindings and revocations
+        key.verify()?;
+        if !key.details.revocation_signatures.is_empty() {
+            Err(PgpError::Rejected("the key is revoked"))?;
+        }
+        let self_signature = latest(key.details.users.iter().flat_map(|user| &user.signatures))
+            .ok_or(PgpError::Rejected("the key has no self-signature"))?;
+        // keys without flags predate them, they can sign
+        let flags = self_signature.key_flags();
+        let primary_key = if flags == Default::default() || flags.sign() {
+            Some(SigningKey {
+                expires_at: expires_at(&key.primary_key, self_signature),
+                key: key.primary_key.clone(),
+            })
+        } else {
+            None
+        };
+        let mut subkeys = Vec::new();
+        for subkey in &key.public_subkeys {
+            if subkey
+                .signatures
+                .iter()
+                .any(|signature| signature.typ() == SignatureType::SubkeyRevocation)
+            {
+                debug!("Skipping revoked PGP subkey {:?}", subkey.key.key_id());
+                continue;
+            }
+            let binding = match latest(&subkey.signatures) {
+                Some(binding) if binding.key_flags().sign() => binding,
+                _ => continue,
+            };
+            // a signing subkey must also certify its binding to the primary key
+            binding
+                .embedded_signature()
+                .ok_or(PgpError::Rejected(
+                    "signing subkey without primary key binding signature",
+                ))?
+                .verify_backwards_key_binding(&subkey.key, &key.primary_key)?;
+            subkeys.push(SigningKey {
+                expires_at: expires_at(&subkey.key, binding),
+                key: subkey.key.clone(),
+            });
+        }
+        if primary_key.is_none() && subkeys.is_empty() {
+            Err(PgpError::Rejected("no key able to sign"))?;
+        }
+        Ok(Self {
+            primary_key,
+            subkeys,
+        })
+    }
+
+    fn check(
+        &self,
+        signature: &packet::Signature,
+        msg: &[u8],
+        now: DateTime<Utc>,
+    ) -> Result<(), PgpError> {
+        if signature.typ() != SignatureType::Binary {
+            Err(PgpError::Rejected("not a binary document signature"))?;
+        }
+        let created = *signature
+            .created()
+            .ok_or(PgpError::Rejected("no signature creation time"))?;
+        if let Some(validity) = signature.signature_expiration_time() {
+            if created + *validity <= now {
+                Err(PgpError::Rejected("the signature has expired"))?;
+            }
+        }
+        if let Some(primary_key) = self
+            .primary_key
+            .as_ref()
+            .filter(|k| issued_by(signature, &k.key))
+        {
+            return primary_key.check(signature, created, msg);
+        }
+        match self.subkeys.iter().find(|k| issued_by(signature, &k.key)) {
+            Some(subkey) => subkey.check(signature, created, msg),
+            None => Err(PgpError::Rejected(
+                "not signed by a signing key of this key block",
+            )),
+        }
+    }
+}
+
+impl<K: PublicKeyTrait> SigningKey<K> {
+    fn check(
+        &self,
+        signature: &packet::Signature,
+        created: DateTime<Utc>,
+        msg: &[u8],
+    ) -> Result<(), PgpError> {
+        if created < *self.key.created_at() {
+            Err(PgpError::Rejected("the signature predates the key"))?;
+        }
+        // signatures made while the key was valid stay valid
+        if self.expires_at.map(|expires_at| created >= expires_at) == Some(true) {
+            Err(PgpError::Rejected("the key was expired when signing"))?;
+        }
+        Ok(signature.verify(&self.key, msg)?)
+    }
+}
+
+/// Most recent of `signatures`, the one in effect
+fn latest<'a>(
+    signatures: impl IntoIterator<Item = &'a packet::Signature>,
+) -> Option<&'a packet::Signature> {
+    signatures
+        .into_iter()
+        .max_by_key(|signature| signature.created().cloned())
+}
+
+fn expires_at(key: &impl PublicKeyTrait, signature: &packet::Signature) -> Option<DateTime<Utc>> {
+    signature
+        .key_expiration_time()
+        .map(|validity| *key.created_at() + *validity)
+}
+
+fn issued_by(signature: &packet::Signature, key: &impl PublicKeyTrait) -> bool {
+    signature.issuer().contains(&&key.key_id())
+        || signature.issuer_fingerprint().contains(&&key.fingerprint())
+}
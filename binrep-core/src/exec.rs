@@ -3,63 +3,352 @@ use crate::file_utils::path_concat2;
 use crate::metadata::Artifact;
 use anyhow::Error;
 use core::borrow::Borrow;
-use std::path::Path;
-use std::process::{Command, ExitStatus};
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::Duration;
 
 #[derive(thiserror::Error, Debug)]
-#[error("Command {command} returned with status {exit_status}")]
+#[error("Command {command} {}", outcome(.exit_status, .timeout))]
 pub struct ExecutionError {
     pub command: String,
     pub exit_status: ExitStatus,
     pub output_lines: Vec<Line>,
+    /// Set if the command was killed after running longer than this timeout
+    pub timeout: Option<Duration>,
+}
+
+/// Placeholder replaced by the path of each file, the command is run once per file
+const FILE_PLACEHOLDER: &str = "{}";
+/// Placeholder replaced by the paths of all the files, the command is run once
+const FILES_PLACEHOLDER: &str = "{files}";
+
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "Command {command} uses both {{}} (run once per file) and {{files}} (run once with all \
+    the files), use only one of them"
+)]
+pub struct AmbiguousPlaceholdersError {
+    pub command: String,
+}
+
+fn outcome(exit_status: &ExitStatus, timeout: &Option<Duration>) -> String {
+    match timeout {
+        Some(timeout) => format!("timed out after {}s", timeout.as_secs_f64()),
+        None => format!("returned with status {}", exit_status),
+    }
+}
+
+/// Options altering the behavior of [`exec`]
+#[derive(Debug, Clone, Default)]
+pub struct ExecOptions {
+    /// Kill the command if still running after this duration
+    pub timeout: Option<Duration>,
+    /// Working directory of the command, default to the directory the artifact was pulled to
+    pub working_dir: Option<PathBuf>,
 }
 
 pub fn exec<P: AsRef<Path>>(
+    artifact_name: &str,
     artifact: &Artifact,
     pull_directory: P,
     command: &Option<String>,
+    options: &ExecOptions,
 ) -> Result<Option<Vec<Line>>, Error> {
     match command {
         None => Ok(None),
         Some(command) => {
-            if command.contains("{}") {
+            // file paths stay valid from the working directory
+            let pull_directory = std::path::absolute(pull_directory)?;
+            let working_dir = options.working_dir.as_ref().unwrap_or(&pull_directory);
+            let env = artifact_env(artifact_name, artifact, &pull_directory)?;
+            if command.contains(FILE_PLACEHOLDER) && command.contains(FILES_PLACEHOLDER) {
+                Err(AmbiguousPlaceholdersError {
+                    command: command.clone(),
+                })?;
+            }
+            if command.contains(FILE_PLACEHOLDER) {
                 let mut ret = vec![];
                 for file in &artifact.files {
                     let path = path_concat2(&pull_directory, &file.name);
-                    let specific_command = command.replace("{}", path.to_string_lossy().borrow());
-                    ret.append(&mut exec_command(&specific_command, artifact)?);
+                    let specific_command =
+                        command.replace(FILE_PLACEHOLDER, path.to_string_lossy().borrow());
+                    ret.append(&mut exec_command(
+                        &specific_command,
+                        &env,
+                        working_dir,
+                        options.timeout,
+                    )?);
                 }
                 Ok(Some(ret))
+            } else if command.contains(FILES_PLACEHOLDER) {
+                let paths: Vec<String> = artifact
+                    .files
+                    .iter()
+                    .map(|file| {
+                        shell_quote(&path_concat2(&pull_directory, &file.name).to_string_lossy())
+                    })
+                    .collect();
+                Ok(Some(exec_command(
+                    &command.replace(FILES_PLACEHOLDER, &paths.join(" ")),
+                    &env,
+                    working_dir,
+                    options.timeout,
+                )?))
             } else {
-                Ok(Some(exec_command(command.as_str(), artifact)?))
+                Ok(Some(exec_command(
+                    command.as_str(),
+                    &env,
+                    working_dir,
+                    options.timeout,
+                )?))
             }
         }
     }
 }
 
-fn add_artifact_env(cmd: &mut Command, artifact: &Artifact) {
-    cmd.env("BINREP_ARTIFACT_VERSION", artifact.version.to_string());
+/// Quote `arg` so that the shell running the command reads it as a single argument
+fn shell_quote(arg: &str) -> String {
+    if cfg!(target_os = "windows") {
+        format!("\"{}\"", arg.replace('"', "\"\""))
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Environment variables describing the pulled artifact given to the command:
+///  - `BINREP_ARTIFACT_NAME` and `BINREP_ARTIFACT_VERSION`
+///  - `BINREP_DESTINATION_DIR`: absolute path of the directory the artifact was pulled to
+///  - `BINREP_FILE_COUNT`: number of files of the artifact
+///  - `BINREP_FILES`: absolute paths of the files, one per line
+///  - `BINREP_CHECKSUM_<i>`: checksum of the i-th file (starting at 0) of `BINREP_FILES`, as
+///    written in the artifact metadata
+fn artifact_env<P: AsRef<Path>>(
+    artifact_name: &str,
+    artifact: &Artifact,
+    pull_directory: P,
+) -> Result<Vec<(String, String)>, Error> {
+    let destination_dir = std::path::absolute(pull_directory)?;
+    let files: Vec<String> = artifact
+        .files
+        .iter()
+        .map(|file| {
+            path_concat2(&destination_dir, &file.name)
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    let mut env = vec![
+        (
+            "BINREP_ARTIFACT_NAME".to_string(),
+            artifact_name.to_string(),
+        ),
+        (
+            "BINREP_ARTIFACT_VERSION".to_string(),
+            artifact.version.to_string(),
+        ),
+        (
+            "BINREP_DESTINATION_DIR".to_string(),
+            destination_dir.to_string_lossy().into_owned(),
+        ),
+        (
+            "BINREP_FILE_COUNT".to_string(),
+            artifact.files.len().to_string(),
+        ),
+        ("BINREP_FILES".to_string(), files.join("\n")),
+    ];
+    for (index, file) in artifact.files.iter().enumerate() {
+        env.push((format!("BINREP_CHECKSUM_{}", index), file.checksum.clone()));
+    }
+    Ok(env)
 }
 
-fn exec_command(command: &str, artifact: &Artifact) -> Result<Vec<Line>, Error> {
+fn exec_command(
+    command: &str,
+    env: &[(String, String)],
+    working_dir: &Path,
+    timeout: Option<Duration>,
+) -> Result<Vec<Line>, Error> {
     let status = if cfg!(target_os = "windows") {
         let mut cmd = std::process::Command::new("cmd");
         cmd.args(&["/C", &command]);
-        add_artifact_env(&mut cmd, artifact);
-        extexec(cmd, true)?
+        cmd.envs(env.iter().cloned()).current_dir(working_dir);
+        extexec(cmd, true, timeout)?
     } else {
         let mut cmd = std::process::Command::new("sh");
         cmd.arg("-c").arg(&command);
-        add_artifact_env(&mut cmd, artifact);
-        extexec(cmd, true)?
+        cmd.envs(env.iter().cloned()).current_dir(working_dir);
+        extexec(cmd, true, timeout)?
     };
-    if !status.exit_status.success() {
+    if status.timed_out || !status.exit_status.success() {
         Err(ExecutionError {
             command: String::from(command),
             exit_status: status.exit_status,
+            timeout: timeout.filter(|_| status.timed_out),
             output_lines: status.output_lines,
         })?
     } else {
         Ok(status.output_lines)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::extended_exec::Type;
+    use crate::metadata::{Artifact, ChecksumMethod, File, Labels};
+    use semver::Version;
+
+    #[test]
+    fn artifact_env() {
+        let file = |name: &str, checksum: &str| File {
+            name: name.to_string(),
+            checksum: checksum.to_string(),
+            checksum_method: ChecksumMethod::Sha256,
+            unix_mode: None,
+            size: None,
+        };
+        let artifact = Artifact {
+            version: Version::new(1, 2, 3),
+            pushed_at: None,
+            files: vec![file("binrep", "c2hhMQ=="), file("README.md", "c2hhMg==")],
+            signatures: vec![],
+            labels: Labels::new(),
+        };
+        let pull_dir = tempfile::tempdir().unwrap();
+        let lines = super::exec(
+            "binrep",
+            &artifact,
+            pull_dir.path(),
+            &Some("env".to_string()),
+            &Default::default(),
+        )
+        .unwrap()
+        .unwrap();
+        let env: Vec<String> = lines
+            .iter()
+            .filter(|line| line.line_type == Type::Out)
+            .map(|line| String::from_utf8_lossy(&line.line).into_owned())
+            .collect();
+        let dir = pull_dir.path().to_string_lossy();
+        for expected in &[
+            "BINREP_ARTIFACT_NAME=binrep".to_string(),
+            "BINREP_ARTIFACT_VERSION=1.2.3".to_string(),
+            format!("BINREP_DESTINATION_DIR={}", dir),
+            "BINREP_FILE_COUNT=2".to_string(),
+            // the value spans two lines
+            format!("BINREP_FILES={}/binrep", dir),
+            format!("{}/README.md", dir),
+            "BINREP_CHECKSUM_0=c2hhMQ==".to_string(),
+            "BINREP_CHECKSUM_1=c2hhMg==".to_string(),
+        ] {
+            assert!(env.contains(expected), "{} not in {:?}", expected, env);
+        }
+    }
+
+    fn artifact_with_files(names: &[&str]) -> Artifact {
+        Artifact {
+            version: Version::new(1, 2, 3),
+            pushed_at: None,
+            files: names
+                .iter()
+                .map(|name| File {
+                    name: name.to_string(),
+                    checksum: "c2hhMQ==".to_string(),
+                    checksum_method: ChecksumMethod::Sha256,
+                    unix_mode: None,
+                    size: None,
+                })
+                .collect(),
+            signatures: vec![],
+            labels: Labels::new(),
+        }
+    }
+
+    /// Standard output lines of `command` run on an artifact made of `files`
+    fn exec_output(files: &[&str], command: &str) -> Result<Vec<String>, anyhow::Error> {
+        let lines = super::exec(
+            "binrep",
+            &artifact_with_files(files),
+            "/srv/binrep",
+            &Some(command.to_string()),
+            // the pull directory does not exist
+            &super::ExecOptions {
+                working_dir: Some(std::env::temp_dir()),
+                ..Default::default()
+            },
+        )?
+        .unwrap();
+        Ok(lines
+            .iter()
+            .filter(|line| line.line_type == Type::Out)
+            .map(|line| String::from_utf8_lossy(&line.line).into_owned())
+            .collect())
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(
+            vec!["/srv/binrep/binrep", "/srv/binrep/README.md"],
+            exec_output(&["binrep", "README.md"], "echo {}").unwrap()
+        );
+        let files = ["binrep", "it's a file"];
+        // a single invocation, one argument per file
+        assert_eq!(
+            vec!["2 /srv/binrep/binrep|/srv/binrep/it's a file"],
+            exec_output(&files, "set -- {files}; IFS='|'; echo \"$# $*\"",).unwrap()
+        );
+        assert_eq!(
+            vec!["no files"],
+            exec_output(&files, "echo no files").unwrap()
+        );
+        let error = exec_output(&files, "cp {} {files}").unwrap_err();
+        assert!(error
+            .downcast_ref::<super::AmbiguousPlaceholdersError>()
+            .is_some());
+    }
+
+    #[test]
+    fn working_dir() {
+        let artifact = artifact_with_files(&[]);
+        let pull_dir = tempfile::tempdir().unwrap();
+        let working_dir = tempfile::tempdir().unwrap();
+        let pwd = |options: &super::ExecOptions| {
+            let lines = super::exec(
+                "binrep",
+                &artifact,
+                pull_dir.path(),
+                &Some("pwd".to_string()),
+                options,
+            )
+            .unwrap()
+            .unwrap();
+            String::from_utf8_lossy(&lines.last().unwrap().line).into_owned()
+        };
+        assert_eq!(pull_dir.path().to_string_lossy(), pwd(&Default::default()));
+        assert_eq!(
+            working_dir.path().to_string_lossy(),
+            pwd(&super::ExecOptions {
+                working_dir: Some(working_dir.path().to_path_buf()),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn timeout() {
+        let artifact = artifact_with_files(&[]);
+        let error = super::exec(
+            "binrep",
+            &artifact,
+            ".",
+            &Some("sleep 10".to_string()),
+            &super::ExecOptions {
+                timeout: Some(std::time::Duration::from_millis(200)),
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        let error = error.downcast::<super::ExecutionError>().unwrap();
+        assert_eq!(Some(std::time::Duration::from_millis(200)), error.timeout);
+        assert!(error.to_string().contains("timed out after 0.2s"));
+    }
+}
@@ -2,7 +2,15 @@ use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::io;
 use std::io::{Error, Read, Write};
-use std::process::{Command, ExitStatus, Stdio};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Delay between two checks of the status of a command run with a timeout
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Delay given to the capture threads to forward the last lines of a killed command
+const KILLED_OUTPUT_DELAY: Duration = Duration::from_millis(200);
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum Type {
@@ -31,6 +39,8 @@ impl Debug for Line {
 pub struct Output {
     pub exit_status: ExitStatus,
     pub output_lines: Vec<Line>,
+    /// The command was killed after running longer than the timeout
+    pub timed_out: bool,
 }
 
 fn capture_lines<R: Read + Send + 'static, W: Write + Send + 'static>(
@@ -38,7 +48,7 @@ fn capture_lines<R: Read + Send + 'static, W: Write + Send + 'static>(
     mut duplicate_stream: Option<W>,
     line_sender: crossbeam::channel::Sender<Line>,
     line_type: Type,
-) {
+) -> JoinHandle<()> {
     std::thread::spawn(move || {
         let mut line_buffer = Vec::new();
         for byte in reader.bytes() {
@@ -71,10 +81,42 @@ fn capture_lines<R: Read + Send + 'static, W: Write + Send + 'static>(
                 line_type,
             });
         }
-    });
+    })
+}
+
+/// Wait for `child` to exit for at most `timeout`, none if still running
+fn wait_timeout(child: &mut Child, timeout: Duration) -> Result<Option<ExitStatus>, io::Error> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(exit_status) = child.try_wait()? {
+            return Ok(Some(exit_status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        std::thread::sleep(WAIT_POLL_INTERVAL);
+    }
+}
+
+/// Kill the process group led by `child`: the command and the processes it started
+fn kill_process_group(child: &Child) -> Result<(), io::Error> {
+    // the id of a process group is the pid of its leader
+    if unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) } == -1 {
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() != Some(libc::ESRCH) {
+            return Err(error);
+        }
+    }
+    Ok(())
 }
 
-pub fn extexec(mut command: Command, tee_output_to_std: bool) -> Result<Output, io::Error> {
+/// Run `command`, capturing its output lines. If `timeout` is given, the command is run in its
+/// own process group, killed once it has run for longer.
+pub fn extexec(
+    mut command: Command,
+    tee_output_to_std: bool,
+    timeout: Option<Duration>,
+) -> Result<Output, io::Error> {
     let tee_stderr = if tee_output_to_std {
         Some(std::io::stderr())
     } else {
@@ -94,28 +136,55 @@ pub fn extexec(mut command: Command, tee_output_to_std: bool) -> Result<Output,
         })
         .unwrap(); // we can safely unwrap here: channels cannot be dropped ;)
 
+    if timeout.is_some() {
+        command.process_group(0);
+    }
     let mut child = command
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()?;
 
-    capture_lines(
-        child.stdout.take().unwrap(),
-        tee_stdout,
-        lines_sender.clone(),
-        Type::Out,
-    );
-    capture_lines(
-        child.stderr.take().unwrap(),
-        tee_stderr,
-        lines_sender,
-        Type::Err,
-    );
-    let exit_status = child.wait().unwrap();
-    let output_lines: Vec<_> = line_receiver.iter().collect();
+    let capture_threads = [
+        capture_lines(
+            child.stdout.take().unwrap(),
+            tee_stdout,
+            lines_sender.clone(),
+            Type::Out,
+        ),
+        capture_lines(
+            child.stderr.take().unwrap(),
+            tee_stderr,
+            lines_sender,
+            Type::Err,
+        ),
+    ];
+    let (exit_status, timed_out) = match timeout {
+        None => (child.wait()?, false),
+        Some(timeout) => match wait_timeout(&mut child, timeout)? {
+            Some(exit_status) => (exit_status, false),
+            None => {
+                kill_process_group(&child)?;
+                (child.wait()?, true)
+            }
+        },
+    };
+    let output_lines: Vec<_> = if timed_out {
+        // processes started by the command which left its process group may survive it and keep
+        // its output open: the capture threads are left behind, they stop once the output is
+        // closed
+        let deadline = Instant::now() + KILLED_OUTPUT_DELAY;
+        std::iter::from_fn(|| line_receiver.recv_deadline(deadline).ok()).collect()
+    } else {
+        let output_lines = line_receiver.iter().collect();
+        for capture_thread in capture_threads {
+            let _ = capture_thread.join();
+        }
+        output_lines
+    };
     Ok(Output {
         output_lines,
         exit_status,
+        timed_out,
     })
 }
 
@@ -148,7 +217,7 @@ mod tests {
         let mut cmd = Command::new("bash");
         cmd.arg("-c").arg("echo coucou");
 
-        let output = extexec(cmd, false).unwrap();
+        let output = extexec(cmd, false, None).unwrap();
         assert_eq!(
             vec![
                 Line::cmd(r#""bash" "-c" "echo coucou""#),
@@ -161,7 +230,7 @@ mod tests {
     fn stderr() {
         let mut cmd = Command::new("bash");
         cmd.arg("-c").arg(">&2 echo coucou");
-        let output = extexec(cmd, true).unwrap();
+        let output = extexec(cmd, true, None).unwrap();
         assert_eq!(
             vec![
                 Line::cmd(r#""bash" "-c" ">&2 echo coucou""#),
@@ -176,7 +245,7 @@ mod tests {
         let mut cmd = Command::new("bash");
         cmd.arg("-c")
             .arg("echo foo\nsleep 1\n>&2 echo coucou\nsleep 1;echo bar");
-        let output = extexec(cmd, true).unwrap();
+        let output = extexec(cmd, true, None).unwrap();
         assert_eq!(
             vec![
                 Line::cmd(r#""bash" "-c" "echo foo\nsleep 1\n>&2 echo coucou\nsleep 1;echo bar""#),
@@ -190,7 +259,7 @@ mod tests {
         let mut cmd = Command::new("bash");
         cmd.arg("-c")
             .arg("echo foo\nsleep 1\n>&2 echo coucou\nsleep 1;echo bar");
-        let output = extexec(cmd, false).unwrap();
+        let output = extexec(cmd, false, None).unwrap();
         assert_eq!(
             vec![
                 Line::cmd(r#""bash" "-c" "echo foo\nsleep 1\n>&2 echo coucou\nsleep 1;echo bar""#),
@@ -201,4 +270,48 @@ mod tests {
             output.output_lines
         );
     }
+
+    #[test]
+    fn timeout() {
+        let mut cmd = Command::new("bash");
+        // sleep keeps the output open once bash is killed
+        cmd.arg("-c").arg("echo foo\nsleep 10\necho bar");
+        let started = Instant::now();
+        let output = extexec(cmd, false, Some(Duration::from_millis(500))).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(output.timed_out);
+        assert!(!output.exit_status.success());
+        assert!(output.output_lines.contains(&Line::out("foo")));
+        assert!(!output.output_lines.contains(&Line::out("bar")));
+
+        let mut cmd = Command::new("bash");
+        cmd.arg("-c").arg("echo foo");
+        let output = extexec(cmd, false, Some(Duration::from_secs(10))).unwrap();
+        assert!(!output.timed_out);
+        assert!(output.exit_status.success());
+    }
+
+    #[test]
+    fn timeout_kills_started_processes() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("sleep 30 & echo $!; wait");
+        let output = extexec(cmd, false, Some(Duration::from_millis(500))).unwrap();
+        assert!(output.timed_out);
+        let pid = output
+            .output_lines
+            .iter()
+            .find(|line| line.line_type == Out)
+            .map(|line| String::from_utf8_lossy(&line.line).trim().to_string())
+            .unwrap();
+        // gone, or a zombie if nothing reaps the orphaned sleep
+        let running = || match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => !stat.contains(") Z "),
+            Err(_) => false,
+        };
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while running() {
+            assert!(Instant::now() < deadline, "sleep {} still running", pid);
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
 }
@@ -2,15 +2,28 @@ use anyhow::Error;
 use fs2::FileExt;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::collections::BTreeSet;
 use std::fs::File;
 use std::io::{ErrorKind, Read, Write};
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use tempfile::tempdir;
 
 #[derive(thiserror::Error, Debug)]
 #[error("{0} is not a directory")]
 pub struct PathIsNotADirectoryError(pub String);
 
+#[derive(thiserror::Error, Debug)]
+#[error("Unable to lock {path} within {timeout:?}, another process holds the lock")]
+pub struct LockTimeout {
+    pub path: String,
+    pub timeout: Duration,
+}
+
+/// Longest delay between two attempts of [`LockFile::create_and_lock_timeout`]
+const MAX_LOCK_RETRY_DELAY: Duration = Duration::from_secs(1);
+
 pub struct LockFile<P: AsRef<Path>> {
     lock_file_path: P,
     lock_file: File,
@@ -25,13 +38,61 @@ impl<P: AsRef<Path>> LockFile<P> {
             lock_file_path,
         })
     }
+
+    /// Like [`LockFile::create_and_lock`], but retry with an increasing delay while another
+    /// process holds the lock, failing with [`LockTimeout`] once `timeout` is elapsed
+    pub async fn create_and_lock_timeout(
+        lock_file_path: P,
+        timeout: Duration,
+    ) -> Result<Self, Error> {
+        let start = Instant::now();
+        let mut delay = Duration::from_millis(10);
+        loop {
+            let lock_file = File::create(&lock_file_path)?;
+            match lock_file.try_lock_exclusive() {
+                // the previous holder removes the file on release: the lock is only valid if
+                // the path still points to the locked file
+                Ok(_) if is_same_file(&lock_file, &lock_file_path)? => {
+                    return Ok(Self {
+                        lock_file,
+                        lock_file_path,
+                    })
+                }
+                Ok(_) => continue,
+                Err(e) if e.kind() != fs2::lock_contended_error().kind() => Err(e)?,
+                Err(_) => {}
+            }
+            let remaining = timeout.saturating_sub(start.elapsed());
+            if remaining.is_zero() {
+                Err(LockTimeout {
+                    path: lock_file_path.as_ref().to_string_lossy().into(),
+                    timeout,
+                })?;
+            }
+            tokio::time::sleep(delay.min(remaining)).await;
+            delay = (delay * 2).min(MAX_LOCK_RETRY_DELAY);
+        }
+    }
+}
+
+fn is_same_file<P: AsRef<Path>>(file: &File, path: P) -> Result<bool, std::io::Error> {
+    match std::fs::metadata(path) {
+        Ok(metadata) => {
+            let file_metadata = file.metadata()?;
+            Ok(file_metadata.dev() == metadata.dev() && file_metadata.ino() == metadata.ino())
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
 }
 
 impl<P: AsRef<Path>> Drop for LockFile<P> {
     #[allow(unused_must_use)]
     fn drop(&mut self) {
-        self.lock_file.unlock();
+        // removed while still locked: a waiter locking the released file then sees it is not
+        // the lock file anymore
         std::fs::remove_file(&self.lock_file_path);
+        self.lock_file.unlock();
     }
 }
 
@@ -54,6 +115,14 @@ pub fn mkdirs<P: AsRef<Path>>(dir: P) -> Result<(), Error> {
     Ok(())
 }
 
+/// Create the directory of `file` and its parents
+pub fn mkdirs_parent<P: AsRef<Path>>(file: P) -> Result<(), Error> {
+    match file.as_ref().parent() {
+        Some(dir) => mkdirs(dir),
+        None => Ok(()),
+    }
+}
+
 pub fn mv<S: AsRef<Path>, D: AsRef<Path>>(src: S, dst: D) -> Result<(), std::io::Error> {
     info!(
         "mv {} to {}",
@@ -69,6 +138,30 @@ pub fn mv<S: AsRef<Path>, D: AsRef<Path>>(src: S, dst: D) -> Result<(), std::io:
     }
 }
 
+/// Flush the content of `files` then the entries of `dir` and of its subdirectories holding
+/// `files` (eg. created for nested file names) to disk
+pub fn sync_to_disk<F: AsRef<Path>, D: AsRef<Path>>(files: &[F], dir: D) -> Result<(), Error> {
+    let dir = dir.as_ref();
+    let mut sub_dirs = BTreeSet::new();
+    for file in files {
+        let file = file.as_ref();
+        File::open(file)?.sync_all()?;
+        sub_dirs.extend(
+            file.ancestors()
+                .skip(1)
+                .take_while(|parent| *parent != dir && parent.starts_with(dir))
+                .map(Path::to_path_buf),
+        );
+    }
+    // directory entries (renames, removals, created directories) are only durable once the
+    // directory is synced: subdirectories first, then their parents
+    for sub_dir in sub_dirs.iter().rev() {
+        File::open(sub_dir)?.sync_all()?;
+    }
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
 pub fn path_concat2<T: AsRef<Path>, U: AsRef<Path>>(p1: T, p2: U) -> PathBuf {
     [p1.as_ref(), p2.as_ref().into()]
         .iter()
@@ -83,6 +176,18 @@ pub fn read_sane_from_file<P: AsRef<Path>, D: DeserializeOwned>(file: P) -> Resu
     Ok(sane::from_str(&s)?)
 }
 
+/// Read a configuration file: TOML or YAML depending on its extension (`.toml`, `.yaml`,
+/// `.yml`), else sane
+pub fn read_config_from_file<P: AsRef<Path>, D: DeserializeOwned>(file: P) -> Result<D, Error> {
+    let extension = file.as_ref().extension().and_then(|ext| ext.to_str());
+    let s = std::fs::read_to_string(&file)?;
+    match extension {
+        Some("toml") => Ok(toml::from_str(&s)?),
+        Some("yaml" | "yml") => Ok(serde_yaml::from_str(&s)?),
+        _ => Ok(sane::from_str(&s)?),
+    }
+}
+
 pub fn write_sane_to_file<P: AsRef<Path>, S: Serialize>(file: P, meta: &S) -> Result<(), Error> {
     let mut file = File::create(file)?;
     file.write_all(sane::to_string(meta)?.as_bytes())?;
@@ -102,3 +207,77 @@ fn test_mkdirs() {
     mkdirs(&non_existing).unwrap();
     assert!(std::fs::metadata(&non_existing).unwrap().is_dir());
 }
+
+#[cfg(test)]
+mod test {
+    use super::{sync_to_disk, LockFile, LockTimeout};
+    use fs2::FileExt;
+    use std::fs::File;
+    use std::time::{Duration, Instant};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn lock_timeout() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(".binrep.lock");
+        let lock = LockFile::create_and_lock(path.clone()).unwrap();
+        assert!(LockFile::create_and_lock(&path).is_err());
+
+        let start = Instant::now();
+        let err = LockFile::create_and_lock_timeout(&path, Duration::from_millis(100))
+            .await
+            .err()
+            .unwrap();
+        assert!(err.downcast_ref::<LockTimeout>().is_some(), "{}", err);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        // released by another thread while waiting
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            drop(lock);
+        });
+        LockFile::create_and_lock_timeout(&path, Duration::from_secs(10))
+            .await
+            .unwrap();
+        release.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn lock_three_contenders() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(".binrep.lock");
+        let first = LockFile::create_and_lock(path.clone()).unwrap();
+        // a waiter opened the lock file before its release
+        let stale = File::create(&path).unwrap();
+        drop(first);
+        let third = LockFile::create_and_lock_timeout(&path, Duration::from_secs(1))
+            .await
+            .unwrap();
+        // the released file can be locked again, but it is not the lock file anymore
+        stale.try_lock_exclusive().unwrap();
+        assert!(!super::is_same_file(&stale, &path).unwrap());
+        let err = LockFile::create_and_lock_timeout(&path, Duration::from_millis(50))
+            .await
+            .err()
+            .unwrap();
+        assert!(err.downcast_ref::<LockTimeout>().is_some(), "{}", err);
+        drop(third);
+        LockFile::create_and_lock_timeout(&path, Duration::from_secs(1))
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn sync_nested_files() {
+        let dir = tempdir().unwrap();
+        let nested = dir.path().join("docs").join("api");
+        std::fs::create_dir_all(&nested).unwrap();
+        let files = [dir.path().join("bin"), nested.join("index.html")];
+        for file in &files {
+            std::fs::write(file, "content").unwrap();
+        }
+        sync_to_disk(&files, dir.path()).unwrap();
+        // every file must exist
+        assert!(sync_to_disk(&[dir.path().join("missing")], dir.path()).is_err());
+    }
+}
@@ -6,8 +6,10 @@
 #[macro_use]
 extern crate log;
 
+pub mod artifact_builder;
 mod backend;
 pub mod binrep;
+pub mod clock;
 pub mod config;
 pub mod config_resolver;
 mod crypto;
@@ -15,10 +17,12 @@ pub mod exec;
 pub mod extended_exec;
 pub mod file_utils;
 pub mod metadata;
+pub mod notify;
 mod path;
 pub mod progress;
 mod repository;
 pub mod slack;
 
+pub use chrono;
 pub use semver;
 pub use slack_hook3;
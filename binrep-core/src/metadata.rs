@@ -1,6 +1,7 @@
 use semver::Version;
 use serde::Deserialize;
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::fmt;
 
@@ -29,6 +30,41 @@ impl Versions {
     }
 }
 
+/// Highest released (non pre-release) version of an artifact, written along the versions list
+/// so that it is read without the whole list
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Latest {
+    pub version: Option<Version>,
+}
+
+impl Latest {
+    pub fn of(versions: &Versions) -> Self {
+        Self {
+            version: versions
+                .versions
+                .iter()
+                .filter(|version| version.pre.is_empty())
+                .max()
+                .cloned(),
+        }
+    }
+}
+
+/// Version a channel (eg. `stable`) of an artifact points to
+#[derive(Serialize, Deserialize, Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Channel {
+    pub version: Version,
+}
+
+/// Metadata of all versions of an artifact, used by the flat repository layout
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Ord, PartialOrd, Eq, PartialEq)]
+pub struct FlatArtifact {
+    pub versions: Vec<Artifact>,
+}
+
+/// Free form key-value metadata of an artifact (eg. `git_sha`, `branch`), covered by the signature
+pub type Labels = BTreeMap<String, String>;
+
 #[derive(Serialize, Deserialize, Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Copy)]
 pub enum ChecksumMethod {
     #[serde(rename = "SHA256")]
@@ -45,6 +81,19 @@ pub struct File {
     pub checksum: String,
     pub checksum_method: ChecksumMethod,
     pub unix_mode: Option<u32>,
+    /// Size in bytes, not part of the signature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+impl fmt::Display for ChecksumMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ChecksumMethod::Sha256 => "SHA256",
+            ChecksumMethod::Sha384 => "SHA384",
+            ChecksumMethod::Sha512 => "SHA512",
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Copy)]
@@ -57,6 +106,9 @@ pub enum SignatureMethod {
     HmacSha512,
     #[serde(rename = "ED25519")]
     ED25519,
+    /// OpenPGP detached signature, verification only
+    #[serde(rename = "PGP")]
+    Pgp,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
@@ -64,23 +116,102 @@ pub struct Signature {
     pub key_id: String,
     pub signature: String,
     pub signature_method: SignatureMethod,
+    /// Version of the signed message format, none for the original format (1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheme_version: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+#[serde(from = "RawArtifact", into = "RawArtifact")]
 pub struct Artifact {
     pub version: Version,
-    pub signature: Signature,
+    /// RFC3339 timestamp of the push
+    pub pushed_at: Option<String>,
+    /// All signatures of the artifact, the first one is the primary signature
+    pub signatures: Vec<Signature>,
     pub files: Vec<File>,
+    pub labels: Labels,
+}
+
+/// Serialized form of an [`Artifact`]: the primary signature is stored in `signature`, as
+/// understood by older versions, the others in `additional_signatures`.
+#[derive(Serialize, Deserialize)]
+struct RawArtifact {
+    version: Version,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pushed_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<Signature>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    additional_signatures: Vec<Signature>,
+    files: Vec<File>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    labels: Labels,
+}
+
+impl From<RawArtifact> for Artifact {
+    fn from(raw: RawArtifact) -> Self {
+        Self {
+            version: raw.version,
+            pushed_at: raw.pushed_at,
+            signatures: raw
+                .signature
+                .into_iter()
+                .chain(raw.additional_signatures)
+                .collect(),
+            files: raw.files,
+            labels: raw.labels,
+        }
+    }
+}
+
+impl From<Artifact> for RawArtifact {
+    fn from(artifact: Artifact) -> Self {
+        let mut signatures = artifact.signatures.into_iter();
+        Self {
+            version: artifact.version,
+            pushed_at: artifact.pushed_at,
+            signature: signatures.next(),
+            additional_signatures: signatures.collect(),
+            files: artifact.files,
+            labels: artifact.labels,
+        }
+    }
+}
+
+impl Artifact {
+    /// Sum of the file sizes, none if the size of a file is unknown (pushed by an older version).
+    /// The sizes are not signed: the sum saturates instead of overflowing.
+    pub fn total_size(&self) -> Option<u64> {
+        self.files.iter().try_fold(0u64, |total, file| {
+            file.size.map(|size| total.saturating_add(size))
+        })
+    }
 }
 
 impl fmt::Display for Artifact {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} - {}", self.version, self.signature.signature)?;
+        write!(f, "{}", self.version)?;
+        for signature in &self.signatures {
+            write!(f, " - {}", signature.signature)?;
+        }
+        if let Some(pushed_at) = &self.pushed_at {
+            write!(f, "\n  pushed at {}", pushed_at)?;
+        }
+        for (key, value) in &self.labels {
+            write!(f, "\n  label {}={}", key, value)?;
+        }
         for file in &self.files {
             write!(f, "\n  {} - {}", file.name, file.checksum)?;
             if let Some(unix_mode) = file.unix_mode {
                 write!(f, " - {:o}", unix_mode)?;
             }
+            if let Some(size) = file.size {
+                write!(f, " - {} bytes", size)?;
+            }
+        }
+        if let Some(total_size) = self.total_size() {
+            write!(f, "\n  total size {} bytes", total_size)?;
         }
         Ok(())
     }
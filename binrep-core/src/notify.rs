@@ -0,0 +1,110 @@
+//! Notifications posted as a JSON body to a generic webhook, eg. an incident bot
+use serde::Deserialize;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Body sent when none is configured
+pub const DEFAULT_BODY: &str = r#"{"artifact":"{artifact}","version":"{version}","hostname":"{hostname}","status":"{status}"}"#;
+
+/// A webhook not answering within this delay fails the notification instead of blocking the sync
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A config where any value is optional, like the slack one
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct WebhookNotifier {
+    url: Option<String>,
+    /// JSON body template, `{artifact}`, `{version}`, `{hostname}` and `{status}` are replaced
+    /// by their JSON escaped values: they are meant to be used inside JSON strings
+    body: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookNotifierConfig {
+    webhook: Option<WebhookNotifier>,
+}
+
+/// Values substituted in the body template
+pub struct Notification<'a> {
+    pub artifact: &'a str,
+    pub version: &'a str,
+    pub hostname: &'a str,
+    pub status: &'a str,
+}
+
+impl WebhookNotifier {
+    pub fn override_with(&self, config: WebhookNotifier) -> Self {
+        Self {
+            url: config.url.or_else(|| self.url.clone()),
+            body: config.body.or_else(|| self.body.clone()),
+        }
+    }
+
+    /// Body template with the values of the notification substituted
+    pub fn render(&self, notification: &Notification) -> String {
+        let escape = |value: &str| {
+            let quoted = serde_json::Value::from(value).to_string();
+            quoted[1..quoted.len() - 1].to_string()
+        };
+        self.body
+            .as_deref()
+            .unwrap_or(DEFAULT_BODY)
+            .replace("{artifact}", &escape(notification.artifact))
+            .replace("{version}", &escape(notification.version))
+            .replace("{hostname}", &escape(notification.hostname))
+            .replace("{status}", &escape(notification.status))
+    }
+
+    /// Post the notification if an url is configured, returns whether it was sent
+    pub async fn send(&self, notification: &Notification<'_>) -> anyhow::Result<bool> {
+        if let Some(url) = &self.url {
+            let body: serde_json::Value = serde_json::from_str(&self.render(notification))?;
+            reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()?
+                .post(url)
+                .json(&body)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+impl From<WebhookNotifierConfig> for WebhookNotifier {
+    fn from(c: WebhookNotifierConfig) -> Self {
+        c.webhook.unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Notification, WebhookNotifier};
+
+    #[test]
+    fn render() {
+        let notification = Notification {
+            artifact: "binrep",
+            version: "1.2.3",
+            hostname: "web\"1",
+            status: "updated",
+        };
+        let body: serde_json::Value =
+            serde_json::from_str(&WebhookNotifier::default().render(&notification)).unwrap();
+        assert_eq!("binrep", body["artifact"]);
+        assert_eq!("1.2.3", body["version"]);
+        assert_eq!("web\"1", body["hostname"]);
+        assert_eq!("updated", body["status"]);
+
+        let notifier = WebhookNotifier {
+            url: None,
+            body: Some(r#"{"text":"{artifact} {version} {status} on {hostname}"}"#.to_string()),
+        };
+        assert_eq!(
+            r#"{"text":"binrep 1.2.3 updated on web\"1"}"#,
+            notifier.render(&notification)
+        );
+    }
+}
@@ -2,6 +2,11 @@
 pub fn artifacts() -> &'static str {
     "artifacts.sane"
 }
+/// detached signature of an index file (artifacts or versions list)
+#[inline(always)]
+pub fn signature(path: &str) -> String {
+    vec![path, ".sig"].into_iter().collect()
+}
 pub mod artifact {
     use semver::Version;
     #[inline(always)]
@@ -9,6 +14,21 @@ pub mod artifact {
         vec![artifact_name, "/versions.sane"].into_iter().collect()
     }
     #[inline(always)]
+    pub fn latest(artifact_name: &str) -> String {
+        vec![artifact_name, "/latest.sane"].into_iter().collect()
+    }
+    #[inline(always)]
+    pub fn channel(artifact_name: &str, channel: &str) -> String {
+        vec![artifact_name, "/channels/", channel, ".sane"]
+            .into_iter()
+            .collect()
+    }
+    /// all versions metadata when using the flat layout
+    #[inline(always)]
+    pub fn flat(artifact_name: &str) -> String {
+        vec![artifact_name, ".sane"].into_iter().collect()
+    }
+    #[inline(always)]
     pub fn artifact(artifact_name: &str, artifact_version: &Version) -> String {
         vec![
             artifact_name,
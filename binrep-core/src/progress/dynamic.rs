@@ -0,0 +1,70 @@
+use crate::progress::interactive::{InteractiveProgress, InteractiveProgressReporter};
+use crate::progress::noop::NOOPProgress;
+use crate::progress::{Progress, ProgressReporter};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static SILENT: AtomicBool = AtomicBool::new(false);
+
+/// Reporter selected at runtime (eg. from a command line flag): reports like the
+/// [`InteractiveProgressReporter`] unless silenced with [`DynProgressReporter::set_silent`]
+pub struct DynProgressReporter;
+
+impl DynProgressReporter {
+    /// Silence the progress created from now on, for the whole process
+    pub fn set_silent(silent: bool) {
+        SILENT.store(silent, Ordering::Relaxed);
+    }
+}
+
+impl ProgressReporter for DynProgressReporter {
+    type Output = DynProgress;
+
+    fn create(name: Option<String>, max: Option<usize>) -> Self::Output {
+        if SILENT.load(Ordering::Relaxed) {
+            DynProgress::Silent(NOOPProgress)
+        } else {
+            DynProgress::Interactive(InteractiveProgressReporter::create(name, max))
+        }
+    }
+}
+
+pub enum DynProgress {
+    Silent(NOOPProgress),
+    Interactive(InteractiveProgress),
+}
+
+impl Progress for DynProgress {
+    fn inc(&mut self, amount: usize) {
+        match self {
+            DynProgress::Silent(p) => p.inc(amount),
+            DynProgress::Interactive(p) => p.inc(amount),
+        }
+    }
+
+    fn tick(&mut self) {
+        match self {
+            DynProgress::Silent(p) => p.tick(),
+            DynProgress::Interactive(p) => p.tick(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DynProgress, DynProgressReporter};
+    use crate::progress::ProgressReporter;
+
+    #[test]
+    fn silent() {
+        DynProgressReporter::set_silent(true);
+        assert!(matches!(
+            DynProgressReporter::create(Some("downloading".to_string()), Some(10)),
+            DynProgress::Silent(_)
+        ));
+        DynProgressReporter::set_silent(false);
+        assert!(matches!(
+            DynProgressReporter::create(Some("downloading".to_string()), Some(10)),
+            DynProgress::Interactive(_)
+        ));
+    }
+}
@@ -1,7 +1,14 @@
 use crate::progress::non_interactive::NonInteractiveProgress;
 use crate::progress::{Progress, ProgressReporter};
 use atty::Stream;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::sync::OnceLock;
+
+/// Bars of concurrent downloads are drawn together instead of overwriting each other
+fn multi_progress() -> &'static MultiProgress {
+    static MULTI_PROGRESS: OnceLock<MultiProgress> = OnceLock::new();
+    MULTI_PROGRESS.get_or_init(MultiProgress::new)
+}
 
 /// Reporter that either display a nice progress bar or ticker on interactive
 /// cli session, or use the non interactive reporter when on a non interactive session
@@ -11,9 +18,10 @@ impl ProgressReporter for IndicatifProgressReporter {
     type Output = IndicatifProgress;
 
     fn create(name: Option<String>, max: Option<usize>) -> Self::Output {
-        let pb = max
-            .map(|length| ProgressBar::new(length as u64))
-            .unwrap_or(ProgressBar::new_spinner());
+        let pb = multi_progress().add(
+            max.map(|length| ProgressBar::new(length as u64))
+                .unwrap_or(ProgressBar::new_spinner()),
+        );
         pb.set_style(
             ProgressStyle::default_bar()
                 .template("[{elapsed_precise}] {bar:40.cyan/blue} {bytes:>7}/{total_bytes:7} {msg}")
@@ -42,6 +42,62 @@ impl<R: Read, P: Progress> Read for ProgressReaderAdapter<R, P> {
     }
 }
 
+/// Progress shared by several transfers, eg. the downloads of all the files of an artifact
+#[derive(Clone)]
+pub struct SharedProgress(Arc<Mutex<dyn Progress + Send>>);
+
+impl SharedProgress {
+    pub fn new<P: Progress + Send + 'static>(progress: P) -> Self {
+        Self(Arc::new(Mutex::new(progress)))
+    }
+}
+
+impl Progress for SharedProgress {
+    fn inc(&mut self, amount: usize) {
+        self.0.lock().unwrap().inc(amount)
+    }
+
+    fn tick(&mut self) {
+        self.0.lock().unwrap().tick()
+    }
+}
+
+/// Progress of a single transfer: its own or a shared one
+pub enum TransferProgress<P: Progress> {
+    Own(P),
+    Shared(SharedProgress),
+}
+
+impl<P: Progress> TransferProgress<P> {
+    /// The shared progress if any, else a new progress created by `T`
+    pub fn new<T: ProgressReporter<Output = P>>(
+        shared: Option<&SharedProgress>,
+        name: String,
+        max: Option<usize>,
+    ) -> Self {
+        match shared {
+            Some(shared) => TransferProgress::Shared(shared.clone()),
+            None => TransferProgress::Own(T::create(Some(name), max)),
+        }
+    }
+}
+
+impl<P: Progress> Progress for TransferProgress<P> {
+    fn inc(&mut self, amount: usize) {
+        match self {
+            TransferProgress::Own(p) => p.inc(amount),
+            TransferProgress::Shared(p) => p.inc(amount),
+        }
+    }
+
+    fn tick(&mut self) {
+        match self {
+            TransferProgress::Own(p) => p.tick(),
+            TransferProgress::Shared(p) => p.tick(),
+        }
+    }
+}
+
 #[pin_project]
 pub struct ProgressReaderAsyncAdapter<R: AsyncRead, P: Progress + Send> {
     #[pin]
@@ -77,11 +133,13 @@ impl<R: AsyncRead, P: Progress + Send> AsyncRead for ProgressReaderAsyncAdapter<
     }
 }
 
+mod dynamic;
 mod indicatif;
 mod interactive;
 mod non_interactive;
 mod noop;
 
+pub use dynamic::DynProgressReporter;
 use futures::io::Error;
 use futures::task::{Context, Poll};
 pub use interactive::InteractiveProgressReporter;
@@ -89,4 +147,5 @@ pub use noop::NOOPProgress;
 use pin_project::pin_project;
 use std::io;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use tokio::io::AsyncRead;
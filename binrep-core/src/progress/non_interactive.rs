@@ -1,5 +1,9 @@
 use crate::progress::{Progress, ProgressReporter};
 use indicatif::HumanBytes;
+use std::time::{Duration, Instant};
+
+/// Minimum delay between two progress lines
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
 
 /// Progress reporter suitable for non interactive programs
 /// outputs progress to stderr
@@ -14,28 +18,80 @@ impl ProgressReporter for NonInteractiveProgressReporter {
             println!("{}", name);
         }
 
-        NonInteractiveProgress { max, done: 0 }
+        NonInteractiveProgress::new(max, Instant::now())
     }
 }
 
 pub struct NonInteractiveProgress {
     max: Option<usize>,
     done: usize,
+    started: Instant,
+    /// time and percentage of the last progress line
+    last_report: Option<(Instant, usize)>,
+}
+
+impl NonInteractiveProgress {
+    fn new(max: Option<usize>, started: Instant) -> Self {
+        Self {
+            max,
+            done: 0,
+            started,
+            last_report: None,
+        }
+    }
+
+    /// Progress line to print after `amount` more bytes at `now`, if any: at most one per
+    /// percent and per second, the completion is always reported
+    fn progress_line(&mut self, amount: usize, now: Instant) -> Option<String> {
+        let max = self.max.filter(|max| *max > 0)?;
+        self.done += amount;
+        let pc = 100 * self.done / max;
+        let (last_time, last_pc) = self.last_report.unwrap_or((self.started, 0));
+        let complete = self.done >= max;
+        if pc == last_pc || (!complete && now.duration_since(last_time) < REPORT_INTERVAL) {
+            return None;
+        }
+        self.last_report = Some((now, pc));
+        let elapsed = now.duration_since(self.started).as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            self.done as f64 / elapsed
+        } else {
+            0.0
+        };
+        let eta = if rate > 0.0 {
+            format_duration(Duration::from_secs_f64(
+                max.saturating_sub(self.done) as f64 / rate,
+            ))
+        } else {
+            "unknown".to_string()
+        };
+        Some(format!(
+            " {} .......... .......... .......... .......... .......... {}% {}/s ETA {}",
+            HumanBytes(self.done as u64),
+            pc,
+            HumanBytes(rate as u64),
+            eta
+        ))
+    }
+}
+
+/// Format a duration for humans, eg. `1h02m03s`, `2m05s` or `7s`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}h{:02}m{:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m{:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
 }
 
 impl Progress for NonInteractiveProgress {
     fn inc(&mut self, amount: usize) {
-        if let Some(max) = &self.max {
-            let cur_pc = 100 * self.done / max;
-            self.done += amount;
-            let next_pc = 100 * self.done / max;
-            if cur_pc != next_pc {
-                eprintln!(
-                    " {} .......... .......... .......... .......... .......... {}%",
-                    HumanBytes(self.done as u64),
-                    next_pc
-                )
-            }
+        if let Some(line) = self.progress_line(amount, Instant::now()) {
+            eprintln!("{}", line)
         }
     }
 
@@ -43,3 +99,44 @@ impl Progress for NonInteractiveProgress {
         // does nothing;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{format_duration, NonInteractiveProgress};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn format() {
+        assert_eq!("7s", format_duration(Duration::from_secs(7)));
+        assert_eq!("2m05s", format_duration(Duration::from_secs(125)));
+        assert_eq!("1h02m03s", format_duration(Duration::from_secs(3723)));
+    }
+
+    #[test]
+    fn progress_lines() {
+        let started = Instant::now();
+        let at = |secs: f64| started + Duration::from_secs_f64(secs);
+        let mut progress = NonInteractiveProgress::new(Some(1000), started);
+        // less than a second since the start
+        assert_eq!(None, progress.progress_line(100, at(0.5)));
+        let line = progress.progress_line(100, at(2.0)).unwrap();
+        assert!(line.contains(" 20% 100 B/s ETA 8s"), "{}", line);
+        // less than a second since the last line
+        assert_eq!(None, progress.progress_line(100, at(2.5)));
+        assert!(progress
+            .progress_line(1, at(4.0))
+            .unwrap()
+            .contains(" 30% "));
+        // same percentage
+        assert_eq!(None, progress.progress_line(1, at(6.0)));
+        // the completion is always reported
+        let line = progress.progress_line(698, at(6.1)).unwrap();
+        assert!(line.contains(" 100% "), "{}", line);
+        assert!(line.contains("ETA 0s"), "{}", line);
+        // unknown size
+        assert_eq!(
+            None,
+            NonInteractiveProgress::new(None, started).progress_line(100, at(2.0))
+        );
+    }
+}
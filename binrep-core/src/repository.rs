@@ -1,19 +1,31 @@
 use crate::backend::file_backend::FileBackend;
+use crate::backend::gcs_backend::GcsBackend;
+use crate::backend::http_backend::HttpBackend;
 use crate::backend::s3_backend::S3Backend;
 use crate::backend::{Backend, BackendError};
-use crate::config::{BackendType, Config};
+use crate::clock::{Clock, SystemClock};
+use crate::config::{BackendType, Config, RepositoryLayout};
 use crate::crypto::Signer;
-use crate::metadata::{Artifact, Artifacts, ChecksumMethod, Signature, SignatureMethod, Versions};
+use crate::metadata::{
+    Artifact, Artifacts, Channel, ChecksumMethod, FlatArtifact, Labels, Latest, Signature,
+    SignatureMethod, Versions,
+};
 use crate::path::artifacts;
 use anyhow::Error;
+use chrono::{DateTime, Utc};
 use core::borrow::Borrow;
 use futures::{StreamExt, TryStreamExt};
 use ring::digest::{Algorithm, Digest};
 use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::fs::File;
 use std::io::{BufReader, ErrorKind, Read};
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tempfile::{tempdir, tempdir_in, TempDir};
 
 use crate::crypto;
@@ -21,12 +33,16 @@ use crate::file_utils;
 use crate::file_utils::{mv, path_concat2};
 use crate::metadata;
 use crate::path;
-use crate::progress::ProgressReporter;
+use crate::progress::{ProgressReporter, SharedProgress};
+
+/// Orphaned files modified more recently are not deleted by [`Repository::fsck`]
+pub const FSCK_GRACE_PERIOD_HOURS: i64 = 24;
 
 /// Low level API to the repository
 pub struct Repository<T: ProgressReporter> {
     backend: Box<dyn Backend<T>>,
     config: Config,
+    clock: Arc<dyn Clock>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -35,6 +51,24 @@ pub enum RepositoryError {
     ArtifactNameError,
     #[error("Artifact version already exists")]
     ArtifactVersionAlreadyExists,
+    #[error("An artifact must contain at least one file")]
+    EmptyArtifact,
+    #[error("Invalid file name {0:?}")]
+    InvalidFileName(String),
+    #[error("Several files are named {0:?} in the artifact")]
+    DuplicateFileName(String),
+    #[error("Invalid label name {0:?}, only alphanumeric characters and -_. are allowed")]
+    InvalidLabelName(String),
+    #[error("Invalid channel name {0:?}, only alphanumeric characters and -_. are allowed")]
+    InvalidChannelName(String),
+    #[error("No channel {channel} for {artifact}")]
+    ChannelNotFound { artifact: String, channel: String },
+    #[error("Channel {channel} points to {artifact}, point it to another version first")]
+    ChannelTarget { artifact: String, channel: String },
+    #[error("Wrong signature of {0}")]
+    WrongIndexSignature(String),
+    #[error("Missing signature of {0}, the repository indexes are not signed")]
+    MissingIndexSignature(String),
     #[error("Wrong artifact signature")]
     WrongArtifactSignature,
     #[error("Wrong file checksum for {0}")]
@@ -45,6 +79,256 @@ pub enum RepositoryError {
     MissingFileBackendRoot,
     #[error("Missing S3 configuration")]
     MissingS3Configuration,
+    #[error("Http backend url is missing")]
+    MissingHttpBackendUrl,
+    #[error("Missing GCS configuration")]
+    MissingGcsConfiguration,
+    #[error("Not enough disk space in {path}: {required} bytes required, {available} available")]
+    InsufficientDiskSpace {
+        path: String,
+        required: u64,
+        available: u64,
+    },
+    #[error("Invalid timestamp for artifact {artifact}: {problem}")]
+    InvalidArtifactTimestamp { artifact: String, problem: String },
+    #[error("File missing after pull {0}")]
+    MissingPulledFile(String),
+    #[error("Artifact version not found {0}")]
+    ResourceNotFound(String),
+    #[error("{0} already exists in the destination with different files")]
+    MirrorConflict(String),
+    #[error("{artifact} files are hashed with {checksum_method}, the destination publishes {expected} checksums")]
+    ChecksumMethodMismatch {
+        artifact: String,
+        checksum_method: ChecksumMethod,
+        expected: ChecksumMethod,
+    },
+    #[error("No file {file:?} in {artifact}, its files are: {files}")]
+    FileNotInArtifact {
+        file: String,
+        artifact: String,
+        files: String,
+    },
+    #[error("No file of {0} matches the file filter")]
+    NoFileMatchingFilter(String),
+}
+
+/// Check every pulled file is present, a backend may report success without having
+/// delivered the file
+fn ensure_files_present<P: AsRef<Path>>(paths: &[P]) -> Result<(), RepositoryError> {
+    match paths.iter().find(|path| !path.as_ref().is_file()) {
+        Some(missing) => Err(RepositoryError::MissingPulledFile(
+            missing.as_ref().to_string_lossy().into(),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Fail fast if the known size of the artifact files does not fit in `dir`. Files are
+/// downloaded to a temporary directory created inside `dir`, so one check covers both.
+fn check_disk_space<P: AsRef<Path>>(artifact: &Artifact, dir: P) -> Result<(), Error> {
+    // sizes are not signed: a tampered artifact may overflow their sum
+    let required = artifact
+        .files
+        .iter()
+        .filter_map(|file| file.size)
+        .fold(0u64, u64::saturating_add);
+    if required == 0 {
+        return Ok(());
+    }
+    let available = fs2::available_space(dir.as_ref())?;
+    if required > available {
+        Err(RepositoryError::InsufficientDiskSpace {
+            path: dir.as_ref().to_string_lossy().into(),
+            required,
+            available,
+        })?;
+    }
+    Ok(())
+}
+
+/// Options altering the behavior of [`Repository::push_artifact_with_options`]
+#[derive(Debug, Clone, Default)]
+pub struct PushOptions {
+    /// Labels of the pushed artifact
+    pub labels: Labels,
+    /// Replace the version if it already exists (eg. a development version rebuilt again and
+    /// again): the new artifact is signed again, the files of the replaced version not part of
+    /// it are only deleted once it is uploaded
+    pub force: bool,
+}
+
+/// Glob patterns (eg. `*.bin`, `docs/*`) selecting the files of an artifact by name: a file is
+/// selected if it matches any include pattern (or there is none) and no exclude pattern
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Ord, PartialOrd, Eq, PartialEq)]
+pub struct FileFilter {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    includes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    excludes: Vec<String>,
+}
+
+impl FileFilter {
+    pub fn new(includes: Vec<String>, excludes: Vec<String>) -> Result<Self, Error> {
+        for pattern in includes.iter().chain(&excludes) {
+            glob::Pattern::new(pattern)?;
+        }
+        Ok(Self { includes, excludes })
+    }
+
+    /// True if all files are selected
+    pub fn is_empty(&self) -> bool {
+        self.includes.is_empty() && self.excludes.is_empty()
+    }
+
+    pub fn matches(&self, file_name: &str) -> bool {
+        let matches = |pattern: &String| {
+            glob::Pattern::new(pattern)
+                .map(|pattern| pattern.matches(file_name))
+                .unwrap_or(false)
+        };
+        (self.includes.is_empty() || self.includes.iter().any(matches))
+            && !self.excludes.iter().any(matches)
+    }
+}
+
+/// Options altering the behavior of [`Repository::pull_artifact_with_options`]
+#[derive(Debug, Clone, Default)]
+pub struct PullOptions {
+    /// Replace the files already present in the destination directory
+    pub overwrite_dest: bool,
+    /// Only download the selected files, the signature is still verified over all the files
+    pub file_filter: FileFilter,
+    /// Directory keeping the interrupted downloads of a backend resuming them, so that the
+    /// next pull completes them (default to [`partial_download_dir`] of the destination)
+    pub partial_dir: Option<PathBuf>,
+}
+
+/// Directory of `dir` keeping the interrupted downloads of `artifact_name`
+pub fn partial_download_dir<P: AsRef<Path>>(artifact_name: &str, dir: P) -> PathBuf {
+    path_concat2(dir, format!(".{}.binrep-partial", artifact_name))
+}
+
+/// Create the directory keeping the partial downloads of `version` in `partial_dir`, the ones
+/// of other versions are removed
+fn prepare_partial_dir(partial_dir: &Path, version: &Version) -> Result<PathBuf, Error> {
+    let version = version.to_string();
+    file_utils::mkdirs(partial_dir)?;
+    for entry in std::fs::read_dir(partial_dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy() != version.as_str() {
+            std::fs::remove_dir_all(entry.path())?;
+        }
+    }
+    let version_dir = path_concat2(partial_dir, version);
+    file_utils::mkdirs(&version_dir)?;
+    Ok(version_dir)
+}
+
+/// Options altering the behavior of [`Repository::mirror_to`]
+#[derive(Debug, Clone)]
+pub struct MirrorOptions {
+    /// Verify the signature of each artifact before copying it (default to true)
+    pub verify_signatures: bool,
+}
+
+impl Default for MirrorOptions {
+    fn default() -> Self {
+        Self {
+            verify_signatures: true,
+        }
+    }
+}
+
+/// Failure to mirror an artifact version, or a whole artifact if its versions cannot be listed
+#[derive(Debug)]
+pub struct MirrorFailure {
+    pub artifact_name: String,
+    pub version: Option<Version>,
+    pub error: Error,
+}
+
+/// Outcome of [`Repository::mirror_to`]
+#[derive(Debug, Default)]
+pub struct MirrorReport {
+    pub copied: Vec<(String, Version)>,
+    /// Versions already present in the destination with the same files
+    pub skipped: Vec<(String, Version)>,
+    pub failed: Vec<MirrorFailure>,
+}
+
+/// Outcome of [`Repository::fsck`]
+#[derive(Debug, Default)]
+pub struct FsckReport {
+    /// Number of artifact versions whose signature was verified
+    pub checked_versions: usize,
+    /// Metadata that cannot be read or verified, with the error
+    pub invalid: Vec<(String, Error)>,
+    /// Files referenced by a verified artifact but missing from the backend
+    pub missing_files: Vec<String>,
+    /// Files referenced by a verified artifact whose presence cannot be checked, with the error
+    pub unchecked_files: Vec<(String, Error)>,
+    /// Files of the backend referenced by no artifact
+    pub orphaned_files: Vec<String>,
+    /// Orphaned files deleted when repairing
+    pub removed_files: Vec<String>,
+    /// Orphaned files kept when repairing: modified less than [`FSCK_GRACE_PERIOD_HOURS`] ago,
+    /// they may belong to a push in progress
+    pub recent_files: Vec<String>,
+}
+
+impl FsckReport {
+    /// Whether no problem was found, or only orphaned files which were removed
+    pub fn is_clean(&self) -> bool {
+        self.invalid.is_empty()
+            && self.missing_files.is_empty()
+            && self.unchecked_files.is_empty()
+            && self.orphaned_files.len() == self.removed_files.len()
+    }
+}
+
+/// Outcome of [`Repository::resign_all`]
+#[derive(Debug, Default)]
+pub struct ResignReport {
+    pub resigned: Vec<(String, Version)>,
+    /// Versions whose artifact cannot be verified or re-signed, with the error
+    pub failed: Vec<(String, Version, Error)>,
+}
+
+/// A file to push
+pub(crate) struct PushedFile<'a> {
+    pub name: String,
+    pub content: FileContent<'a>,
+    pub unix_mode: Option<u32>,
+}
+
+pub(crate) enum FileContent<'a> {
+    Local(&'a Path),
+    InMemory(&'a [u8]),
+}
+
+/// File names are joined to the destination directory when pulling: they are relative paths
+/// whose components are separated by `/`, `.` and `..` components would escape the directory
+fn validate_file_name(name: &str) -> Result<(), RepositoryError> {
+    if name.split('/').all(|component| {
+        !component.is_empty() && component != "." && component != ".." && !component.contains('\\')
+    }) {
+        Ok(())
+    } else {
+        Err(RepositoryError::InvalidFileName(name.to_string()))
+    }
+}
+
+fn validate_label_name(name: &str) -> Result<(), RepositoryError> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        Ok(())
+    } else {
+        Err(RepositoryError::InvalidLabelName(name.to_string()))
+    }
 }
 
 fn validate_artifact_name(name: &str) -> Result<(), RepositoryError> {
@@ -60,6 +344,18 @@ fn validate_artifact_name(name: &str) -> Result<(), RepositoryError> {
     })
 }
 
+fn validate_channel_name(name: &str) -> Result<(), RepositoryError> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        Ok(())
+    } else {
+        Err(RepositoryError::InvalidChannelName(name.to_string()))
+    }
+}
+
 impl<T> Repository<T>
 where
     T: ProgressReporter + 'static,
@@ -67,24 +363,94 @@ where
 {
     pub fn new(config: Config) -> Result<Self, Error> {
         // Construct the backend
+        let max_metadata_size = config.backend.max_metadata_size();
         let backend: Box<dyn Backend<T>> = match &config.backend.backend_type {
-            BackendType::File => Box::new(FileBackend::<T>::new(
-                &config
-                    .backend
-                    .file_backend_opt
-                    .as_ref()
-                    .ok_or(RepositoryError::MissingFileBackendRoot)?
-                    .root,
-            )),
-            BackendType::S3 => Box::new(S3Backend::<T>::new(
-                config
-                    .backend
-                    .s3_backend_opt
-                    .as_ref()
-                    .ok_or(RepositoryError::MissingS3Configuration)?,
-            )?),
+            BackendType::File => Box::new(
+                FileBackend::<T>::new(
+                    &config
+                        .backend
+                        .file_backend_opt
+                        .as_ref()
+                        .ok_or(RepositoryError::MissingFileBackendRoot)?
+                        .root,
+                )
+                .with_max_metadata_size(max_metadata_size),
+            ),
+            BackendType::S3 => Box::new(
+                S3Backend::<T>::new(
+                    config
+                        .backend
+                        .s3_backend_opt
+                        .as_ref()
+                        .ok_or(RepositoryError::MissingS3Configuration)?,
+                )?
+                .with_max_metadata_size(max_metadata_size)
+                .with_checksum_buffer_size(config.checksum_buffer_size()),
+            ),
+            BackendType::Http => Box::new(
+                HttpBackend::<T>::new(
+                    config
+                        .backend
+                        .http_backend_opt
+                        .as_ref()
+                        .ok_or(RepositoryError::MissingHttpBackendUrl)?,
+                )?
+                .with_max_metadata_size(max_metadata_size),
+            ),
+            BackendType::Gcs => Box::new(
+                GcsBackend::<T>::new(
+                    config
+                        .backend
+                        .gcs_backend_opt
+                        .as_ref()
+                        .ok_or(RepositoryError::MissingGcsConfiguration)?,
+                )?
+                .with_max_metadata_size(max_metadata_size),
+            ),
         };
-        Ok(Self { backend, config })
+        Ok(Self {
+            backend,
+            config,
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Create a repository using the given backend instead of the configured one
+    #[cfg(test)]
+    pub(crate) fn with_backend(config: Config, backend: Box<dyn Backend<T>>) -> Self {
+        Self {
+            backend,
+            config,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Replace the time source used for timestamps
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    fn layout(&self) -> RepositoryLayout {
+        self.config.backend.layout()
+    }
+
+    fn validate_artifact_name(&self, name: &str) -> Result<(), RepositoryError> {
+        validate_artifact_name(name)?;
+        // with the flat layout, the artifact metadata file would be the artifacts list
+        if self.layout() == RepositoryLayout::Flat
+            && path::artifact::flat(name) == path::artifacts()
+        {
+            return Err(RepositoryError::ArtifactNameError);
+        }
+        Ok(())
     }
 
     /// Initialize the repository, do nothing if the repository is already initialized.
@@ -93,20 +459,70 @@ where
     async fn init(&mut self) -> Result<Artifacts, Error> {
         match self.list_artifacts().await {
             Ok(artifacts) => Ok(artifacts),
-            Err(_) => {
-                let new_artifacts = Artifacts::new();
-                self.write_artifacts(&new_artifacts).await?;
-                Ok(new_artifacts)
-            }
+            // only a missing list is initialized, not an unreadable or tampered one
+            Err(e) => match e.downcast::<BackendError>()? {
+                BackendError::ResourceNotFound => {
+                    let new_artifacts = Artifacts::new();
+                    self.write_artifacts(&new_artifacts).await?;
+                    Ok(new_artifacts)
+                }
+                e => Err(e)?,
+            },
         }
     }
 
     async fn write_artifacts(&mut self, artifacts: &Artifacts) -> Result<(), Error> {
-        info!("writing {}", path::artifacts());
-        Ok(self
-            .backend
-            .create_file(path::artifacts(), sane::to_string(artifacts)?)
-            .await?)
+        self.write_index(path::artifacts(), sane::to_string(artifacts)?)
+            .await
+    }
+
+    /// Write an index file (artifacts or versions list) with its detached signature if
+    /// the repository is configured to publish, removing the signature of the previous content
+    /// otherwise
+    async fn write_index(&mut self, path: &str, content: String) -> Result<(), Error> {
+        info!("writing {}", path);
+        let signature = match self.config.get_publish_algorithm() {
+            Ok(publish_algorithm) => Some(crypto::sign_index(
+                path,
+                &content,
+                publish_algorithm.signer.as_ref(),
+            )?),
+            Err(_) => None,
+        };
+        if signature.is_none() {
+            // a stale signature would not match the new content
+            match self.backend.delete_file(&path::signature(path)).await {
+                Ok(()) | Err(BackendError::ResourceNotFound) => (),
+                Err(e) => Err(e)?,
+            }
+        }
+        self.backend.create_file(path, content).await?;
+        if let Some(signature) = signature {
+            self.backend
+                .create_file(&path::signature(path), sane::to_string(&signature)?)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Read an index file, verifying its signature if `verify_indexes` is set
+    async fn read_index(&mut self, path: &str) -> Result<String, Error> {
+        info!("Reading {}", path);
+        let content = self.backend.read_file(path).await?;
+        if self.config.verify_indexes() {
+            let signature = match self.backend.read_file(&path::signature(path)).await {
+                Ok(signature) => sane::from_str::<Signature>(&signature)?,
+                // not to be mistaken for a missing index
+                Err(BackendError::ResourceNotFound) => {
+                    Err(RepositoryError::MissingIndexSignature(path.to_string()))?
+                }
+                Err(e) => Err(e)?,
+            };
+            if !crypto::verify_index(path, &content, &signature, &self.config)? {
+                Err(RepositoryError::WrongIndexSignature(path.to_string()))?;
+            }
+        }
+        Ok(content)
     }
 
     async fn write_artifact_versions(
@@ -114,12 +530,27 @@ where
         artifact_name: &str,
         versions: &Versions,
     ) -> Result<(), Error> {
-        let versions_path = path::artifact::versions(artifact_name);
-        info!("writing {}", versions_path);
-        Ok(self
-            .backend
-            .create_file(&versions_path, sane::to_string(versions)?)
-            .await?)
+        if self.layout() == RepositoryLayout::Flat {
+            // versions are derived from the artifact metadata written by write_artifact
+            return Ok(());
+        }
+        self.write_index(
+            &path::artifact::versions(artifact_name),
+            sane::to_string(versions)?,
+        )
+        .await?;
+        // the latest version is only a shortcut, readers fall back to the versions list if missing
+        let latest_path = path::artifact::latest(artifact_name);
+        if let Err(e) = self
+            .write_index(&latest_path, sane::to_string(&Latest::of(versions))?)
+            .await
+        {
+            warn!("Cannot write {}: {}", latest_path, e);
+            if let Err(e) = self.backend.delete_file(&latest_path).await {
+                warn!("Cannot delete the outdated {}: {}", latest_path, e);
+            }
+        }
+        Ok(())
     }
 
     async fn write_artifact(
@@ -128,17 +559,49 @@ where
         version: &Version,
         artifact: &Artifact,
     ) -> Result<(), Error> {
-        let artifact_path = path::artifact::artifact(artifact_name, version);
-        info!("writing {}", artifact_path);
+        match self.layout() {
+            RepositoryLayout::Standard => {
+                let artifact_path = path::artifact::artifact(artifact_name, version);
+                info!("writing {}", artifact_path);
+                Ok(self
+                    .backend
+                    .create_file(&artifact_path, sane::to_string(artifact)?)
+                    .await?)
+            }
+            RepositoryLayout::Flat => {
+                let mut flat_artifact = self.read_flat_artifact(artifact_name).await?;
+                flat_artifact.versions.retain(|a| &a.version != version);
+                flat_artifact.versions.push(artifact.clone());
+                self.write_flat_artifact(artifact_name, &flat_artifact)
+                    .await
+            }
+        }
+    }
+
+    async fn read_flat_artifact(&mut self, artifact_name: &str) -> Result<FlatArtifact, Error> {
+        let path = path::artifact::flat(artifact_name);
+        info!("Reading {}", path);
+        Ok(sane::from_str::<FlatArtifact>(
+            &self.backend.read_file(&path).await?,
+        )?)
+    }
+
+    async fn write_flat_artifact(
+        &mut self,
+        artifact_name: &str,
+        flat_artifact: &FlatArtifact,
+    ) -> Result<(), Error> {
+        let path = path::artifact::flat(artifact_name);
+        info!("writing {}", path);
         Ok(self
             .backend
-            .create_file(&artifact_path, sane::to_string(artifact)?)
+            .create_file(&path, sane::to_string(flat_artifact)?)
             .await?)
     }
 
     /// Initialize artifact repo, do nothing if the artifact repo is already initialized
     async fn init_artifact(&mut self, artifact_name: &str) -> Result<Versions, Error> {
-        validate_artifact_name(artifact_name)?;
+        self.validate_artifact_name(artifact_name)?;
         match self.list_artifact_versions(artifact_name).await {
             Ok(versions) => Ok(versions),
             Err(e) => {
@@ -152,8 +615,16 @@ where
                         let mut artifacts = self.init().await?;
                         // write new versions file
                         let new_versions = Versions::new();
-                        self.write_artifact_versions(artifact_name, &new_versions)
-                            .await?;
+                        match self.layout() {
+                            RepositoryLayout::Standard => {
+                                self.write_artifact_versions(artifact_name, &new_versions)
+                                    .await?
+                            }
+                            RepositoryLayout::Flat => {
+                                self.write_flat_artifact(artifact_name, &FlatArtifact::default())
+                                    .await?
+                            }
+                        }
                         // register artifact
                         artifacts.artifacts.push(artifact_name.into());
                         self.write_artifacts(&artifacts).await?;
@@ -165,147 +636,1076 @@ where
         }
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub async fn list_artifacts(&mut self) -> Result<Artifacts, Error> {
-        let artifacts_path = path::artifacts();
-        info!("Reading {}", artifacts_path);
         Ok(sane::from_str::<Artifacts>(
-            &self.backend.read_file(artifacts_path).await?,
+            &self.read_index(path::artifacts()).await?,
         )?)
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name))
+    )]
     pub async fn list_artifact_versions(&mut self, artifact_name: &str) -> Result<Versions, Error> {
-        validate_artifact_name(artifact_name)?;
+        self.validate_artifact_name(artifact_name)?;
 
-        let path: String = path::artifact::versions(artifact_name);
-        info!("Reading {}", path);
-        Ok(sane::from_str::<Versions>(
-            &self.backend.read_file(&path).await?,
-        )?)
+        match self.layout() {
+            RepositoryLayout::Standard => {
+                let path: String = path::artifact::versions(artifact_name);
+                Ok(sane::from_str::<Versions>(&self.read_index(&path).await?)?)
+            }
+            RepositoryLayout::Flat => Ok(Versions {
+                versions: self
+                    .read_flat_artifact(artifact_name)
+                    .await?
+                    .versions
+                    .into_iter()
+                    .map(|artifact| artifact.version)
+                    .collect(),
+            }),
+        }
     }
 
+    /// Highest released (non pre-release) version of an artifact, read from its `latest.sane`
+    /// file, or from its versions list in repositories written before `latest.sane` existed.
+    ///
+    /// The versions list is also read if the version of `latest.sane` does not exist.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name))
+    )]
+    pub async fn get_latest(&mut self, artifact_name: &str) -> Result<Option<Version>, Error> {
+        self.validate_artifact_name(artifact_name)?;
+        if self.layout() == RepositoryLayout::Standard {
+            match self
+                .read_index(&path::artifact::latest(artifact_name))
+                .await
+            {
+                Ok(content) => match sane::from_str::<Latest>(&content)?.version {
+                    Some(latest) if self.artifact_exists(artifact_name, &latest).await? => {
+                        return Ok(Some(latest))
+                    }
+                    Some(latest) => warn!(
+                        "Latest version {} of {} does not exist, reading its versions list",
+                        latest, artifact_name
+                    ),
+                    None => return Ok(None),
+                },
+                Err(e) => match e.downcast::<BackendError>()? {
+                    BackendError::ResourceNotFound => {
+                        debug!("No latest version file for {}", artifact_name)
+                    }
+                    e => Err(e)?,
+                },
+            }
+        }
+        Ok(Latest::of(&self.list_artifact_versions(artifact_name).await?).version)
+    }
+
+    /// Point a channel (eg. `stable`) of an artifact to one of its versions, replacing the
+    /// version it pointed to
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name, channel = channel, version = %version))
+    )]
+    pub async fn set_channel(
+        &mut self,
+        artifact_name: &str,
+        channel: &str,
+        version: &Version,
+    ) -> Result<(), Error> {
+        validate_channel_name(channel)?;
+        if !self.artifact_exists(artifact_name, version).await? {
+            Err(RepositoryError::ResourceNotFound(format!(
+                "{} {}",
+                artifact_name, version
+            )))?;
+        }
+        self.write_index(
+            &path::artifact::channel(artifact_name, channel),
+            sane::to_string(&Channel {
+                version: version.clone(),
+            })?,
+        )
+        .await
+    }
+
+    /// Version a channel of an artifact currently points to
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name, channel = channel))
+    )]
+    pub async fn get_channel(
+        &mut self,
+        artifact_name: &str,
+        channel: &str,
+    ) -> Result<Version, Error> {
+        self.validate_artifact_name(artifact_name)?;
+        validate_channel_name(channel)?;
+        match self
+            .read_index(&path::artifact::channel(artifact_name, channel))
+            .await
+        {
+            Ok(content) => Ok(sane::from_str::<Channel>(&content)?.version),
+            Err(e) => match e.downcast::<BackendError>()? {
+                BackendError::ResourceNotFound => Err(RepositoryError::ChannelNotFound {
+                    artifact: artifact_name.to_string(),
+                    channel: channel.to_string(),
+                })?,
+                e => Err(e)?,
+            },
+        }
+    }
+
+    /// Channels of an artifact with the version each one points to, sorted by name
+    pub async fn list_channels(
+        &mut self,
+        artifact_name: &str,
+    ) -> Result<Vec<(String, Version)>, Error> {
+        self.validate_artifact_name(artifact_name)?;
+        let prefix = path::artifact::channel(artifact_name, "");
+        let prefix = prefix.trim_end_matches(".sane");
+        let mut names: Vec<String> = self
+            .backend
+            .list_files(prefix)
+            .await?
+            .iter()
+            .filter_map(|file| file[prefix.len()..].strip_suffix(".sane"))
+            .map(str::to_string)
+            .collect();
+        names.sort();
+        let mut channels = Vec::new();
+        for name in names {
+            let version = self.get_channel(artifact_name, &name).await?;
+            channels.push((name, version));
+        }
+        Ok(channels)
+    }
+
+    /// Tell whether a version of an artifact exists, without downloading its metadata (except
+    /// with the flat layout, all versions being stored in a single file)
+    pub async fn artifact_exists(
+        &mut self,
+        artifact_name: &str,
+        artifact_version: &Version,
+    ) -> Result<bool, Error> {
+        self.validate_artifact_name(artifact_name)?;
+        match self.layout() {
+            RepositoryLayout::Standard => Ok(self
+                .backend
+                .head_file(&path::artifact::artifact(artifact_name, artifact_version))
+                .await?),
+            RepositoryLayout::Flat => match self.read_flat_artifact(artifact_name).await {
+                Ok(flat_artifact) => Ok(flat_artifact
+                    .versions
+                    .iter()
+                    .any(|artifact| &artifact.version == artifact_version)),
+                Err(e) => match e.downcast::<BackendError>()? {
+                    BackendError::ResourceNotFound => Ok(false),
+                    e => Err(e)?,
+                },
+            },
+        }
+    }
+
+    /// Read artifact metadata without verifying it
+    async fn read_artifact(
+        &mut self,
+        artifact_name: &str,
+        artifact_version: &Version,
+    ) -> Result<Artifact, Error> {
+        match self.layout() {
+            RepositoryLayout::Standard => {
+                let path: String = path::artifact::artifact(artifact_name, artifact_version);
+                info!("Reading {}", path);
+                Ok(sane::from_str::<Artifact>(
+                    &self.backend.read_file(&path).await?,
+                )?)
+            }
+            RepositoryLayout::Flat => Ok(self
+                .read_flat_artifact(artifact_name)
+                .await?
+                .versions
+                .into_iter()
+                .find(|artifact| &artifact.version == artifact_version)
+                .ok_or(BackendError::ResourceNotFound)?),
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name, version = %artifact_version))
+    )]
     pub async fn get_artifact(
         &mut self,
         artifact_name: &str,
         artifact_version: &Version,
     ) -> Result<Artifact, Error> {
-        validate_artifact_name(artifact_name)?;
+        self.validate_artifact_name(artifact_name)?;
 
-        let path: String = path::artifact::artifact(artifact_name, artifact_version);
-        info!("Reading {}", path);
-        let ret = sane::from_str::<Artifact>(&self.backend.read_file(&path).await?)?;
+        let ret = self.read_artifact(artifact_name, artifact_version).await?;
         if !ret.verify_signature(&self.config)? {
             Err(RepositoryError::WrongArtifactSignature)?;
         }
+        self.check_timestamp(artifact_name, &ret)?;
         Ok(ret)
     }
 
+    /// Check the push timestamp of an artifact.
+    ///
+    /// Timestamps that cannot be parsed or that are too far in the future (misconfigured
+    /// builder clock) are logged, or rejected if the configuration says so.
+    fn check_timestamp(
+        &self,
+        artifact_name: &str,
+        artifact: &Artifact,
+    ) -> Result<(), RepositoryError> {
+        let pushed_at = match &artifact.pushed_at {
+            None => return Ok(()),
+            Some(pushed_at) => pushed_at,
+        };
+        let problem = match DateTime::parse_from_rfc3339(pushed_at) {
+            Err(e) => Some(format!("cannot parse '{}': {}", pushed_at, e)),
+            Ok(pushed_at) => {
+                let skew = pushed_at.with_timezone(&Utc) - self.clock.now();
+                if skew.num_seconds() > self.config.max_clock_skew_secs() as i64 {
+                    Some(format!(
+                        "pushed {}s in the future, check the clock of the publisher",
+                        skew.num_seconds()
+                    ))
+                } else {
+                    None
+                }
+            }
+        };
+        match problem {
+            None => Ok(()),
+            Some(problem) if self.config.reject_invalid_timestamps() => {
+                Err(RepositoryError::InvalidArtifactTimestamp {
+                    artifact: format!("{} {}", artifact_name, artifact.version),
+                    problem,
+                })
+            }
+            Some(problem) => {
+                warn!(
+                    "Invalid timestamp for artifact {} {}: {}",
+                    artifact_name, artifact.version, problem
+                );
+                Ok(())
+            }
+        }
+    }
+
     pub async fn push_artifact<P: AsRef<Path>>(
         &mut self,
         artifact_name: &str,
         version: &Version,
         files: &[P],
     ) -> Result<Artifact, Error> {
+        self.push_artifact_with_options(artifact_name, version, files, &PushOptions::default())
+            .await
+    }
+
+    pub async fn push_artifact_with_options<P: AsRef<Path>>(
+        &mut self,
+        artifact_name: &str,
+        version: &Version,
+        files: &[P],
+        options: &PushOptions,
+    ) -> Result<Artifact, Error> {
+        let files = files
+            .iter()
+            .map(|file| {
+                let name = file
+                    .as_ref()
+                    .iter()
+                    .last()
+                    .unwrap() // this cannot fail ;)
+                    .to_string_lossy()
+                    .to_string();
+                (file.as_ref(), name)
+            })
+            .collect::<Vec<_>>();
+        self.push_artifact_with_paths(artifact_name, version, &files, options)
+            .await
+    }
+
+    /// Push local files under their path in the artifact, eg. `("./build/server", "bin/server")`,
+    /// the directories are recreated on pull
+    pub async fn push_artifact_with_paths<P: AsRef<Path>, N: AsRef<str>>(
+        &mut self,
+        artifact_name: &str,
+        version: &Version,
+        files: &[(P, N)],
+        options: &PushOptions,
+    ) -> Result<Artifact, Error> {
+        let files = files
+            .iter()
+            .map(|(file, name)| -> Result<_, Error> {
+                let permissions = std::fs::metadata(file)?.permissions();
+                Ok(PushedFile {
+                    name: name.as_ref().to_string(),
+                    content: FileContent::Local(file.as_ref()),
+                    unix_mode: Some(permissions.mode() & 0o777),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.push_files(artifact_name, version, &files, options)
+            .await
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name, version = %version))
+    )]
+    pub(crate) async fn push_files(
+        &mut self,
+        artifact_name: &str,
+        version: &Version,
+        files: &[PushedFile<'_>],
+        options: &PushOptions,
+    ) -> Result<Artifact, Error> {
+        let labels = &options.labels;
+        // nothing would be signed
+        if files.is_empty() {
+            Err(RepositoryError::EmptyArtifact)?;
+        }
+        let mut names = HashSet::new();
+        for file in files {
+            validate_file_name(&file.name)?;
+            // pulled files would overwrite each other
+            if !names.insert(&file.name) {
+                Err(RepositoryError::DuplicateFileName(file.name.clone()))?;
+            }
+        }
+        for label in labels.keys() {
+            validate_label_name(label)?;
+        }
         // Compute sums & signature
         let mut versions = self.init_artifact(artifact_name).await?;
-        if versions.versions.contains(&version) {
+        if versions.versions.contains(&version) && !options.force {
             Err(RepositoryError::ArtifactVersionAlreadyExists)?;
         }
 
         let publish_algorithm = self.config.get_publish_algorithm()?;
+        let algorithm = publish_algorithm.checksum_method.algorithm();
+        let buffer_size = self.config.checksum_buffer_size();
 
         // create the "Artifact": computes hash & signatures
-        let mut digests = Vec::new();
-        let mut filenames = Vec::new();
-        let mut unix_mode = Vec::new();
-        let mut to_sign = String::new();
-        for file in files {
-            let digest = data_encoding::BASE64.encode(
-                crypto::digest_file(file, publish_algorithm.checksum_method.algorithm())?.as_ref(),
-            );
-            let filename = file
-                .as_ref()
-                .iter()
-                .last()
-                .unwrap() // this cannot fail ;)
-                .to_string_lossy();
-
-            // construct string to sign
-            to_sign.push_str(&filename);
-            to_sign.push_str(&digest);
-
-            filenames.push(filename);
-            digests.push(digest);
-
-            let meta = std::fs::metadata(file)?;
-            let permissions = meta.permissions();
-            unix_mode.push(Some(permissions.mode() & 0o777))
-        }
-        let signature = Signature {
-            key_id: publish_algorithm.signer.key_id(),
-            signature_method: publish_algorithm.signer.signature_method(),
-            signature: data_encoding::BASE64
-                .encode(&publish_algorithm.signer.sign(to_sign.as_bytes())?),
-        };
+        let artifact_files = files
+            .iter()
+            .map(|file| -> Result<_, Error> {
+                let (digest, size) = match &file.content {
+                    FileContent::Local(path) => (
+                        crypto::digest_file(path, algorithm, buffer_size)?,
+                        std::fs::metadata(path)?.len(),
+                    ),
+                    FileContent::InMemory(data) => {
+                        (ring::digest::digest(algorithm, data), data.len() as u64)
+                    }
+                };
+                Ok(metadata::File {
+                    checksum_method: publish_algorithm.checksum_method,
+                    checksum: data_encoding::BASE64.encode(digest.as_ref()),
+                    name: file.name.clone(),
+                    unix_mode: file.unix_mode,
+                    size: Some(size),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let signatures = publish_algorithm.sign(&artifact_files, labels)?;
 
         let artifact = Artifact {
             version: version.clone(),
-            files: filenames
-                .iter()
-                .zip(digests.into_iter())
-                .zip(unix_mode)
-                .map(|((filename, digest), unix_mode)| metadata::File {
-                    checksum_method: publish_algorithm.checksum_method,
-                    checksum: digest,
-                    name: filename.to_string(),
-                    unix_mode,
-                })
-                .collect(),
-            signature,
+            pushed_at: Some(self.clock.now().to_rfc3339()),
+            files: artifact_files,
+            signatures,
+            labels: labels.clone(),
         };
 
-        for (file, filename) in files.iter().zip(filenames.iter()) {
-            let local_path = PathBuf::from(file.as_ref());
-            self.backend
-                .push_file(
-                    local_path,
-                    &path::artifact::artifact_file(artifact_name, version, filename),
-                )
-                .await?;
-        }
+        // the replaced version stays in place until the new one is uploaded
+        let replaced = if versions.versions.contains(version) {
+            info!("replacing {} {}", artifact_name, version);
+            versions.versions.retain(|v| v != version);
+            match self.read_artifact(artifact_name, version).await {
+                Ok(replaced) => Some(replaced),
+                Err(e) => match e.downcast::<BackendError>()? {
+                    // listed without metadata, eg. an interrupted push
+                    BackendError::ResourceNotFound => None,
+                    e => Err(e)?,
+                },
+            }
+        } else {
+            None
+        };
+        let replaced_paths: HashSet<String> = match &replaced {
+            Some(replaced) => {
+                let mut paths: HashSet<String> = replaced
+                    .files
+                    .iter()
+                    .map(|file| path::artifact::artifact_file(artifact_name, version, &file.name))
+                    .collect();
+                paths.insert(path::artifact::artifact(artifact_name, version));
+                paths
+            }
+            None => HashSet::new(),
+        };
 
-        self.write_artifact(artifact_name, version, &artifact)
-            .await?;
         versions.versions.push(version.clone());
-        self.write_artifact_versions(artifact_name, &versions)
-            .await?;
+        let mut pushed = Vec::new();
+        if let Err(e) = self
+            .upload_artifact(artifact_name, files, &artifact, &versions, &mut pushed)
+            .await
+        {
+            // do not leave a broken version behind, nor delete the files of the replaced one
+            for path in pushed.iter().rev() {
+                if replaced_paths.contains(path) {
+                    continue;
+                }
+                info!("deleting {}", path);
+                if let Err(delete_error) = self.backend.delete_file(path).await {
+                    warn!("Cannot delete {}: {}", path, delete_error);
+                }
+            }
+            return Err(e);
+        }
+
+        if let Some(replaced) = replaced {
+            for file in &replaced.files {
+                if artifact.files.iter().any(|f| f.name == file.name) {
+                    continue;
+                }
+                let path = path::artifact::artifact_file(artifact_name, version, &file.name);
+                info!("deleting {}", path);
+                if let Err(e) = self.backend.delete_file(&path).await {
+                    warn!("Cannot delete {}: {}", path, e);
+                }
+            }
+        }
 
         Ok(artifact)
     }
 
-    pub async fn pull_artifact<P: AsRef<Path>>(
+    /// Push the files of `artifact` then its metadata, the version is only listed once
+    /// everything else is written. The paths written are added to `pushed`.
+    async fn upload_artifact(
         &mut self,
         artifact_name: &str,
-        artifact_version: &Version,
+        files: &[PushedFile<'_>],
+        artifact: &Artifact,
+        versions: &Versions,
+        pushed: &mut Vec<String>,
+    ) -> Result<(), Error> {
+        let version = &artifact.version;
+        {
+            // no upload is started after the first failure, but those in progress are awaited:
+            // dropping them could leave orphan objects (eg. multipart uploads) behind. Every
+            // attempted file is recorded, a failed upload may still have partially landed.
+            let failed = Cell::new(false);
+            let backend = &*self.backend;
+            let mut uploads = futures::stream::iter(files)
+                .take_while(|_| futures::future::ready(!failed.get()))
+                .map(|file| async move {
+                    let remote = path::artifact::artifact_file(artifact_name, version, &file.name);
+                    let result = match &file.content {
+                        FileContent::Local(path) => {
+                            backend.push_file(PathBuf::from(path), &remote).await
+                        }
+                        FileContent::InMemory(data) => {
+                            backend.push_bytes(data.to_vec(), &remote).await
+                        }
+                    };
+                    (remote, result)
+                })
+                .buffer_unordered(self.config.max_concurrent_uploads());
+            let mut error = None;
+            while let Some((remote, result)) = uploads.next().await {
+                pushed.push(remote);
+                if let Err(e) = result {
+                    failed.set(true);
+                    error.get_or_insert(e);
+                }
+            }
+            if let Some(e) = error {
+                Err(e)?;
+            }
+        }
+
+        self.write_artifact(artifact_name, version, artifact)
+            .await?;
+        if self.layout() == RepositoryLayout::Standard {
+            pushed.push(path::artifact::artifact(artifact_name, version));
+        }
+        self.write_artifact_versions(artifact_name, versions).await
+    }
+
+    /// Re-sign every version of every artifact with the configured publish signers, `key_id`
+    /// replacing the primary signing key if given. The signature only covers names and
+    /// checksums and labels, no file is downloaded.
+    ///
+    /// Each artifact signature is verified before being replaced: versions failing verification
+    /// are left untouched and reported, the other ones are still re-signed.
+    pub async fn resign_all(&mut self, key_id: Option<&str>) -> Result<ResignReport, Error> {
+        let mut publish_algorithm = self.config.get_publish_algorithm()?;
+        if let Some(key_id) = key_id {
+            publish_algorithm.signer = self.config.get_signer_with_key(key_id)?;
+        }
+        let mut report = ResignReport::default();
+        for artifact_name in self.list_artifacts().await?.artifacts {
+            for version in self.list_artifact_versions(&artifact_name).await?.versions {
+                let result = async {
+                    let mut artifact = self.get_artifact(&artifact_name, &version).await?;
+                    artifact.signatures =
+                        publish_algorithm.sign(&artifact.files, &artifact.labels)?;
+                    self.write_artifact(&artifact_name, &version, &artifact)
+                        .await
+                }
+                .await;
+                match result {
+                    Ok(()) => {
+                        info!("Re-signed {} {}", artifact_name, version);
+                        report.resigned.push((artifact_name.clone(), version));
+                    }
+                    Err(e) => {
+                        warn!("Cannot re-sign {} {}: {}", artifact_name, version, e);
+                        report.failed.push((artifact_name.clone(), version, e));
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Delete a version of an artifact, its metadata and its files. Refuses to delete a version
+    /// a channel points to.
+    ///
+    /// The version is removed from the versions list first: if the deletion is interrupted,
+    /// orphan files are left behind but no listed version misses its files.
+    ///
+    /// Returns the deleted artifact.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name, version = %version))
+    )]
+    pub async fn delete_artifact_version(
+        &mut self,
+        artifact_name: &str,
+        version: &Version,
+    ) -> Result<Artifact, Error> {
+        self.validate_artifact_name(artifact_name)?;
+        let artifact = match self.read_artifact(artifact_name, version).await {
+            Ok(artifact) => artifact,
+            Err(e) => match e.downcast::<BackendError>()? {
+                BackendError::ResourceNotFound => Err(RepositoryError::ResourceNotFound(format!(
+                    "{} {}",
+                    artifact_name, version
+                )))?,
+                e => Err(e)?,
+            },
+        };
+        if let Some((channel, _)) = self
+            .list_channels(artifact_name)
+            .await?
+            .into_iter()
+            .find(|(_, channel_version)| channel_version == version)
+        {
+            Err(RepositoryError::ChannelTarget {
+                artifact: format!("{} {}", artifact_name, version),
+                channel,
+            })?;
+        }
+        match self.layout() {
+            RepositoryLayout::Standard => {
+                let mut versions = self.list_artifact_versions(artifact_name).await?;
+                versions.versions.retain(|v| v != version);
+                self.write_artifact_versions(artifact_name, &versions)
+                    .await?;
+            }
+            RepositoryLayout::Flat => {
+                let mut flat_artifact = self.read_flat_artifact(artifact_name).await?;
+                flat_artifact.versions.retain(|a| &a.version != version);
+                self.write_flat_artifact(artifact_name, &flat_artifact)
+                    .await?;
+            }
+        }
+        let mut paths: Vec<String> = artifact
+            .files
+            .iter()
+            .map(|file| path::artifact::artifact_file(artifact_name, version, &file.name))
+            .collect();
+        if self.layout() == RepositoryLayout::Standard {
+            paths.push(path::artifact::artifact(artifact_name, version));
+        }
+        for path in paths {
+            info!("deleting {}", path);
+            match self.backend.delete_file(&path).await {
+                // already deleted by an interrupted deletion
+                Err(BackendError::ResourceNotFound) => warn!("{} not found", path),
+                result => result?,
+            }
+        }
+        Ok(artifact)
+    }
+
+    /// Copy every version of every artifact to `destination`, files and metadata included.
+    /// Signatures and push timestamps are kept as is: the destination must trust the keys of
+    /// this repository.
+    ///
+    /// Versions already present in the destination with the same files are skipped. Channels
+    /// are then pointed to the same versions as in this repository. A failure is reported and
+    /// the mirror goes on with the next version.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn mirror_to(
+        &mut self,
+        destination: &mut Repository<T>,
+        options: &MirrorOptions,
+    ) -> Result<MirrorReport, Error> {
+        let mut report = MirrorReport::default();
+        for artifact_name in self.list_artifacts().await?.artifacts {
+            let (versions, existing_versions) =
+                match self.mirror_versions(destination, &artifact_name).await {
+                    Ok(versions) => versions,
+                    Err(error) => {
+                        warn!("Cannot mirror {}: {}", artifact_name, error);
+                        report.failed.push(MirrorFailure {
+                            artifact_name,
+                            version: None,
+                            error,
+                        });
+                        continue;
+                    }
+                };
+            for version in versions.versions {
+                let mirrored = self
+                    .mirror_version(
+                        destination,
+                        &artifact_name,
+                        &version,
+                        existing_versions.versions.contains(&version),
+                        options,
+                    )
+                    .await;
+                match mirrored {
+                    Ok(true) => {
+                        info!("Mirrored {} {}", artifact_name, version);
+                        report.copied.push((artifact_name.clone(), version))
+                    }
+                    Ok(false) => {
+                        info!("{} {} already mirrored", artifact_name, version);
+                        report.skipped.push((artifact_name.clone(), version))
+                    }
+                    Err(error) => {
+                        warn!("Cannot mirror {} {}: {}", artifact_name, version, error);
+                        report.failed.push(MirrorFailure {
+                            artifact_name: artifact_name.clone(),
+                            version: Some(version),
+                            error,
+                        })
+                    }
+                }
+            }
+            if let Err(error) = self.copy_channels(destination, &artifact_name, None).await {
+                warn!("Cannot mirror the channels of {}: {}", artifact_name, error);
+                report.failed.push(MirrorFailure {
+                    artifact_name,
+                    version: None,
+                    error,
+                });
+            }
+        }
+        Ok(report)
+    }
+
+    /// Point the channels of `destination` to the same versions as in this repository, only
+    /// those pointing to `version` if given
+    async fn copy_channels(
+        &mut self,
+        destination: &mut Repository<T>,
+        artifact_name: &str,
+        version: Option<&Version>,
+    ) -> Result<(), Error> {
+        for (channel, channel_version) in self.list_channels(artifact_name).await? {
+            if matches!(version, Some(version) if version != &channel_version) {
+                continue;
+            }
+            match destination.get_channel(artifact_name, &channel).await {
+                Ok(existing) if existing == channel_version => continue,
+                Ok(_) => {}
+                Err(e) => match e.downcast::<RepositoryError>()? {
+                    RepositoryError::ChannelNotFound { .. } => {}
+                    e => Err(e)?,
+                },
+            }
+            info!(
+                "Pointing channel {} of {} to {}",
+                channel, artifact_name, channel_version
+            );
+            destination
+                .set_channel(artifact_name, &channel, &channel_version)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Versions of an artifact in this repository and in `destination`
+    async fn mirror_versions(
+        &mut self,
+        destination: &mut Repository<T>,
+        artifact_name: &str,
+    ) -> Result<(Versions, Versions), Error> {
+        let versions = self.list_artifact_versions(artifact_name).await?;
+        let existing_versions = match destination.list_artifact_versions(artifact_name).await {
+            Ok(versions) => versions,
+            Err(e) => match e.downcast::<BackendError>()? {
+                BackendError::ResourceNotFound => Versions::new(),
+                e => Err(e)?,
+            },
+        };
+        Ok((versions, existing_versions))
+    }
+
+    /// Copy a single version to `destination`, files and metadata included, eg. from a staging
+    /// repository to a production one. The signature is verified and kept as is: the artifact
+    /// can still be verified with the keys of this repository. Refuses to copy files hashed with
+    /// another checksum method than the one published by the destination. The channels of this
+    /// repository pointing to the version are pointed to it in the destination too.
+    ///
+    /// Returns false if the version is already in the destination with the same files.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name, version = %version))
+    )]
+    pub async fn promote_to(
+        &mut self,
+        destination: &mut Repository<T>,
+        artifact_name: &str,
+        version: &Version,
+    ) -> Result<bool, Error> {
+        let artifact = self.get_artifact(artifact_name, version).await?;
+        if let Some(params) = &destination.config.publish_parameters {
+            if let Some(file) = artifact
+                .files
+                .iter()
+                .find(|file| file.checksum_method != params.checksum_method)
+            {
+                Err(RepositoryError::ChecksumMethodMismatch {
+                    artifact: format!("{} {}", artifact_name, version),
+                    checksum_method: file.checksum_method,
+                    expected: params.checksum_method,
+                })?;
+            }
+        }
+        let (_, existing_versions) = self.mirror_versions(destination, artifact_name).await?;
+        let copied = self
+            .copy_version(
+                destination,
+                artifact_name,
+                &artifact,
+                existing_versions.versions.contains(version),
+            )
+            .await?;
+        self.copy_channels(destination, artifact_name, Some(version))
+            .await?;
+        Ok(copied)
+    }
+
+    /// Copy a version to `destination`, returns false if it is already there
+    async fn mirror_version(
+        &mut self,
+        destination: &mut Repository<T>,
+        artifact_name: &str,
+        version: &Version,
+        exists: bool,
+        options: &MirrorOptions,
+    ) -> Result<bool, Error> {
+        let artifact = if options.verify_signatures {
+            self.get_artifact(artifact_name, version).await?
+        } else {
+            self.read_artifact(artifact_name, version).await?
+        };
+        self.copy_version(destination, artifact_name, &artifact, exists)
+            .await
+    }
+
+    /// Copy the files and metadata of `artifact` to `destination`, returns false if it is
+    /// already there
+    async fn copy_version(
+        &mut self,
+        destination: &mut Repository<T>,
+        artifact_name: &str,
+        artifact: &Artifact,
+        exists: bool,
+    ) -> Result<bool, Error> {
+        let version = &artifact.version;
+        if exists {
+            // same names, checksums and modes: the files are identical
+            if destination
+                .read_artifact(artifact_name, version)
+                .await?
+                .files
+                == artifact.files
+            {
+                return Ok(false);
+            }
+            Err(RepositoryError::MirrorConflict(format!(
+                "{} {}",
+                artifact_name, version
+            )))?;
+        }
+        let tmp_dir = tempdir()?;
+        let mut file_paths = Vec::new();
+        for file in &artifact.files {
+            file_paths.push(
+                self.copy_to_tmpdir(artifact_name, version, file, &tmp_dir, None)
+                    .await?,
+            );
+        }
+        destination
+            .import_artifact(artifact_name, artifact, &file_paths)
+            .await?;
+        Ok(true)
+    }
+
+    /// Check the whole repository: the signature of every version listed by the indexes, the
+    /// presence of the files they reference, and files referenced by no artifact. Problems are
+    /// collected in the report, only a failure to list the artifacts or the files is an error.
+    ///
+    /// Orphaned files are deleted if `repair` is set, unless modified less than
+    /// [`FSCK_GRACE_PERIOD_HOURS`] ago: they may be the files of a push in progress, or a
+    /// temporary file of the file backend being written. Files of a version or an artifact whose
+    /// metadata cannot be verified are never considered orphaned.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn fsck(&mut self, repair: bool) -> Result<FsckReport, Error> {
+        let mut report = FsckReport::default();
+        let mut referenced: HashSet<String> = HashSet::new();
+        // prefixes of the files of unverifiable metadata
+        let mut unknown_prefixes = Vec::new();
+        referenced.insert(path::artifacts().to_string());
+        referenced.insert(path::signature(path::artifacts()));
+        for artifact_name in self.list_artifacts().await?.artifacts {
+            let versions_path = match self.layout() {
+                RepositoryLayout::Standard => path::artifact::versions(&artifact_name),
+                RepositoryLayout::Flat => path::artifact::flat(&artifact_name),
+            };
+            referenced.insert(path::signature(&versions_path));
+            referenced.insert(versions_path.clone());
+            let latest_path = path::artifact::latest(&artifact_name);
+            referenced.insert(path::signature(&latest_path));
+            referenced.insert(latest_path);
+            // channels are not indexed
+            unknown_prefixes.push(format!("{}/channels/", artifact_name));
+            let versions = match self.list_artifact_versions(&artifact_name).await {
+                Ok(versions) => versions,
+                Err(error) => {
+                    report.invalid.push((versions_path, error));
+                    unknown_prefixes.push(format!("{}/", artifact_name));
+                    continue;
+                }
+            };
+            for version in versions.versions {
+                let artifact_path = path::artifact::artifact(&artifact_name, &version);
+                if self.layout() == RepositoryLayout::Standard {
+                    referenced.insert(artifact_path.clone());
+                }
+                let artifact = match self.get_artifact(&artifact_name, &version).await {
+                    Ok(artifact) => artifact,
+                    Err(error) => {
+                        report.invalid.push((artifact_path, error));
+                        unknown_prefixes.push(path::artifact::artifact_file(
+                            &artifact_name,
+                            &version,
+                            "",
+                        ));
+                        continue;
+                    }
+                };
+                report.checked_versions += 1;
+                for file in &artifact.files {
+                    let file_path =
+                        path::artifact::artifact_file(&artifact_name, &version, &file.name);
+                    match self.backend.head_file(&file_path).await {
+                        Ok(true) => {}
+                        Ok(false) => report.missing_files.push(file_path.clone()),
+                        Err(error) => report
+                            .unchecked_files
+                            .push((file_path.clone(), error.into())),
+                    }
+                    referenced.insert(file_path);
+                }
+            }
+        }
+        let mut files = self.backend.list_files("").await?;
+        files.sort();
+        for file in files {
+            if referenced.contains(&file)
+                || unknown_prefixes
+                    .iter()
+                    .any(|prefix| file.starts_with(prefix.as_str()))
+            {
+                continue;
+            }
+            if repair {
+                if self.recently_modified(&file).await {
+                    report.recent_files.push(file.clone());
+                } else {
+                    self.backend.delete_file(&file).await?;
+                    report.removed_files.push(file.clone());
+                }
+            }
+            report.orphaned_files.push(file);
+        }
+        Ok(report)
+    }
+
+    /// Whether a file was modified during the fsck grace period, or its modification time is
+    /// unknown
+    async fn recently_modified(&mut self, path: &str) -> bool {
+        match self.backend.last_modified(path).await {
+            Ok(Some(modified)) => {
+                self.clock.now() - modified < chrono::Duration::hours(FSCK_GRACE_PERIOD_HOURS)
+            }
+            // the backend does not report modification times, the file may be in use
+            Ok(None) => true,
+            Err(e) => {
+                warn!("Unable to get the modification time of {}: {}", path, e);
+                true
+            }
+        }
+    }
+
+    /// Store an artifact version, metadata included, as read from another repository
+    async fn import_artifact(
+        &mut self,
+        artifact_name: &str,
+        artifact: &Artifact,
+        file_paths: &[PathBuf],
+    ) -> Result<(), Error> {
+        let mut versions = self.init_artifact(artifact_name).await?;
+        for (file, file_path) in artifact.files.iter().zip(file_paths) {
+            let remote =
+                path::artifact::artifact_file(artifact_name, &artifact.version, &file.name);
+            self.backend.push_file(file_path.clone(), &remote).await?;
+        }
+        self.write_artifact(artifact_name, &artifact.version, artifact)
+            .await?;
+        versions.versions.push(artifact.version.clone());
+        self.write_artifact_versions(artifact_name, &versions)
+            .await?;
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(artifact = artifact_name, version = %artifact_version))
+    )]
+    pub async fn pull_artifact<P: AsRef<Path>>(
+        &mut self,
+        artifact_name: &str,
+        artifact_version: &Version,
         destination_dir: P,
         overwrite_dest: bool,
+    ) -> Result<Artifact, Error> {
+        let options = PullOptions {
+            overwrite_dest,
+            ..Default::default()
+        };
+        self.pull_artifact_with_options(artifact_name, artifact_version, destination_dir, &options)
+            .await
+    }
+
+    /// Pull the files of an artifact selected by the file filter of `options`, the files of the
+    /// returned artifact are the pulled ones
+    pub async fn pull_artifact_with_options<P: AsRef<Path>>(
+        &mut self,
+        artifact_name: &str,
+        artifact_version: &Version,
+        destination_dir: P,
+        options: &PullOptions,
     ) -> Result<Artifact, Error> {
         // First: download to a temporary dir,
         // then verify checksum
         // then move to final destination
 
-        let artifact = self.get_artifact(artifact_name, artifact_version).await?;
+        let mut artifact = self.get_artifact(artifact_name, artifact_version).await?;
+        if !options.file_filter.is_empty() {
+            artifact
+                .files
+                .retain(|file| options.file_filter.matches(&file.name));
+            if artifact.files.is_empty() {
+                Err(RepositoryError::NoFileMatchingFilter(format!(
+                    "{} {}",
+                    artifact_name, artifact_version
+                )))?;
+            }
+        }
 
         file_utils::mkdirs(&destination_dir)?;
+        if self.config.check_disk_space() {
+            check_disk_space(&artifact, &destination_dir)?;
+        }
+
+        // interrupted downloads are kept at a stable path when the backend can resume them
+        let partial_dir = if self.backend.resumes_downloads() {
+            Some(
+                options
+                    .partial_dir
+                    .clone()
+                    .unwrap_or_else(|| partial_download_dir(artifact_name, &destination_dir)),
+            )
+        } else {
+            None
+        };
+        // the temporary directory is removed when dropped
+        let (_tmp_dir, download_dir) = match &partial_dir {
+            Some(partial_dir) => (None, prepare_partial_dir(partial_dir, artifact_version)?),
+            None => {
+                let tmp_dir = tempdir_in(&destination_dir)?;
+                let download_dir = tmp_dir.path().to_path_buf();
+                (Some(tmp_dir), download_dir)
+            }
+        };
 
-        let tmp_dir = tempdir_in(&destination_dir)?;
+        // a single progress for all the files, its total is unknown if a size is missing
+        let shared_progress = if self.config.aggregate_progress() {
+            Some(SharedProgress::new(T::create(
+                Some(format!(
+                    "downloading {} {}",
+                    artifact_name, artifact_version
+                )),
+                artifact
+                    .total_size()
+                    .map(|size| usize::try_from(size).unwrap_or(usize::MAX)),
+            )))
+        } else {
+            None
+        };
 
-        let mut temporary_file_paths: Vec<PathBuf> = Vec::new();
-        for file in &artifact.files {
-            temporary_file_paths.push(
-                self.copy_to_tmpdir(&artifact_name, artifact_version, file, &tmp_dir)
-                    .await?,
-            );
-        }
+        // the first failure drops the other downloads in progress
+        let repository = &*self;
+        let tmp_dir_path = download_dir.as_path();
+        let shared_progress = shared_progress.as_ref();
+        let mut downloads: Vec<(usize, PathBuf)> =
+            futures::stream::iter(artifact.files.iter().enumerate())
+                .map(|(index, file)| async move {
+                    let path = repository
+                        .copy_to_tmpdir(
+                            artifact_name,
+                            artifact_version,
+                            file,
+                            tmp_dir_path,
+                            shared_progress,
+                        )
+                        .await?;
+                    Ok::<_, Error>((index, path))
+                })
+                .buffer_unordered(self.config.max_concurrent_downloads())
+                .try_collect()
+                .await?;
+        // downloads complete in any order
+        downloads.sort_by_key(|(index, _)| *index);
+        let temporary_file_paths: Vec<PathBuf> =
+            downloads.into_iter().map(|(_, path)| path).collect();
 
         // all files are downloaded with checksum been verified,
         // move them to the final destination
@@ -320,7 +1720,7 @@ where
                 .try_fold(Vec::new(), |mut paths, file| -> Result<_, Error> {
                     let dest_file_path = path_concat2(&dest_path, &file.name);
                     if let Ok(_) = std::fs::metadata(&dest_file_path) {
-                        if !overwrite_dest {
+                        if !options.overwrite_dest {
                             // cannot overwrite => error
                             Err(RepositoryError::DestinationFileAlreadyExists(
                                 dest_file_path.to_string_lossy().into(),
@@ -334,29 +1734,78 @@ where
                     Ok(paths)
                 })?;
 
-        temporary_file_paths
-            .iter()
-            .zip(dest_file_paths.iter())
-            .try_for_each(|(src, dst)| mv(src, dst))?;
+        for (src, dst) in temporary_file_paths.iter().zip(dest_file_paths.iter()) {
+            file_utils::mkdirs_parent(dst)?;
+            mv(src, dst)?;
+        }
+        ensure_files_present(&dest_file_paths)?;
+        if self.config.fsync() {
+            file_utils::sync_to_disk(&dest_file_paths, &dest_path)?;
+        }
+        if let Some(partial_dir) = partial_dir {
+            std::fs::remove_dir_all(partial_dir)?;
+        }
 
         Ok(artifact)
     }
 
-    async fn copy_to_tmpdir<P: AsRef<Path>>(
+    /// Pull a single file of an artifact and write it to `writer`. The file is downloaded to a
+    /// temporary directory first: nothing is written before its checksum is verified.
+    pub async fn get_file<W: std::io::Write>(
         &mut self,
         artifact_name: &str,
         artifact_version: &Version,
+        file_name: &str,
+        writer: &mut W,
+    ) -> Result<metadata::File, Error> {
+        let artifact = self.get_artifact(artifact_name, artifact_version).await?;
+        let file = artifact
+            .files
+            .iter()
+            .find(|file| file.name == file_name)
+            .ok_or_else(|| RepositoryError::FileNotInArtifact {
+                file: file_name.to_string(),
+                artifact: format!("{} {}", artifact_name, artifact_version),
+                files: artifact
+                    .files
+                    .iter()
+                    .map(|file| file.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            })?;
+        let tmp_dir = tempdir()?;
+        let path = self
+            .copy_to_tmpdir(artifact_name, artifact_version, file, &tmp_dir, None)
+            .await?;
+        std::io::copy(&mut File::open(&path)?, writer)?;
+        writer.flush()?;
+        Ok(file.clone())
+    }
+
+    async fn copy_to_tmpdir<P: AsRef<Path>>(
+        &self,
+        artifact_name: &str,
+        artifact_version: &Version,
         file: &metadata::File,
         tmp_dir: P,
+        shared_progress: Option<&SharedProgress>,
     ) -> Result<PathBuf, Error> {
+        // the metadata may come from an untrusted repository
+        validate_file_name(&file.name)?;
         let dest_path = path_concat2(&tmp_dir, &file.name);
+        file_utils::mkdirs_parent(&dest_path)?;
         info!("Pulling {} to {}", file.name, dest_path.to_string_lossy());
-        self.backend
-            .pull_file(
+        // the checksum is computed while downloading
+        let digest = self
+            .backend
+            .pull_file_with_digest(
                 &path::artifact::artifact_file(artifact_name, artifact_version, &file.name),
                 dest_path.clone(),
+                file.checksum_method.algorithm(),
+                shared_progress,
             )
             .await?;
+        ensure_files_present(&[&dest_path])?;
 
         if let Some(unix_mode) = file.unix_mode {
             let metadata = std::fs::metadata(&dest_path)?;
@@ -365,12 +1814,10 @@ where
             std::fs::set_permissions(&dest_path, permissions)?;
         }
 
-        // let's checksum the file.
-        let digest = data_encoding::BASE64.encode(
-            crypto::digest_file(dest_path.clone(), file.checksum_method.algorithm())?.as_ref(),
-        );
         // verify the checksum
-        if digest != file.checksum {
+        if data_encoding::BASE64.encode(digest.as_ref()) != file.checksum {
+            // a corrupted partial download must not be resumed
+            std::fs::remove_file(&dest_path)?;
             Err(RepositoryError::WrongFileChecksum(file.name.clone()))?;
         }
         Ok(dest_path)
@@ -379,9 +1826,18 @@ where
 
 #[cfg(test)]
 mod test {
-    use crate::config::Config;
+    use super::RepositoryError;
+    use crate::backend::file_backend::FileBackend;
+    use crate::backend::recording_backend::RecordingBackend;
+    use crate::backend::BackendError;
+    use crate::config::{Config, RepositoryLayout};
+    use crate::metadata::ChecksumMethod;
     use crate::progress::NOOPProgress;
+    use futures::future::LocalBoxFuture;
     use semver::Version;
+    use std::cell::{Cell, RefCell};
+    use std::path::PathBuf;
+    use std::rc::Rc;
 
     #[test]
     fn validate_artifact_name() {
@@ -467,4 +1923,1314 @@ mod test {
         .await
         .unwrap();
     }
+
+    #[tokio::test]
+    async fn resign_all() {
+        let mut config = Config::create_file_test_config();
+        config.hmac_keys.as_mut().unwrap().insert(
+            "new-key".to_string(),
+            "9BPkIT+a5giXicKvRIsUzBqf21BRJ7LbcCduCS6cxK7Jf6jGCYzeWXISbpe29zEn".to_string(),
+        );
+        let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+        let v1 = Version::new(1, 0, 0);
+        let v2 = Version::new(2, 0, 0);
+        let pushed = repo
+            .push_artifact("binrep", &v1, &["Cargo.toml"])
+            .await
+            .unwrap();
+        repo.push_artifact("binrep", &v2, &["Cargo.toml", "src/lib.rs"])
+            .await
+            .unwrap();
+
+        let report = repo.resign_all(Some("new-key")).await.unwrap();
+        assert_eq!(
+            vec![
+                ("binrep".to_string(), v1.clone()),
+                ("binrep".to_string(), v2)
+            ],
+            report.resigned
+        );
+        assert!(report.failed.is_empty());
+        let artifact = repo.get_artifact("binrep", &v1).await.unwrap();
+        assert_eq!("new-key", artifact.signatures[0].key_id);
+        assert_ne!(pushed.signatures, artifact.signatures);
+        assert_eq!(pushed.files, artifact.files);
+
+        // old key removed: artifacts are still readable
+        repo.config.hmac_keys.as_mut().unwrap().remove("test");
+        let pull_dir = tempfile::tempdir().unwrap();
+        repo.pull_artifact("binrep", &v1, pull_dir.path(), false)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn resign_all_invalid_version() {
+        let mut config = Config::create_file_test_config();
+        let root = PathBuf::from(&config.backend.file_backend_opt.as_ref().unwrap().root);
+        config.hmac_keys.as_mut().unwrap().insert(
+            "new-key".to_string(),
+            "9BPkIT+a5giXicKvRIsUzBqf21BRJ7LbcCduCS6cxK7Jf6jGCYzeWXISbpe29zEn".to_string(),
+        );
+        let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+        let v1 = Version::new(1, 0, 0);
+        let v2 = Version::new(2, 0, 0);
+        let v3 = Version::new(3, 0, 0);
+        for version in &[&v1, &v2, &v3] {
+            repo.push_artifact("binrep", version, &["Cargo.toml"])
+                .await
+                .unwrap();
+        }
+        std::fs::write(root.join("binrep/2.0.0/artifact.sane"), "not sane").unwrap();
+
+        let report = repo.resign_all(Some("new-key")).await.unwrap();
+        assert_eq!(
+            vec![
+                ("binrep".to_string(), v1.clone()),
+                ("binrep".to_string(), v3.clone())
+            ],
+            report.resigned
+        );
+        assert_eq!(1, report.failed.len());
+        assert_eq!(
+            ("binrep", &v2),
+            (report.failed[0].0.as_str(), &report.failed[0].1)
+        );
+        // the invalid version is left untouched
+        assert_eq!(
+            "not sane",
+            std::fs::read_to_string(root.join("binrep/2.0.0/artifact.sane")).unwrap()
+        );
+        let artifact = repo.get_artifact("binrep", &v3).await.unwrap();
+        assert_eq!("new-key", artifact.signatures[0].key_id);
+    }
+
+    #[tokio::test]
+    async fn multiple_signatures() {
+        let mut config = Config::create_file_test_config();
+        config.ed25519_keys = Config::create_file_test_config_ed25519_publish().ed25519_keys;
+        config
+            .publish_parameters
+            .as_mut()
+            .unwrap()
+            .additional_signatures = Some(vec![crate::config::AdditionalSignature {
+            signature_method: crate::metadata::SignatureMethod::ED25519,
+            hmac_signing_key: None,
+            ed25519_signing_key: Some("test".to_string()),
+        }]);
+        let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+        let version = Version::new(1, 0, 0);
+        let artifact = repo
+            .push_artifact("binrep", &version, &["Cargo.toml"])
+            .await
+            .unwrap();
+        assert_eq!(2, artifact.signatures.len());
+        assert_eq!(
+            artifact,
+            repo.get_artifact("binrep", &version).await.unwrap()
+        );
+
+        // the primary signature is stored as a single signature, as before
+        let mut single = artifact.clone();
+        single.signatures.truncate(1);
+        let serialized = sane::to_string(&single).unwrap();
+        assert!(serialized.contains("signature"));
+        assert!(!serialized.contains("additional_signatures"));
+        assert_eq!(single, sane::from_str(&serialized).unwrap());
+
+        // all signatures must be valid by default
+        let mut tampered = artifact.clone();
+        tampered.signatures[1].signature = tampered.signatures[0].signature.clone();
+        repo.write_artifact("binrep", &version, &tampered)
+            .await
+            .unwrap();
+        assert!(repo.get_artifact("binrep", &version).await.is_err());
+        repo.config.verify_parameters = Some(crate::config::VerifyParameters {
+            signature_quorum: Some(1),
+            ..Default::default()
+        });
+        repo.get_artifact("binrep", &version).await.unwrap();
+
+        // unknown key
+        repo.write_artifact("binrep", &version, &artifact)
+            .await
+            .unwrap();
+        repo.config.ed25519_keys = None;
+        repo.get_artifact("binrep", &version).await.unwrap();
+        repo.config.verify_parameters = None;
+        assert!(repo.get_artifact("binrep", &version).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn push_empty_artifact() {
+        let config = Config::create_file_test_config();
+        let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+        let err = repo
+            .push_artifact::<&str>("binrep", &Version::new(1, 0, 0), &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<super::RepositoryError>(),
+            Some(super::RepositoryError::EmptyArtifact)
+        ));
+        // nothing has been created
+        assert!(repo.list_artifacts().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn get_file() {
+        let config = Config::create_file_test_config();
+        let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+        let version = Version::new(1, 0, 0);
+        repo.push_artifact("binrep", &version, &["Cargo.toml", "src/lib.rs"])
+            .await
+            .unwrap();
+
+        let mut out = Vec::new();
+        let file = repo
+            .get_file("binrep", &version, "lib.rs", &mut out)
+            .await
+            .unwrap();
+        assert_eq!("lib.rs", file.name);
+        assert_eq!(std::fs::read("src/lib.rs").unwrap(), out);
+
+        let mut out = Vec::new();
+        let err = repo
+            .get_file("binrep", &version, "README.md", &mut out)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::FileNotInArtifact { .. })
+        ));
+        assert!(err.to_string().contains("Cargo.toml, lib.rs"), "{}", err);
+        assert!(out.is_empty());
+    }
+
+    #[tokio::test]
+    async fn pull_file_filter() {
+        let config = Config::create_file_test_config();
+        let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+        let version = Version::new(1, 0, 0);
+        repo.push_artifact_with_paths(
+            "binrep",
+            &version,
+            &[
+                ("Cargo.toml", "Cargo.toml"),
+                ("src/lib.rs", "src/lib.rs"),
+                ("src/binrep.rs", "src/binrep.rs"),
+            ],
+            &super::PushOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let pull_dir = tempfile::tempdir().unwrap();
+        let options = super::PullOptions {
+            file_filter: super::FileFilter::new(
+                vec!["src/*".to_string()],
+                vec!["*/binrep.rs".to_string()],
+            )
+            .unwrap(),
+            ..Default::default()
+        };
+        let artifact = repo
+            .pull_artifact_with_options("binrep", &version, pull_dir.path(), &options)
+            .await
+            .unwrap();
+        assert_eq!(
+            vec!["src/lib.rs"],
+            artifact
+                .files
+                .iter()
+                .map(|file| file.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert!(pull_dir.path().join("src/lib.rs").is_file());
+        assert!(!pull_dir.path().join("src/binrep.rs").exists());
+        assert!(!pull_dir.path().join("Cargo.toml").exists());
+
+        let options = super::PullOptions {
+            file_filter: super::FileFilter::new(vec!["*.bin".to_string()], vec![]).unwrap(),
+            ..Default::default()
+        };
+        let err = repo
+            .pull_artifact_with_options("binrep", &version, pull_dir.path(), &options)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NoFileMatchingFilter(_))
+        ));
+        assert!(super::FileFilter::new(vec!["[".to_string()], vec![]).is_err());
+    }
+
+    #[tokio::test]
+    async fn pull_insufficient_disk_space() {
+        let config = Config::create_file_test_config();
+        let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+        let version = Version::new(1, 0, 0);
+        let mut artifact = repo
+            .push_artifact(
+                "binrep",
+                &version,
+                &["Cargo.toml", "src/lib.rs", "src/binrep.rs"],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::metadata("Cargo.toml").unwrap().len(),
+            artifact.files[0].size.unwrap()
+        );
+
+        // sizes are not signed, pretend the files are huge, their sum overflowing
+        for file in &mut artifact.files {
+            file.size = Some(u64::MAX / 2);
+        }
+        repo.write_artifact("binrep", &version, &artifact)
+            .await
+            .unwrap();
+        let pull_dir = tempfile::tempdir().unwrap();
+        let err = repo
+            .pull_artifact("binrep", &version, pull_dir.path(), false)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<super::RepositoryError>(),
+            Some(super::RepositoryError::InsufficientDiskSpace { .. })
+        ));
+        assert!(!pull_dir.path().join("Cargo.toml").exists());
+
+        repo.config.transfer_parameters = Some(crate::config::TransferParameters {
+            check_disk_space: Some(false),
+            ..Default::default()
+        });
+        repo.pull_artifact("binrep", &version, pull_dir.path(), false)
+            .await
+            .unwrap();
+    }
+
+    type HookedBackend = RecordingBackend<FileBackend<NOOPProgress>, NOOPProgress>;
+
+    /// File backend silently dropping one file when pulling
+    fn partial_backend(root: &str, dropped_file: &'static str) -> HookedBackend {
+        RecordingBackend::new(FileBackend::new(root)).with_after_pull(Box::new(
+            move |remote, local| {
+                let dropped = remote.ends_with(dropped_file);
+                let local = local.to_path_buf();
+                Box::pin(async move {
+                    if dropped {
+                        std::fs::remove_file(local)?;
+                    }
+                    Ok(())
+                })
+            },
+        ))
+    }
+
+    /// File backend resuming the downloads, interrupted halfway if `interrupt` is set, recording
+    /// the size of the partial files found when pulling
+    fn resuming_backend(
+        root: &str,
+        interrupt: bool,
+        resumed_from: Rc<RefCell<Vec<u64>>>,
+    ) -> HookedBackend {
+        RecordingBackend::new(FileBackend::new(root))
+            .with_resumed_downloads()
+            .with_before_pull(Box::new(move |_, local| {
+                let offset = std::fs::metadata(local).map(|m| m.len()).unwrap_or(0);
+                resumed_from.borrow_mut().push(offset);
+                Box::pin(futures::future::ready(Ok(())))
+            }))
+            .with_after_pull(Box::new(move |_, local| {
+                let local = local.to_path_buf();
+                Box::pin(async move {
+                    if interrupt {
+                        let file = std::fs::OpenOptions::new().write(true).open(&local)?;
+                        file.set_len(file.metadata()?.len() / 2)?;
+                        Err(std::io::Error::new(
+                            std::io::ErrorKind::ConnectionReset,
+                            "interrupted",
+                        ))?;
+                    }
+                    Ok(())
+                })
+            }))
+    }
+
+    /// File backend recording the maximum number of concurrent pushes or pulls
+    fn slow_backend(root: &str, max_in_progress: Rc<Cell<usize>>) -> HookedBackend {
+        let in_progress = Rc::new(Cell::new(0));
+        let transfer = Rc::new(
+            move || -> LocalBoxFuture<'static, Result<(), BackendError>> {
+                let in_progress = in_progress.clone();
+                let max_in_progress = max_in_progress.clone();
+                Box::pin(async move {
+                    in_progress.set(in_progress.get() + 1);
+                    max_in_progress.set(max_in_progress.get().max(in_progress.get()));
+                    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                    in_progress.set(in_progress.get() - 1);
+                    Ok(())
+                })
+            },
+        );
+        let pull_transfer = transfer.clone();
+        RecordingBackend::new(FileBackend::new(root))
+            .with_before_push(Box::new(move |_| transfer()))
+            .with_before_pull(Box::new(move |_, _| pull_transfer()))
+    }
+
+    /// File backend failing the pushes after `remaining_pushes` successful ones
+    fn failing_backend(root: &str, remaining_pushes: usize) -> HookedBackend {
+        let remaining_pushes = Rc::new(Cell::new(remaining_pushes));
+        // the file lands either way, like an interrupted multipart upload
+        RecordingBackend::new(FileBackend::new(root)).with_after_push(Box::new(move |_| {
+            let remaining_pushes = remaining_pushes.clone();
+            Box::pin(async move {
+                if remaining_pushes.get() == 0 {
+                    return Err(BackendError::Other {
+                        cause: anyhow::anyhow!("connection reset"),
+                    });
+                }
+                remaining_pushes.set(remaining_pushes.get() - 1);
+                // still in progress when the failing upload completes
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                Ok(())
+            })
+        }))
+    }
+
+    #[tokio::test]
+    async fn resume_interrupted_pull() {
+        let config = Config::create_file_test_config();
+        let root = config
+            .backend
+            .file_backend_opt
+            .as_ref()
+            .unwrap()
+            .root
+            .clone();
+        let version = Version::new(1, 0, 0);
+        super::Repository::<NOOPProgress>::new(config.clone())
+            .unwrap()
+            .push_artifact("binrep", &version, &["Cargo.toml"])
+            .await
+            .unwrap();
+        let pull_dir = tempfile::tempdir().unwrap();
+        let partial_dir = super::partial_download_dir("binrep", pull_dir.path());
+        // left by the download of another version
+        std::fs::create_dir_all(partial_dir.join("0.9.0")).unwrap();
+
+        let backend = resuming_backend(&root, true, Default::default());
+        let mut repo = super::Repository::with_backend(config.clone(), Box::new(backend));
+        repo.pull_artifact("binrep", &version, pull_dir.path(), false)
+            .await
+            .unwrap_err();
+        let partial_file = partial_dir.join("1.0.0").join("Cargo.toml");
+        assert!(partial_file.metadata().unwrap().len() > 0);
+        assert!(!partial_dir.join("0.9.0").exists());
+
+        // the next pull completes the partial file
+        let resumed_from = Rc::new(RefCell::new(Vec::new()));
+        let backend = resuming_backend(&root, false, resumed_from.clone());
+        let mut repo = super::Repository::with_backend(config, Box::new(backend));
+        repo.pull_artifact("binrep", &version, pull_dir.path(), false)
+            .await
+            .unwrap();
+        assert!(resumed_from.borrow()[0] > 0);
+        assert_eq!(
+            std::fs::read(pull_dir.path().join("Cargo.toml")).unwrap(),
+            std::fs::read("Cargo.toml").unwrap()
+        );
+        assert!(!partial_dir.exists());
+    }
+
+    #[tokio::test]
+    async fn concurrent_transfers() {
+        let files = [
+            "Cargo.toml",
+            "src/lib.rs",
+            "src/repository.rs",
+            "src/binrep.rs",
+            "src/config.rs",
+        ];
+        for max_concurrent_transfers in &[1, 3] {
+            let mut config = Config::create_file_test_config();
+            config.transfer_parameters = Some(crate::config::TransferParameters {
+                max_concurrent_uploads: Some(*max_concurrent_transfers),
+                max_concurrent_downloads: Some(*max_concurrent_transfers),
+                ..Default::default()
+            });
+            let root = config
+                .backend
+                .file_backend_opt
+                .as_ref()
+                .unwrap()
+                .root
+                .clone();
+            let max_in_progress = Rc::new(Cell::new(0));
+            let backend = slow_backend(&root, max_in_progress.clone());
+            let mut repo =
+                super::Repository::<NOOPProgress>::with_backend(config, Box::new(backend));
+            let version = Version::new(1, 0, 0);
+            repo.push_artifact("binrep", &version, &files)
+                .await
+                .unwrap();
+            assert_eq!(*max_concurrent_transfers, max_in_progress.get());
+
+            max_in_progress.set(0);
+            let pull_dir = tempfile::tempdir().unwrap();
+            let artifact = repo
+                .pull_artifact("binrep", &version, pull_dir.path(), false)
+                .await
+                .unwrap();
+            assert_eq!(*max_concurrent_transfers, max_in_progress.get());
+            for file in &artifact.files {
+                assert!(pull_dir.path().join(&file.name).is_file());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn push_artifact_with_paths() {
+        let config = Config::create_file_test_config();
+        let root = PathBuf::from(&config.backend.file_backend_opt.as_ref().unwrap().root);
+        let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+        let version = Version::new(1, 0, 0);
+        let options = super::PushOptions::default();
+        let artifact = repo
+            .push_artifact_with_paths(
+                "binrep",
+                &version,
+                &[
+                    ("Cargo.toml", "Cargo.toml"),
+                    ("src/lib.rs", "src/nested/lib.rs"),
+                ],
+                &options,
+            )
+            .await
+            .unwrap();
+        assert_eq!("src/nested/lib.rs", artifact.files[1].name);
+
+        let pull_dir = tempfile::tempdir().unwrap();
+        repo.pull_artifact("binrep", &version, pull_dir.path(), false)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read("src/lib.rs").unwrap(),
+            std::fs::read(pull_dir.path().join("src/nested/lib.rs")).unwrap()
+        );
+        assert!(pull_dir.path().join("Cargo.toml").is_file());
+
+        for name in &[
+            "../lib.rs",
+            "/lib.rs",
+            "src//lib.rs",
+            "src/./lib.rs",
+            "src\\lib.rs",
+        ] {
+            let error = repo
+                .push_artifact_with_paths(
+                    "binrep",
+                    &Version::new(2, 0, 0),
+                    &[("src/lib.rs", name)],
+                    &options,
+                )
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::InvalidFileName(_))
+            ));
+        }
+
+        repo.delete_artifact_version("binrep", &version)
+            .await
+            .unwrap();
+        assert!(!root.join("binrep").join("1.0.0").exists());
+    }
+
+    #[tokio::test]
+    async fn artifact_exists() {
+        use crate::backend::recording_backend::BackendOperation;
+
+        for layout in &[RepositoryLayout::Standard, RepositoryLayout::Flat] {
+            let mut config = Config::create_file_test_config();
+            config.backend.layout = Some(*layout);
+            let root = config
+                .backend
+                .file_backend_opt
+                .as_ref()
+                .unwrap()
+                .root
+                .clone();
+            let backend =
+                RecordingBackend::new(crate::backend::file_backend::FileBackend::new(&root));
+            let operations = backend.operations();
+            let mut repo =
+                super::Repository::<NOOPProgress>::with_backend(config, Box::new(backend));
+            let version = Version::new(1, 0, 0);
+            assert!(!repo.artifact_exists("binrep", &version).await.unwrap());
+            repo.push_artifact("binrep", &version, &["Cargo.toml"])
+                .await
+                .unwrap();
+            operations.lock().unwrap().clear();
+            assert!(repo.artifact_exists("binrep", &version).await.unwrap());
+            assert!(!repo
+                .artifact_exists("binrep", &Version::new(2, 0, 0))
+                .await
+                .unwrap());
+            if *layout == RepositoryLayout::Standard {
+                // nothing downloaded
+                assert!(operations
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .all(|operation| matches!(operation, BackendOperation::Head(_))));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn force_push() {
+        for layout in &[RepositoryLayout::Standard, RepositoryLayout::Flat] {
+            let mut config = Config::create_file_test_config();
+            config.backend.layout = Some(*layout);
+            let root = PathBuf::from(&config.backend.file_backend_opt.as_ref().unwrap().root);
+            let mut repo = super::Repository::<NOOPProgress>::new(config.clone()).unwrap();
+            let version = Version::parse("0.0.0-dev").unwrap();
+            repo.push_artifact("binrep", &version, &["Cargo.toml"])
+                .await
+                .unwrap();
+            match repo
+                .push_artifact("binrep", &version, &["src/lib.rs"])
+                .await
+                .unwrap_err()
+                .downcast::<RepositoryError>()
+                .unwrap()
+            {
+                RepositoryError::ArtifactVersionAlreadyExists => {}
+                e => panic!("unexpected error {}", e),
+            }
+
+            let options = super::PushOptions {
+                force: true,
+                ..Default::default()
+            };
+            // a failure before uploading keeps the replaced version
+            repo.push_artifact_with_options("binrep", &version, &["missing.txt"], &options)
+                .await
+                .unwrap_err();
+            let artifact = repo.get_artifact("binrep", &version).await.unwrap();
+            assert_eq!("Cargo.toml", artifact.files[0].name);
+
+            repo.push_artifact_with_options("binrep", &version, &["src/lib.rs"], &options)
+                .await
+                .unwrap();
+            assert_eq!(
+                vec![version.clone()],
+                repo.list_artifact_versions("binrep")
+                    .await
+                    .unwrap()
+                    .versions
+            );
+            let artifact = repo.get_artifact("binrep", &version).await.unwrap();
+            assert_eq!(1, artifact.files.len());
+            assert_eq!("lib.rs", artifact.files[0].name);
+            assert!(!root
+                .join("binrep")
+                .join("0.0.0-dev")
+                .join("Cargo.toml")
+                .exists());
+
+            // a failed upload keeps the replaced version and its files
+            let backend = failing_backend(root.to_str().unwrap(), 0);
+            let mut failing_repo = super::Repository::with_backend(config, Box::new(backend));
+            failing_repo
+                .push_artifact_with_options("binrep", &version, &["Cargo.toml"], &options)
+                .await
+                .unwrap_err();
+            let artifact = repo.get_artifact("binrep", &version).await.unwrap();
+            assert_eq!("lib.rs", artifact.files[0].name);
+            let pull_dir = tempfile::tempdir().unwrap();
+            repo.pull_artifact("binrep", &version, pull_dir.path(), false)
+                .await
+                .unwrap();
+            assert!(!root
+                .join("binrep")
+                .join("0.0.0-dev")
+                .join("Cargo.toml")
+                .exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn get_latest() {
+        for layout in &[RepositoryLayout::Standard, RepositoryLayout::Flat] {
+            let mut config = Config::create_file_test_config();
+            config.backend.layout = Some(*layout);
+            let root = PathBuf::from(&config.backend.file_backend_opt.as_ref().unwrap().root);
+            let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+            for version in &["1.0.0", "1.1.0", "2.0.0-beta"] {
+                repo.push_artifact("binrep", &Version::parse(version).unwrap(), &["Cargo.toml"])
+                    .await
+                    .unwrap();
+            }
+            assert_eq!(
+                Some(Version::new(1, 1, 0)),
+                repo.get_latest("binrep").await.unwrap()
+            );
+            repo.delete_artifact_version("binrep", &Version::new(1, 1, 0))
+                .await
+                .unwrap();
+            assert_eq!(
+                Some(Version::new(1, 0, 0)),
+                repo.get_latest("binrep").await.unwrap()
+            );
+            repo.delete_artifact_version("binrep", &Version::new(1, 0, 0))
+                .await
+                .unwrap();
+            assert_eq!(None, repo.get_latest("binrep").await.unwrap());
+
+            if *layout == RepositoryLayout::Standard {
+                // repositories written before the latest version file existed
+                repo.push_artifact("binrep", &Version::new(1, 2, 0), &["Cargo.toml"])
+                    .await
+                    .unwrap();
+                std::fs::remove_file(root.join("binrep").join("latest.sane")).unwrap();
+                assert_eq!(
+                    Some(Version::new(1, 2, 0)),
+                    repo.get_latest("binrep").await.unwrap()
+                );
+
+                // a latest version that does not exist is ignored
+                std::fs::write(
+                    root.join("binrep").join("latest.sane"),
+                    "version = \"9.9.9\"",
+                )
+                .unwrap();
+                assert_eq!(
+                    Some(Version::new(1, 2, 0)),
+                    repo.get_latest("binrep").await.unwrap()
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn signed_indexes() {
+        let mut config = Config::create_file_test_config();
+        config.verify_parameters = Some(crate::config::VerifyParameters {
+            verify_indexes: Some(true),
+            ..Default::default()
+        });
+        let root = PathBuf::from(&config.backend.file_backend_opt.as_ref().unwrap().root);
+        let mut repo = super::Repository::<NOOPProgress>::new(config.clone()).unwrap();
+        repo.push_artifact("binrep", &Version::new(1, 0, 0), &["Cargo.toml"])
+            .await
+            .unwrap();
+        assert!(root.join("artifacts.sane.sig").is_file());
+        assert_eq!(
+            1,
+            repo.list_artifact_versions("binrep")
+                .await
+                .unwrap()
+                .versions
+                .len()
+        );
+
+        let versions = root.join("binrep/versions.sane");
+        let content = std::fs::read_to_string(&versions).unwrap();
+        std::fs::write(&versions, content.replace("1.0.0", "1.0.1")).unwrap();
+        match repo
+            .list_artifact_versions("binrep")
+            .await
+            .unwrap_err()
+            .downcast::<RepositoryError>()
+            .unwrap()
+        {
+            RepositoryError::WrongIndexSignature(path) => assert_eq!("binrep/versions.sane", path),
+            e => panic!("unexpected error {}", e),
+        }
+        // a tampered list is not overwritten by the next push
+        assert!(repo
+            .push_artifact("binrep", &Version::new(2, 0, 0), &["Cargo.toml"])
+            .await
+            .is_err());
+
+        // not verified by default
+        config.verify_parameters = None;
+        let mut unverified_repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+        assert!(unverified_repo
+            .list_artifact_versions("binrep")
+            .await
+            .is_ok());
+
+        std::fs::remove_file(root.join("artifacts.sane.sig")).unwrap();
+        match repo
+            .list_artifacts()
+            .await
+            .unwrap_err()
+            .downcast::<RepositoryError>()
+            .unwrap()
+        {
+            RepositoryError::MissingIndexSignature(path) => assert_eq!("artifacts.sane", path),
+            e => panic!("unexpected error {}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn unsigned_index_removes_signature() {
+        let mut config = Config::create_file_test_config();
+        let root = PathBuf::from(&config.backend.file_backend_opt.as_ref().unwrap().root);
+        let mut repo = super::Repository::<NOOPProgress>::new(config.clone()).unwrap();
+        for version in &[Version::new(1, 0, 0), Version::new(1, 1, 0)] {
+            repo.push_artifact("binrep", version, &["Cargo.toml"])
+                .await
+                .unwrap();
+        }
+        let signature = root.join("binrep/versions.sane.sig");
+        assert!(signature.is_file());
+
+        config.publish_parameters = None;
+        let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+        repo.delete_artifact_version("binrep", &Version::new(1, 1, 0))
+            .await
+            .unwrap();
+        assert!(!signature.exists());
+        assert_eq!(
+            1,
+            repo.list_artifact_versions("binrep")
+                .await
+                .unwrap()
+                .versions
+                .len()
+        );
+    }
+
+    #[tokio::test]
+    async fn duplicate_file_name() {
+        let config = Config::create_file_test_config();
+        let root = PathBuf::from(&config.backend.file_backend_opt.as_ref().unwrap().root);
+        let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        for sub_dir in &["a", "b"] {
+            std::fs::create_dir(dir.path().join(sub_dir)).unwrap();
+            std::fs::write(dir.path().join(sub_dir).join("x.txt"), sub_dir).unwrap();
+        }
+        let error = repo
+            .push_artifact(
+                "binrep",
+                &Version::new(1, 0, 0),
+                &[dir.path().join("a/x.txt"), dir.path().join("b/x.txt")],
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::DuplicateFileName(name)) if name == "x.txt"
+        ));
+        // nothing pushed
+        assert!(std::fs::read_dir(root).unwrap().next().is_none());
+    }
+
+    /// File backend failing to push more than `remaining_pushes` files
+    #[tokio::test]
+    async fn failed_push_is_cleaned_up() {
+        for layout in &[RepositoryLayout::Standard, RepositoryLayout::Flat] {
+            let mut config = Config::create_file_test_config();
+            config.backend.layout = Some(*layout);
+            let root = config
+                .backend
+                .file_backend_opt
+                .as_ref()
+                .unwrap()
+                .root
+                .clone();
+            let backend = failing_backend(&root, 2);
+            let mut repo = super::Repository::with_backend(config.clone(), Box::new(backend));
+            let version = Version::new(1, 0, 0);
+            let error = repo
+                .push_artifact(
+                    "binrep",
+                    &version,
+                    &["Cargo.toml", "src/lib.rs", "src/repository.rs"],
+                )
+                .await
+                .unwrap_err();
+            assert!(error.to_string().contains("connection reset"));
+
+            // neither the pushed files nor the version are left behind
+            assert!(!PathBuf::from(&root).join("binrep").join("1.0.0").exists());
+            let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+            assert!(repo
+                .list_artifact_versions("binrep")
+                .await
+                .unwrap()
+                .versions
+                .is_empty());
+            repo.push_artifact("binrep", &version, &["Cargo.toml"])
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn pull_missing_file() {
+        let config = Config::create_file_test_config();
+        let root = config
+            .backend
+            .file_backend_opt
+            .as_ref()
+            .unwrap()
+            .root
+            .clone();
+        let version = Version::new(1, 0, 0);
+        super::Repository::<NOOPProgress>::new(config.clone())
+            .unwrap()
+            .push_artifact("binrep", &version, &["Cargo.toml", "src/lib.rs"])
+            .await
+            .unwrap();
+
+        let backend = partial_backend(&root, "lib.rs");
+        let mut repo = super::Repository::with_backend(config, Box::new(backend));
+        let pull_dir = tempfile::tempdir().unwrap();
+        let err = repo
+            .pull_artifact("binrep", &version, pull_dir.path(), false)
+            .await
+            .unwrap_err();
+        match err.downcast_ref::<super::RepositoryError>() {
+            Some(super::RepositoryError::MissingPulledFile(missing)) => {
+                assert!(missing.ends_with("lib.rs"))
+            }
+            _ => panic!("unexpected error {:?}", err),
+        }
+        // nothing installed
+        assert!(!pull_dir.path().join("Cargo.toml").exists());
+    }
+
+    #[tokio::test]
+    async fn artifact_timestamp_in_the_future() {
+        use crate::clock::FixedClock;
+        use std::sync::Arc;
+
+        let mut config = Config::create_file_test_config();
+        config.verify_parameters = Some(crate::config::VerifyParameters {
+            reject_invalid_timestamps: Some(true),
+            ..Default::default()
+        });
+        let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+        let version = Version::new(1, 0, 0);
+        let mut artifact = repo
+            .push_artifact("binrep", &version, &["Cargo.toml"])
+            .await
+            .unwrap();
+        // freshly pushed artifact is valid
+        repo.get_artifact("binrep", &version).await.unwrap();
+
+        // publisher clock is 2 days ahead
+        let now = chrono::Utc::now();
+        let clock = Arc::new(FixedClock::new(now + chrono::Duration::days(2)));
+        repo.set_clock(clock.clone());
+        let future_version = Version::new(2, 0, 0);
+        repo.push_artifact("binrep", &future_version, &["Cargo.toml"])
+            .await
+            .unwrap();
+        clock.set(now);
+        let err = repo
+            .get_artifact("binrep", &future_version)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<super::RepositoryError>(),
+            Some(super::RepositoryError::InvalidArtifactTimestamp { .. })
+        ));
+        // within the tolerated skew once the clock catches up
+        clock.advance(chrono::Duration::days(2) - chrono::Duration::seconds(60));
+        repo.get_artifact("binrep", &future_version).await.unwrap();
+
+        artifact.pushed_at = Some("yesterday".to_string());
+        repo.write_artifact("binrep", &version, &artifact)
+            .await
+            .unwrap();
+        assert!(repo.get_artifact("binrep", &version).await.is_err());
+
+        // only a warning by default
+        repo.config.verify_parameters = None;
+        repo.get_artifact("binrep", &version).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn delete_artifact_version() {
+        for layout in &[RepositoryLayout::Standard, RepositoryLayout::Flat] {
+            let mut config = Config::create_file_test_config();
+            config.backend.layout = Some(*layout);
+            let root = PathBuf::from(&config.backend.file_backend_opt.as_ref().unwrap().root);
+            let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+            let v1 = Version::new(1, 0, 0);
+            let v2 = Version::new(2, 0, 0);
+            repo.push_artifact("binrep", &v1, &["Cargo.toml", "./src/lib.rs"])
+                .await
+                .unwrap();
+            repo.push_artifact("binrep", &v2, &["Cargo.toml"])
+                .await
+                .unwrap();
+
+            let deleted = repo.delete_artifact_version("binrep", &v1).await.unwrap();
+            assert_eq!(2, deleted.files.len());
+            assert_eq!(
+                vec![v2.clone()],
+                repo.list_artifact_versions("binrep")
+                    .await
+                    .unwrap()
+                    .versions
+            );
+            assert!(repo.get_artifact("binrep", &v1).await.is_err());
+            assert!(!root.join("binrep").join("1.0.0").exists());
+            repo.get_artifact("binrep", &v2).await.unwrap();
+
+            let error = repo
+                .delete_artifact_version("binrep", &v1)
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::ResourceNotFound(_))
+            ));
+            // the version can be pushed again
+            repo.push_artifact("binrep", &v1, &["Cargo.toml"])
+                .await
+                .unwrap();
+
+            repo.set_channel("binrep", "stable", &v1).await.unwrap();
+            let error = repo
+                .delete_artifact_version("binrep", &v1)
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::ChannelTarget { .. })
+            ));
+            repo.get_artifact("binrep", &v1).await.unwrap();
+            repo.set_channel("binrep", "stable", &v2).await.unwrap();
+            repo.delete_artifact_version("binrep", &v1).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn mirror() {
+        let mut source =
+            super::Repository::<NOOPProgress>::new(Config::create_file_test_config()).unwrap();
+        let mut destination_config = Config::create_file_test_config();
+        destination_config.backend.layout = Some(crate::config::RepositoryLayout::Flat);
+        let mut destination = super::Repository::<NOOPProgress>::new(destination_config).unwrap();
+        let v1 = Version::new(1, 0, 0);
+        let v2 = Version::new(2, 0, 0);
+        source
+            .push_artifact("binrep", &v1, &["Cargo.toml"])
+            .await
+            .unwrap();
+        source
+            .push_artifact("binrep", &v2, &["Cargo.toml", "./src/lib.rs"])
+            .await
+            .unwrap();
+        source
+            .push_artifact("other", &v1, &["Cargo.toml"])
+            .await
+            .unwrap();
+        source.set_channel("binrep", "stable", &v1).await.unwrap();
+        // same version with other files in the destination
+        destination
+            .push_artifact("other", &v1, &["./src/lib.rs"])
+            .await
+            .unwrap();
+
+        let options = super::MirrorOptions::default();
+        let report = source.mirror_to(&mut destination, &options).await.unwrap();
+        assert_eq!(
+            vec![
+                ("binrep".to_string(), v1.clone()),
+                ("binrep".to_string(), v2.clone())
+            ],
+            report.copied
+        );
+        assert!(report.skipped.is_empty());
+        assert_eq!(1, report.failed.len());
+        assert_eq!("other", report.failed[0].artifact_name);
+        assert_eq!(Some(v1.clone()), report.failed[0].version);
+
+        // signatures and timestamps are kept
+        assert_eq!(
+            source.get_artifact("binrep", &v2).await.unwrap(),
+            destination.get_artifact("binrep", &v2).await.unwrap()
+        );
+        let pull_dir = tempfile::tempdir().unwrap();
+        destination
+            .pull_artifact("binrep", &v2, pull_dir.path(), false)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            vec![("stable".to_string(), v1.clone())],
+            destination.list_channels("binrep").await.unwrap()
+        );
+
+        // moved channels are followed
+        source.set_channel("binrep", "stable", &v2).await.unwrap();
+        let report = source.mirror_to(&mut destination, &options).await.unwrap();
+        assert!(report.copied.is_empty());
+        assert_eq!(2, report.skipped.len());
+        assert_eq!(1, report.failed.len());
+        assert_eq!(
+            v2,
+            destination.get_channel("binrep", "stable").await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn promote() {
+        let mut source =
+            super::Repository::<NOOPProgress>::new(Config::create_file_test_config()).unwrap();
+        let mut destination =
+            super::Repository::<NOOPProgress>::new(Config::create_file_test_config()).unwrap();
+        let v1 = Version::new(1, 0, 0);
+        let v2 = Version::new(2, 0, 0);
+        source
+            .push_artifact("binrep", &v1, &["Cargo.toml"])
+            .await
+            .unwrap();
+        source
+            .push_artifact("binrep", &v2, &["Cargo.toml", "./src/lib.rs"])
+            .await
+            .unwrap();
+        source.set_channel("binrep", "beta", &v2).await.unwrap();
+        source.set_channel("binrep", "stable", &v1).await.unwrap();
+
+        assert!(source
+            .promote_to(&mut destination, "binrep", &v2)
+            .await
+            .unwrap());
+        // only the promoted version is copied, with its signature
+        assert_eq!(
+            vec![v2.clone()],
+            destination
+                .list_artifact_versions("binrep")
+                .await
+                .unwrap()
+                .versions
+        );
+        assert_eq!(
+            source.get_artifact("binrep", &v2).await.unwrap(),
+            destination.get_artifact("binrep", &v2).await.unwrap()
+        );
+        // with the channels pointing to it
+        assert_eq!(
+            vec![("beta".to_string(), v2.clone())],
+            destination.list_channels("binrep").await.unwrap()
+        );
+        assert!(!source
+            .promote_to(&mut destination, "binrep", &v2)
+            .await
+            .unwrap());
+
+        let mut sha256_config = Config::create_file_test_config();
+        sha256_config
+            .publish_parameters
+            .as_mut()
+            .unwrap()
+            .checksum_method = ChecksumMethod::Sha256;
+        let mut sha256_destination = super::Repository::<NOOPProgress>::new(sha256_config).unwrap();
+        let error = source
+            .promote_to(&mut sha256_destination, "binrep", &v1)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::ChecksumMethodMismatch { .. })
+        ));
+        assert!(!sha256_destination
+            .artifact_exists("binrep", &v1)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn fsck() {
+        let config = Config::create_file_test_config();
+        let root = PathBuf::from(&config.backend.file_backend_opt.as_ref().unwrap().root);
+        let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+        let v1 = Version::new(1, 0, 0);
+        let v2 = Version::new(2, 0, 0);
+        repo.push_artifact("binrep", &v1, &["Cargo.toml"])
+            .await
+            .unwrap();
+        repo.push_artifact("binrep", &v2, &["Cargo.toml", "./src/lib.rs"])
+            .await
+            .unwrap();
+        repo.set_channel("binrep", "stable", &v1).await.unwrap();
+        let report = repo.fsck(false).await.unwrap();
+        assert_eq!(2, report.checked_versions);
+        assert!(report.orphaned_files.is_empty());
+        assert!(report.is_clean());
+
+        std::fs::remove_file(root.join("binrep/1.0.0/Cargo.toml")).unwrap();
+        std::fs::write(root.join("binrep/2.0.0/artifact.sane"), "not sane").unwrap();
+        std::fs::create_dir_all(root.join("binrep/3.0.0")).unwrap();
+        std::fs::write(root.join("binrep/3.0.0/leftover"), "").unwrap();
+        std::fs::write(root.join("stray.txt"), "").unwrap();
+        let report = repo.fsck(false).await.unwrap();
+        assert_eq!(1, report.checked_versions);
+        assert_eq!(1, report.invalid.len());
+        assert_eq!("binrep/2.0.0/artifact.sane", report.invalid[0].0);
+        assert_eq!(vec!["binrep/1.0.0/Cargo.toml"], report.missing_files);
+        // the files of the invalid version are not orphaned
+        assert_eq!(
+            vec!["binrep/3.0.0/leftover", "stray.txt"],
+            report.orphaned_files
+        );
+        assert!(report.removed_files.is_empty());
+        assert!(!report.is_clean());
+
+        // may be the files of a push in progress
+        let report = repo.fsck(true).await.unwrap();
+        assert!(report.removed_files.is_empty());
+        assert_eq!(report.orphaned_files, report.recent_files);
+        assert!(root.join("stray.txt").exists());
+
+        let clock = crate::clock::FixedClock::new(chrono::Utc::now());
+        clock.advance(chrono::Duration::hours(super::FSCK_GRACE_PERIOD_HOURS + 1));
+        repo.set_clock(std::sync::Arc::new(clock));
+        let report = repo.fsck(true).await.unwrap();
+        assert_eq!(report.orphaned_files, report.removed_files);
+        assert!(!root.join("stray.txt").exists());
+        assert!(!root.join("binrep/3.0.0").exists());
+        assert!(root.join("binrep/2.0.0/lib.rs").exists());
+        let report = repo.fsck(false).await.unwrap();
+        assert!(report.orphaned_files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fsck_unknown_modification_time() {
+        let config = Config::create_file_test_config();
+        let root = config
+            .backend
+            .file_backend_opt
+            .as_ref()
+            .unwrap()
+            .root
+            .clone();
+        let backend = RecordingBackend::new(FileBackend::<NOOPProgress>::new(&root))
+            .without_modification_times();
+        let mut repo = super::Repository::with_backend(config, Box::new(backend));
+        repo.push_artifact("binrep", &Version::new(1, 0, 0), &["Cargo.toml"])
+            .await
+            .unwrap();
+        std::fs::write(PathBuf::from(&root).join("stray.txt"), "").unwrap();
+        let clock = crate::clock::FixedClock::new(chrono::Utc::now());
+        clock.advance(chrono::Duration::hours(super::FSCK_GRACE_PERIOD_HOURS + 1));
+        repo.set_clock(std::sync::Arc::new(clock));
+
+        // never old enough to be deleted
+        let report = repo.fsck(true).await.unwrap();
+        assert_eq!(vec!["stray.txt"], report.orphaned_files);
+        assert_eq!(report.orphaned_files, report.recent_files);
+        assert!(report.removed_files.is_empty());
+        assert!(PathBuf::from(&root).join("stray.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn integration_test_flat_layout() {
+        let mut config = Config::create_file_test_config();
+        config.backend.layout = Some(crate::config::RepositoryLayout::Flat);
+        let root = config
+            .backend
+            .file_backend_opt
+            .as_ref()
+            .unwrap()
+            .root
+            .clone();
+        let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+        let v1 = Version::new(1, 0, 0);
+        let v2 = Version::new(2, 0, 0);
+        repo.push_artifact("binrep", &v1, &["Cargo.toml"])
+            .await
+            .unwrap();
+        repo.push_artifact("binrep", &v2, &["Cargo.toml", "./src/lib.rs"])
+            .await
+            .unwrap();
+        assert!(repo
+            .push_artifact("binrep", &v2, &["Cargo.toml"])
+            .await
+            .is_err());
+        // name colliding with the artifacts list
+        assert!(repo
+            .push_artifact("artifacts", &v1, &["Cargo.toml"])
+            .await
+            .is_err());
+
+        assert_eq!(
+            vec!["binrep".to_string()],
+            repo.list_artifacts().await.unwrap().artifacts
+        );
+        assert_eq!(
+            vec![v1.clone(), v2.clone()],
+            repo.list_artifact_versions("binrep")
+                .await
+                .unwrap()
+                .versions
+        );
+        assert_eq!(
+            2,
+            repo.get_artifact("binrep", &v2).await.unwrap().files.len()
+        );
+        assert!(repo
+            .get_artifact("binrep", &Version::new(3, 0, 0))
+            .await
+            .is_err());
+
+        let pull_dir = tempfile::tempdir().unwrap();
+        repo.pull_artifact("binrep", &v2, pull_dir.path(), false)
+            .await
+            .unwrap();
+
+        // a single metadata object per artifact
+        let root = std::path::PathBuf::from(root);
+        assert!(root.join("binrep.sane").is_file());
+        assert!(!root.join("binrep").join("versions.sane").exists());
+        assert!(!root
+            .join("binrep")
+            .join("1.0.0")
+            .join("artifact.sane")
+            .exists());
+        let report = repo.fsck(false).await.unwrap();
+        assert_eq!(2, report.checked_versions);
+        assert!(report.is_clean());
+    }
+
+    #[tokio::test]
+    async fn unix_mode_is_signed() {
+        let config = Config::create_file_test_config();
+        let root = PathBuf::from(&config.backend.file_backend_opt.as_ref().unwrap().root);
+        let mut repo = super::Repository::<NOOPProgress>::new(config).unwrap();
+        let version = Version::new(1, 0, 0);
+        repo.push_artifact("binrep", &version, &["Cargo.toml"])
+            .await
+            .unwrap();
+
+        let metadata = root.join("binrep").join("1.0.0").join("artifact.sane");
+        let mut artifact: crate::metadata::Artifact =
+            sane::from_str(&std::fs::read_to_string(&metadata).unwrap()).unwrap();
+        artifact.files[0].unix_mode = Some(0o4755);
+        std::fs::write(&metadata, sane::to_string(&artifact).unwrap()).unwrap();
+
+        let pull_dir = tempfile::tempdir().unwrap();
+        let error = repo
+            .pull_artifact("binrep", &version, pull_dir.path(), false)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast::<RepositoryError>().unwrap(),
+            RepositoryError::WrongArtifactSignature
+        ));
+    }
 }
@@ -0,0 +1,51 @@
+//! Push and pull against a local MinIO server, run with `cargo test --features minio-tests`
+//!
+//! The server and the bucket must exist, eg.
+//! `docker run -p 9000:9000 minio/minio server /data` then `mc mb local/binrep-test`, and the
+//! credentials be configured in the `minio` profile of the aws credentials file. The endpoint
+//! and bucket default to http://localhost:9000 and binrep-test, override them with
+//! BINREP_TEST_MINIO_ENDPOINT and BINREP_TEST_MINIO_BUCKET.
+#![cfg(feature = "minio-tests")]
+use binrep_core::binrep::Binrep;
+use binrep_core::config::{Backend, Config};
+use binrep_core::progress::NOOPProgress;
+use semver::Version;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn minio_config() -> Config {
+    let endpoint = std::env::var("BINREP_TEST_MINIO_ENDPOINT")
+        .unwrap_or_else(|_| "http://localhost:9000".to_string());
+    let bucket =
+        std::env::var("BINREP_TEST_MINIO_BUCKET").unwrap_or_else(|_| "binrep-test".to_string());
+    let mut config = Config::create_file_test_config_ed25519_publish();
+    config.backend = format!(
+        "s3://{}/binrep?region=minio&endpoint={}&path_style=true&profile=minio",
+        bucket, endpoint
+    )
+    .parse::<Backend>()
+    .unwrap();
+    config
+}
+
+#[tokio::test]
+async fn push_pull() {
+    let mut binrep = Binrep::<NOOPProgress>::from_config(minio_config()).unwrap();
+    // a new version on each run as pushed versions cannot be overwritten
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let version = Version::new(1, 0, now.as_secs());
+    let pushed = binrep
+        .push("minio", &version, &["Cargo.toml"])
+        .await
+        .unwrap();
+
+    let tmp = tempfile::tempdir().unwrap();
+    let pulled = binrep.pull("minio", &version, &tmp, true).await.unwrap();
+    assert_eq!(pushed.files, pulled.files);
+    assert_eq!(
+        std::fs::read("Cargo.toml").unwrap(),
+        std::fs::read(tmp.path().join("Cargo.toml")).unwrap()
+    );
+
+    binrep.delete("minio", &version).await.unwrap();
+    assert!(binrep.pull("minio", &version, &tmp, true).await.is_err());
+}
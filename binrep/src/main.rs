@@ -1,58 +1,309 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 use anyhow::Error;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
-use binrep_core::binrep::{parse_version_req, resolve_config};
-use binrep_core::binrep::{Binrep, SyncStatus};
-use binrep_core::exec::exec;
+use binrep_core::binrep::{
+    exec_after_sync, parse_version_req, parse_version_selector, read_sync_metadata, resolve_config,
+    resolve_config_with_path, validate_config,
+};
+use binrep_core::binrep::{
+    Binrep, FileFilter, LocalFileStatus, MirrorOptions, PruneRetention, PullOptions, PushOptions,
+    SyncOptions, SyncStatus,
+};
+use binrep_core::chrono::{DateTime, NaiveDate, Utc};
+use binrep_core::config::{Backend, Config};
+use binrep_core::exec::{exec, ExecOptions};
 use binrep_core::metadata::Artifact;
-use binrep_core::progress::InteractiveProgressReporter;
+use binrep_core::progress::DynProgressReporter;
 use binrep_core::semver::{Version, VersionReq};
 use binrep_core::slack::{SlackConfig, WebhookConfig};
 use binrep_core::slack_hook3::{AttachmentBuilder, PayloadBuilder};
 use ring::signature::KeyPair;
+use serde::Serialize;
 use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
 
 #[derive(StructOpt)]
 struct PullOpt {
     /// Command to execute after the artifact has been successfully pulled
     #[structopt(short = "e", long = "exec")]
     exec_command: Option<String>,
+    /// Kill the command if still running after this number of seconds
+    #[structopt(long = "exec-timeout")]
+    exec_timeout: Option<u64>,
+    /// Only pull the files matching one of these glob patterns (eg: '*.bin'), can be repeated
+    #[structopt(long = "include", number_of_values = 1)]
+    includes: Vec<String>,
+    /// Do not pull the files matching these glob patterns (eg: 'docs/*'), can be repeated
+    #[structopt(long = "exclude", number_of_values = 1)]
+    excludes: Vec<String>,
     artifact_name: String,
+    /// Exact version or version requirement (eg: 1.2.3, ~1, latest), the latest version
+    /// matching a requirement is pulled
     version: String,
     #[structopt(parse(from_os_str))]
     destination_dir: PathBuf,
 }
 
+/// Output format of the commands supporting `--output`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(anyhow::anyhow!(
+                "unknown output format {}, expected text or json",
+                s
+            )),
+        }
+    }
+}
+
+/// JSON output of the sync command
+#[derive(Serialize)]
+struct SyncOutput<'a> {
+    status: &'a SyncStatus,
+    artifact_name: &'a str,
+    from_version: Option<&'a Version>,
+    to_version: &'a Version,
+    /// names of the files of the synced version
+    files: Vec<&'a str>,
+    dry_run: bool,
+}
+
+/// JSON output of `ls` listing versions with their push time
+#[derive(Serialize)]
+struct VersionOutput<'a> {
+    version: &'a Version,
+    pushed_at: Option<&'a DateTime<Utc>>,
+}
+
 #[derive(StructOpt)]
 struct SyncOpt {
     /// Command to execute if the artifact has been updated (a new version has been pulled)
     #[structopt(short = "e", long = "exec")]
     exec_command: Option<String>,
+    /// Kill the command if still running after this number of seconds
+    #[structopt(long = "exec-timeout")]
+    exec_timeout: Option<u64>,
+    /// Restore the previously installed version if the command fails
+    #[structopt(long = "rollback-on-exec-failure")]
+    rollback_on_exec_failure: bool,
+    /// Archive the files of the replaced version in the destination directory, keeping this
+    /// number of versions for `binrep rollback` (at most the sync `history_size`)
+    #[structopt(long = "keep-history", default_value = "0")]
+    keep_history: usize,
+    /// Pull and install the latest matching version even if already in sync, ignoring a broken
+    /// sync metadata
+    #[structopt(short = "f", long = "force", alias = "force-download")]
+    force: bool,
+    /// Only print whether the artifact would be updated: nothing is pulled nor executed
+    #[structopt(short = "n", long = "dry-run")]
+    dry_run: bool,
     artifact_name: String,
-    /// Version requirement (eg: *, 1.x, ^1.0.0, ~1, latest)
+    /// Version requirement (eg: *, 1.x, ^1.0.0, ~1, latest) or channel (eg: @stable)
     version_req: String,
     #[structopt(parse(from_os_str))]
     destination_dir: PathBuf,
 }
 
+#[derive(StructOpt)]
+struct WaitOpt {
+    /// Maximum time to wait in seconds, exit with an error when elapsed
+    #[structopt(short = "t", long = "timeout", default_value = "300")]
+    timeout: u64,
+    /// Initial delay between two checks in seconds, at least 1, doubled after each check
+    #[structopt(short = "i", long = "interval", default_value = "5")]
+    interval: u64,
+    artifact_name: String,
+    /// Version requirement (eg: *, 1.x, ^1.0.0, ~1, latest)
+    version_req: String,
+}
+
+#[derive(StructOpt)]
+struct ResolveOpt {
+    artifact_name: String,
+    /// Version requirement (eg: *, 1.x, ^1.0.0, ~1, latest) or channel (eg: @stable)
+    version_req: String,
+}
+
+#[derive(StructOpt)]
+struct ChannelOpt {
+    artifact_name: String,
+    /// Channel name (eg: stable, beta)
+    channel: String,
+    version: String,
+}
+
+#[derive(StructOpt)]
+struct ResignOpt {
+    /// Signing key to use, default to the signing key of the publish parameters
+    #[structopt(short = "k", long = "key")]
+    key: Option<String>,
+}
+
+#[derive(StructOpt)]
+struct DeleteOpt {
+    artifact_name: String,
+    version: String,
+}
+
+#[derive(StructOpt)]
+struct VerifyOpt {
+    artifact_name: String,
+    version: String,
+    /// Directory where the artifact was pulled or synced
+    #[structopt(parse(from_os_str))]
+    directory: PathBuf,
+}
+
+#[derive(StructOpt)]
+struct PruneOpt {
+    /// Number of most recent versions to keep (semver ordering), at least 1
+    #[structopt(short = "k", long = "keep", required_unless = "keep-within")]
+    keep: Option<usize>,
+    /// Keep the versions pushed within this duration instead (eg. 30d, 12h), versions
+    /// without push timestamp are kept
+    #[structopt(long = "keep-within", conflicts_with = "keep", parse(try_from_str = humantime::parse_duration))]
+    keep_within: Option<Duration>,
+    /// Print the versions that would be deleted without deleting them
+    #[structopt(long = "dry-run")]
+    dry_run: bool,
+    artifact_name: String,
+}
+
+#[derive(StructOpt)]
+struct MirrorOpt {
+    /// Configuration file of the destination repository
+    #[structopt(long = "to", parse(from_os_str))]
+    to: PathBuf,
+    /// Copy artifacts without verifying their signature
+    #[structopt(long = "no-verify")]
+    no_verify: bool,
+}
+
+#[derive(StructOpt)]
+struct FsckOpt {
+    /// Delete the files referenced by no artifact, except the ones modified in the last 24 hours
+    /// (eg. a push in progress)
+    #[structopt(long = "repair")]
+    repair: bool,
+}
+
+#[derive(StructOpt)]
+struct PromoteOpt {
+    /// Configuration file of the destination repository
+    #[structopt(long = "to", parse(from_os_str))]
+    to: PathBuf,
+    artifact_name: String,
+    /// Exact version or version requirement (eg: 1.2.3, ~1, latest)
+    version: String,
+}
+
 #[derive(StructOpt)]
 struct PushOpt {
+    /// S3 storage class of the pushed files (eg. STANDARD_IA, GLACIER_IR), override the
+    /// backend configuration
+    #[structopt(long = "storage-class")]
+    storage_class: Option<String>,
+    /// Release notes file, a truncated snippet is attached to the slack notification
+    #[structopt(long = "release-notes", parse(from_os_str))]
+    release_notes: Option<PathBuf>,
+    /// Log file (eg. build log) to excerpt in the slack notification
+    #[structopt(long = "attach-file", parse(from_os_str))]
+    attach_file: Option<PathBuf>,
+    /// Number of lines to include from the start of the attached file
+    #[structopt(long = "attach-head")]
+    attach_head: Option<usize>,
+    /// Number of lines to include from the end of the attached file (default to 20 if
+    /// --attach-head is not provided)
+    #[structopt(long = "attach-tail")]
+    attach_tail: Option<usize>,
+    /// Label of the artifact (eg. --label branch=main), can be repeated
+    #[structopt(short = "l", long = "label", number_of_values = 1, parse(try_from_str = parse_label))]
+    labels: Vec<(String, String)>,
+    /// Replace the version if it already exists, eg. a development version rebuilt again
+    #[structopt(short = "f", long = "force")]
+    force: bool,
     artifact_name: String,
+    /// Version to push, or auto, auto-minor, auto-major to bump the last version
     version: String,
+    /// Push the standard input as the only file of the artifact, named after this argument
+    /// (eg. a tarball piped from a build), instead of the given files
+    #[structopt(long = "stdin", conflicts_with = "files")]
+    stdin: Option<String>,
     #[structopt(parse(from_os_str))]
     files: Vec<PathBuf>,
 }
 #[derive(StructOpt)]
 struct InspectOpt {
+    /// Only print files, one per line: name<TAB>checksum<TAB>mode (octal, - if unknown), or as
+    /// a json array with `--output json`
+    #[structopt(long = "files", alias = "list-files")]
+    files: bool,
     artifact_name: String,
     version: String,
 }
 
+#[derive(StructOpt)]
+struct FilesOpt {
+    artifact_name: String,
+    /// Exact version, version requirement (eg: ~1, latest) or channel (eg: @stable)
+    version: String,
+}
+
+#[derive(StructOpt)]
+struct CatOpt {
+    artifact_name: String,
+    /// Exact version, version requirement (eg: ~1, latest) or channel (eg: @stable)
+    version: String,
+    /// Name of the file in the artifact
+    file_name: String,
+}
+
+#[derive(StructOpt)]
+struct HistoryOpt {
+    artifact_name: String,
+    /// Directory the artifact is synced to
+    #[structopt(parse(from_os_str))]
+    destination_dir: PathBuf,
+}
+
+#[derive(StructOpt)]
+struct RollbackOpt {
+    artifact_name: String,
+    /// Directory the artifact is synced to
+    #[structopt(parse(from_os_str))]
+    destination_dir: PathBuf,
+    /// Version archived by a previous `sync --keep-history`, see `binrep history`
+    version: String,
+}
+
 #[derive(StructOpt)]
 struct ListOpt {
+    /// Only list versions having this label (eg. --label branch=main), can be repeated
+    #[structopt(short = "l", long = "label", number_of_values = 1, parse(try_from_str = parse_label))]
+    labels: Vec<(String, String)>,
+    /// Only list versions pushed at or after this date (eg. 2024-01-01 or an RFC 3339 timestamp)
+    #[structopt(long = "since", parse(try_from_str = parse_date))]
+    since: Option<DateTime<Utc>>,
+    /// Only list versions pushed before this date (eg. 2024-02-01 or an RFC 3339 timestamp)
+    #[structopt(long = "until", parse(try_from_str = parse_date))]
+    until: Option<DateTime<Utc>>,
+    /// Sort the versions by push date instead of version
+    #[structopt(long = "by-date")]
+    by_date: bool,
     /// artifact name
     artifact_name: Option<String>,
     /// artifact version requirement
@@ -65,6 +316,20 @@ enum UtilsOpt {
     GenerateED25519KeyPar,
 }
 
+#[derive(StructOpt)]
+enum ConfigOpt {
+    /// Print the path of the configuration file in use and its content, secrets redacted
+    #[structopt(name = "show")]
+    Show,
+    /// Print the path of the configuration file in use
+    #[structopt(name = "path")]
+    Path,
+    /// Check the keys, the publish parameters and the backend of the configuration file, without
+    /// connecting to the backend
+    #[structopt(name = "validate")]
+    Validate,
+}
+
 #[derive(StructOpt)]
 enum Command {
     #[structopt(name = "push")]
@@ -77,15 +342,79 @@ enum Command {
     Sync(SyncOpt),
     #[structopt(name = "inspect")]
     Inspect(InspectOpt),
+    /// Print the files of an artifact, without downloading them: name<TAB>size<TAB>checksum
+    /// method<TAB>mode (- if unknown)
+    #[structopt(name = "files")]
+    Files(FilesOpt),
+    /// Write a file of an artifact to the standard output, once its checksum is verified
+    #[structopt(name = "cat")]
+    Cat(CatOpt),
+    /// Print the versions previously synced in a directory
+    #[structopt(name = "history")]
+    History(HistoryOpt),
+    /// Install again a version archived in a directory by `sync --keep-history`
+    #[structopt(name = "rollback")]
+    Rollback(RollbackOpt),
+    /// Wait until a version matching the requirement is published
+    #[structopt(name = "wait")]
+    Wait(WaitOpt),
+    /// Print the version a sync would select, without pulling anything
+    #[structopt(name = "resolve")]
+    Resolve(ResolveOpt),
+    /// Point a channel of an artifact to a version, synced with `@channel`
+    #[structopt(name = "channel")]
+    Channel(ChannelOpt),
+    /// Re-sign all artifacts of the repository (key rotation)
+    #[structopt(name = "resign")]
+    Resign(ResignOpt),
+    /// Delete a version of an artifact with its files
+    #[structopt(name = "delete")]
+    Delete(DeleteOpt),
+    /// Check the files of a pulled artifact against its checksums and signature
+    #[structopt(name = "verify")]
+    Verify(VerifyOpt),
+    /// Delete old versions of an artifact
+    #[structopt(name = "prune")]
+    Prune(PruneOpt),
+    /// Copy all artifacts missing from another repository (eg. disaster recovery)
+    #[structopt(name = "mirror")]
+    Mirror(MirrorOpt),
+    /// Copy an artifact version to another repository, keeping its signature (eg. from staging
+    /// to production)
+    #[structopt(name = "promote")]
+    Promote(PromoteOpt),
+    /// Check the signature of every artifact version, their files and report orphaned files
+    #[structopt(name = "fsck")]
+    Fsck(FsckOpt),
     #[structopt(name = "utils")]
     Utils(UtilsOpt),
+    /// Print the configuration file in use
+    #[structopt(name = "config")]
+    Config(ConfigOpt),
 }
 
 #[derive(StructOpt)]
 struct Opt {
-    /// Configuration file, if not specified, default to ~/.binrep/config.sane and /etc/binrep/config.sane
+    /// Configuration file, if not specified, default to $BINREP_CONFIG,
+    /// $XDG_CONFIG_HOME/binrep/config.sane, ~/.binrep/config.sane and /etc/binrep/config.sane
     #[structopt(short = "c", long = "config", parse(from_os_str))]
     config_file: Option<PathBuf>,
+    /// Backend url overriding the configured backend, eg. file:/srv/repo or
+    /// s3://bucket/prefix?region=eu-west-1 (keys still come from the configuration file)
+    #[structopt(short = "b", long = "backend", alias = "repo-url")]
+    backend: Option<Backend>,
+    /// Do not report the progress of downloads and uploads (eg. in cron jobs)
+    #[structopt(short = "q", long = "quiet", alias = "no-progress")]
+    quiet: bool,
+    /// Output format of ls (an array of artifact names or versions), inspect (the artifact
+    /// metadata), files and sync ({status, artifact_name, from_version, to_version, dry_run}):
+    /// text or json
+    #[structopt(short = "o", long = "output", default_value = "text", global = true)]
+    output: OutputFormat,
+    /// Print the spans of push, pull, sync and backend operations with their timing to stderr
+    #[cfg(feature = "tracing")]
+    #[structopt(long = "trace")]
+    trace: bool,
     #[structopt(subcommand)]
     command: Command,
 }
@@ -93,6 +422,15 @@ struct Opt {
 async fn main() {
     env_logger::init();
     let opt = Opt::from_args();
+    #[cfg(feature = "tracing")]
+    if opt.trace {
+        use std::io::IsTerminal;
+        tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .with_ansi(std::io::stderr().is_terminal())
+            .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+            .init();
+    }
     if let Err(e) = _main(opt).await {
         eprintln!("{} - {:?}", e, e);
         std::process::exit(1);
@@ -100,51 +438,171 @@ async fn main() {
 }
 
 async fn _main(opt: Opt) -> Result<(), Error> {
-    // If BINREP_CONFIG environment variable is provided, use it!
-    let env_config = std::env::var("BINREP_CONFIG");
-    let provided_config = match env_config {
-        Ok(cfg) => Some(PathBuf::from(cfg)),
-        Err(_) => opt.config_file.clone(),
-    };
+    // BINREP_CONFIG environment variable, if provided, is used instead
+    let provided_config = opt.config_file.clone();
 
     let slack_configuration: SlackConfig = resolve_config(&provided_config)?;
-    let mut binrep = Binrep::<InteractiveProgressReporter>::new(&provided_config)?;
+    let (config_path, mut config): (PathBuf, Config) = resolve_config_with_path(&provided_config)?;
+    // the command line takes precedence over the environment
+    config.apply_env_overrides();
+    if let Some(mut backend) = opt.backend.clone() {
+        backend.max_metadata_size = config.backend.max_metadata_size;
+        config.backend = backend;
+    }
+    if let Command::Config(config_opt) = &opt.command {
+        println!("{}", config_path.display());
+        match config_opt {
+            ConfigOpt::Show => println!("{}", serde_json::to_string_pretty(&config.redacted())?),
+            ConfigOpt::Path => {}
+            ConfigOpt::Validate => {
+                let errors = validate_config(&config).await;
+                if !errors.is_empty() {
+                    for e in &errors {
+                        println!("  - {:#}", e);
+                    }
+                    Err(anyhow::anyhow!(
+                        "{} problem(s) found in {}",
+                        errors.len(),
+                        config_path.display()
+                    ))?;
+                }
+                if config.publish_parameters.is_none() {
+                    println!("No publish parameters: artifacts can only be pulled");
+                }
+                println!("Configuration is valid");
+            }
+        }
+        return Ok(());
+    }
+    if let Command::Push(PushOpt {
+        storage_class: Some(storage_class),
+        ..
+    }) = &opt.command
+    {
+        match &mut config.backend.s3_backend_opt {
+            Some(s3) => s3.storage_class = Some(storage_class.clone()),
+            None => Err(anyhow::anyhow!("--storage-class requires a S3 backend"))?,
+        }
+    }
+    // progress would be mixed with the file content or the json output
+    DynProgressReporter::set_silent(
+        opt.quiet || opt.output == OutputFormat::Json || matches!(opt.command, Command::Cat(_)),
+    );
+    let mut binrep = Binrep::<DynProgressReporter>::from_overridden_config(config)?;
+    let output = opt.output;
     match opt.command {
         // LIST----------
         Command::List(opt) => match opt.artifact_name {
-            None => print_list(binrep.list_artifacts().await?.artifacts),
+            None if !opt.labels.is_empty() => {
+                Err(anyhow::anyhow!("--label requires an artifact name"))?
+            }
+            None if opt.since.is_some() || opt.until.is_some() || opt.by_date => Err(
+                anyhow::anyhow!("--since, --until and --by-date require an artifact name"),
+            )?,
+            None => print_list(binrep.list_artifacts().await?.artifacts, output)?,
+            // push dates are read from the metadata of every version
+            Some(artifact_name) if opt.since.is_some() || opt.until.is_some() || opt.by_date => {
+                let (since, until) = (opt.since, opt.until);
+                let pushed_within = |pushed_at: &Option<DateTime<Utc>>| match pushed_at {
+                    Some(pushed_at) => {
+                        since.is_none_or(|since| *pushed_at >= since)
+                            && until.is_none_or(|until| *pushed_at < until)
+                    }
+                    None => since.is_none() && until.is_none(),
+                };
+                let mut versions: Vec<_> = binrep
+                    .list_artifact_versions_detailed(
+                        &artifact_name,
+                        &parse_optional_version_req(opt.version_req)?,
+                        &opt.labels.into_iter().collect(),
+                    )
+                    .await?
+                    .into_iter()
+                    .filter(|(_, pushed_at)| pushed_within(pushed_at))
+                    .collect();
+                if opt.by_date {
+                    versions.sort_by(|(v1, d1), (v2, d2)| (d1, v1).cmp(&(d2, v2)));
+                } else {
+                    versions.sort_by(|(v1, _), (v2, _)| v1.cmp(v2));
+                }
+                match output {
+                    OutputFormat::Text => {
+                        for (version, pushed_at) in versions {
+                            println!(
+                                "{} {}",
+                                version,
+                                pushed_at.map_or("-".to_string(), |d| d.to_rfc3339())
+                            );
+                        }
+                    }
+                    OutputFormat::Json => {
+                        let versions: Vec<_> = versions
+                            .iter()
+                            .map(|(version, pushed_at)| VersionOutput {
+                                version,
+                                pushed_at: pushed_at.as_ref(),
+                            })
+                            .collect();
+                        println!("{}", serde_json::to_string(&versions)?);
+                    }
+                }
+            }
             Some(artifact_name) => print_list(
                 binrep
-                    .list_artifact_versions(
+                    .list_artifact_versions_with_labels(
                         &artifact_name,
                         &parse_optional_version_req(opt.version_req)?,
+                        &opt.labels.into_iter().collect(),
                     )
                     .await?,
-            ),
+                output,
+            )?,
         },
         Command::Push(opt) => {
             let artifact_name = &opt.artifact_name;
             let artifact_version = match opt.version.as_str() {
-                "auto" => binrep
-                    .last_version(artifact_name, &VersionReq::STAR)
-                    .await
-                    .or_else::<Error, _>(|e| {
-                        // ignore errors and go on with default version
-                        Ok(Some(Version::new(0, 0, 0)))
-                    })?
-                    .map(|mut v| {
-                        v.patch += 1;
-                        v
-                    })
-                    .unwrap_or(Version::new(0, 0, 1)),
+                "auto" => auto_version(&mut binrep, artifact_name, VersionBump::Patch).await,
+                "auto-minor" => auto_version(&mut binrep, artifact_name, VersionBump::Minor).await,
+                "auto-major" => auto_version(&mut binrep, artifact_name, VersionBump::Major).await,
                 v => Version::parse(v)?,
             };
-            let artifact_files = opt.files;
+            // read notification attachments before pushing anything
+            let release_notes = opt
+                .release_notes
+                .as_ref()
+                .map(|notes| release_notes_snippet(notes))
+                .transpose()?;
+            let log_excerpt = opt
+                .attach_file
+                .as_ref()
+                .map(|file| log_excerpt(file, opt.attach_head, opt.attach_tail))
+                .transpose()?;
+            // the standard input is buffered in a temporary file, removed once pushed
+            let (_stdin_dir, artifact_files) = match &opt.stdin {
+                Some(file_name) => {
+                    let stdin_dir = tempfile::tempdir()?;
+                    let file = read_stdin_to_file(stdin_dir.path(), file_name)?;
+                    (Some(stdin_dir), vec![file])
+                }
+                None => (None, opt.files),
+            };
+            let options = PushOptions {
+                labels: opt.labels.into_iter().collect(),
+                force: opt.force,
+            };
             let pushed = binrep
-                .push(artifact_name, &artifact_version, &artifact_files)
+                .push_with_options(artifact_name, &artifact_version, &artifact_files, &options)
                 .await?;
             println!("Pushed {} {}", artifact_name, pushed);
-            match send_slack_push_notif(&slack_configuration.into(), artifact_name, &pushed).await {
+            match send_slack_push_notif(
+                &slack_configuration.into(),
+                artifact_name,
+                &pushed,
+                release_notes.as_deref(),
+                log_excerpt.as_deref(),
+            )
+            .await
+            {
                 Ok(sent) => {
                     if sent {
                         println!("Slack notification sent.");
@@ -155,41 +613,357 @@ async fn _main(opt: Opt) -> Result<(), Error> {
         }
         Command::Pull(opt) => {
             let artifact_name = &opt.artifact_name;
-            let artifact_version = Version::parse(&opt.version)?;
             let destination_dir = opt.destination_dir;
-            let pulled = binrep
-                .pull(artifact_name, &artifact_version, &destination_dir, true)
-                .await?;
+            let pull_options = PullOptions {
+                overwrite_dest: true,
+                file_filter: FileFilter::new(opt.includes, opt.excludes)?,
+                ..Default::default()
+            };
+            let pulled = match Version::parse(&opt.version) {
+                Ok(artifact_version) => {
+                    binrep
+                        .pull_with_options(
+                            artifact_name,
+                            &artifact_version,
+                            &destination_dir,
+                            &pull_options,
+                        )
+                        .await?
+                }
+                Err(_) => {
+                    binrep
+                        .pull_latest_matching_with_options(
+                            artifact_name,
+                            &parse_version_req(&opt.version)?,
+                            &destination_dir,
+                            &pull_options,
+                        )
+                        .await?
+                }
+            };
             println!("Pulled {} {}", artifact_name, pulled);
-            exec(&pulled, &destination_dir, &opt.exec_command)?;
+            exec(
+                artifact_name,
+                &pulled,
+                &destination_dir,
+                &opt.exec_command,
+                &ExecOptions {
+                    timeout: opt.exec_timeout.map(Duration::from_secs),
+                    ..Default::default()
+                },
+            )?;
         }
         Command::Sync(opt) => {
             let artifact_name = &opt.artifact_name;
-            let version_req = parse_version_req(&opt.version_req)?;
+            let version_req = parse_version_selector(&opt.version_req)?;
             let destination_dir = opt.destination_dir;
+            let options = SyncOptions {
+                force: opt.force,
+                dry_run: opt.dry_run,
+                rollback_on_exec_failure: opt.rollback_on_exec_failure,
+                keep_history: opt.keep_history,
+                ..Default::default()
+            };
             let sync = binrep
-                .sync(artifact_name, &version_req, &destination_dir)
+                .sync_with_options(artifact_name, &version_req, &destination_dir, &options)
                 .await?;
-            let print_output = opt.exec_command.is_none();
-            match sync.status {
-                SyncStatus::UpToDate => {
-                    if print_output {
-                        println!("Nothing pulled, files are in sync");
-                    }
+            match output {
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string(&SyncOutput {
+                        status: &sync.status,
+                        artifact_name,
+                        from_version: sync.previous_version.as_ref(),
+                        to_version: &sync.artifact.version,
+                        files: sync
+                            .artifact
+                            .files
+                            .iter()
+                            .map(|file| file.name.as_str())
+                            .collect(),
+                        dry_run: sync.dry_run,
+                    })?
+                ),
+                OutputFormat::Text if opt.exec_command.is_none() || sync.dry_run => {
+                    println!("{}", sync)
                 }
-                SyncStatus::Updated => {
-                    if print_output {
-                        println!("Updated {} to {}", artifact_name, sync.artifact);
-                    }
-                    exec(&sync.artifact, &destination_dir, &opt.exec_command)?;
+                OutputFormat::Text => {}
+            }
+            if sync.status == SyncStatus::Updated && !sync.dry_run {
+                exec_after_sync(
+                    &sync,
+                    &destination_dir,
+                    &opt.exec_command,
+                    &ExecOptions {
+                        timeout: opt.exec_timeout.map(Duration::from_secs),
+                        ..Default::default()
+                    },
+                )?;
+            }
+        }
+        Command::Files(opt) => {
+            let artifact_name = &opt.artifact_name;
+            let artifact_version =
+                resolve_version(&mut binrep, artifact_name, &opt.version).await?;
+            let artifact = binrep.artifact(artifact_name, &artifact_version).await?;
+            if output == OutputFormat::Json {
+                println!("{}", serde_json::to_string(&artifact.files)?);
+            } else {
+                for file in &artifact.files {
+                    let size = file
+                        .size
+                        .map(|size| size.to_string())
+                        .unwrap_or_else(|| "-".to_string());
+                    let mode = file
+                        .unix_mode
+                        .map(|mode| format!("{:o}", mode))
+                        .unwrap_or_else(|| "-".to_string());
+                    println!(
+                        "{}\t{}\t{}\t{}",
+                        file.name, size, file.checksum_method, mode
+                    );
                 }
             }
         }
+        Command::Cat(opt) => {
+            let artifact_name = &opt.artifact_name;
+            let artifact_version =
+                resolve_version(&mut binrep, artifact_name, &opt.version).await?;
+            binrep
+                .get_file(
+                    artifact_name,
+                    &artifact_version,
+                    &opt.file_name,
+                    &mut std::io::stdout().lock(),
+                )
+                .await?;
+        }
         Command::Inspect(opt) => {
             let artifact_name = &opt.artifact_name;
             let artifact_version = Version::parse(&opt.version)?;
             let artifact = binrep.artifact(artifact_name, &artifact_version).await?;
-            println!("{} {}", artifact_name, artifact);
+            if opt.files && output == OutputFormat::Json {
+                println!("{}", serde_json::to_string(&artifact.files)?);
+            } else if output == OutputFormat::Json {
+                println!("{}", serde_json::to_string(&artifact)?);
+            } else if opt.files {
+                for file in &artifact.files {
+                    let mode = file
+                        .unix_mode
+                        .map(|mode| format!("{:o}", mode))
+                        .unwrap_or_else(|| "-".to_string());
+                    println!("{}\t{}\t{}", file.name, file.checksum, mode);
+                }
+            } else {
+                println!("{} {}", artifact_name, artifact);
+            }
+        }
+        Command::History(opt) => {
+            match read_sync_metadata(&opt.artifact_name, &opt.destination_dir)? {
+                None => println!(
+                    "{} has never been synced to {}",
+                    opt.artifact_name,
+                    opt.destination_dir.to_string_lossy()
+                ),
+                Some(meta) => {
+                    println!("{} {} (current)", meta.last_updated, meta.artifact.version);
+                    for entry in meta.history {
+                        if entry.archived {
+                            println!("{} {} (archived)", entry.last_updated, entry.version);
+                        } else {
+                            println!("{} {}", entry.last_updated, entry.version);
+                        }
+                    }
+                }
+            }
+        }
+        Command::Rollback(opt) => {
+            let version = Version::parse(&opt.version)?;
+            let rollback = binrep
+                .rollback(&opt.artifact_name, &opt.destination_dir, &version)
+                .await?;
+            println!("{}", rollback);
+        }
+        Command::Wait(opt) => {
+            let version_req = parse_version_req(&opt.version_req)?;
+            match binrep
+                .wait_for_version(
+                    &opt.artifact_name,
+                    &version_req,
+                    Duration::from_secs(opt.timeout),
+                    Duration::from_secs(opt.interval),
+                )
+                .await?
+            {
+                Some(version) => println!("{} {}", opt.artifact_name, version),
+                None => Err(anyhow::anyhow!(
+                    "No version of {} matching {} published after {}s",
+                    opt.artifact_name,
+                    version_req,
+                    opt.timeout
+                ))?,
+            }
+        }
+        Command::Resolve(opt) => {
+            let version_req = parse_version_selector(&opt.version_req)?;
+            println!(
+                "{}",
+                binrep.resolve(&opt.artifact_name, &version_req).await?
+            );
+        }
+        Command::Resign(opt) => {
+            let report = binrep.resign_all(opt.key.as_deref()).await?;
+            for (artifact_name, version) in &report.resigned {
+                println!("Re-signed {} {}", artifact_name, version);
+            }
+            for (artifact_name, version, error) in &report.failed {
+                eprintln!("Failed {} {}: {}", artifact_name, version, error);
+            }
+            println!(
+                "{} artifact versions re-signed, {} failed",
+                report.resigned.len(),
+                report.failed.len()
+            );
+            if !report.failed.is_empty() {
+                Err(anyhow::anyhow!("resign is incomplete"))?;
+            }
+        }
+        Command::Channel(opt) => {
+            let artifact_version = Version::parse(&opt.version)?;
+            binrep
+                .set_channel(&opt.artifact_name, &opt.channel, &artifact_version)
+                .await?;
+            println!(
+                "{} @{} -> {}",
+                opt.artifact_name, opt.channel, artifact_version
+            );
+        }
+        Command::Delete(opt) => {
+            let artifact_version = Version::parse(&opt.version)?;
+            binrep.delete(&opt.artifact_name, &artifact_version).await?;
+            println!("Deleted {} {}", opt.artifact_name, artifact_version);
+        }
+        Command::Verify(opt) => {
+            let artifact_version = Version::parse(&opt.version)?;
+            let report = binrep
+                .verify_local(&opt.artifact_name, &artifact_version, &opt.directory)
+                .await?;
+            for (name, status) in &report.files {
+                match status {
+                    LocalFileStatus::Ok => println!("OK\t{}", name),
+                    LocalFileStatus::Missing => println!("MISSING\t{}", name),
+                    LocalFileStatus::ChecksumMismatch => println!("MISMATCH\t{}", name),
+                }
+            }
+            if !report.is_ok() {
+                Err(anyhow::anyhow!(
+                    "{} {} does not match its metadata",
+                    opt.artifact_name,
+                    artifact_version
+                ))?;
+            }
+        }
+        Command::Prune(opt) => {
+            let retention = match (opt.keep, opt.keep_within) {
+                (_, Some(duration)) => PruneRetention::PushedWithin(duration),
+                (keep, None) => PruneRetention::Newest(keep.unwrap_or_default()),
+            };
+            let report = binrep
+                .prune(&opt.artifact_name, &retention, opt.dry_run)
+                .await?;
+            let action = if opt.dry_run {
+                "Would delete"
+            } else {
+                "Deleted"
+            };
+            for version in &report.pruned {
+                println!("{} {} {}", action, opt.artifact_name, version);
+            }
+            for (version, error) in &report.failed {
+                eprintln!("Failed {} {}: {}", opt.artifact_name, version, error);
+            }
+            if !report.failed.is_empty() {
+                Err(anyhow::anyhow!("prune is incomplete"))?;
+            }
+        }
+        Command::Mirror(opt) => {
+            let options = MirrorOptions {
+                verify_signatures: !opt.no_verify,
+            };
+            let report = binrep
+                .mirror(Config::read_from_file(&opt.to)?, &options)
+                .await?;
+            for (artifact_name, version) in &report.copied {
+                println!("Copied {} {}", artifact_name, version);
+            }
+            for failure in &report.failed {
+                match &failure.version {
+                    Some(version) => eprintln!(
+                        "Failed {} {}: {}",
+                        failure.artifact_name, version, failure.error
+                    ),
+                    None => eprintln!("Failed {}: {}", failure.artifact_name, failure.error),
+                }
+            }
+            println!(
+                "{} copied, {} already present, {} failed",
+                report.copied.len(),
+                report.skipped.len(),
+                report.failed.len()
+            );
+            if !report.failed.is_empty() {
+                Err(anyhow::anyhow!("mirror is incomplete"))?;
+            }
+        }
+        Command::Promote(opt) => {
+            let version = resolve_version(&mut binrep, &opt.artifact_name, &opt.version).await?;
+            if binrep
+                .promote(
+                    Config::read_from_file(&opt.to)?,
+                    &opt.artifact_name,
+                    &version,
+                )
+                .await?
+            {
+                println!("Promoted {} {}", opt.artifact_name, version);
+            } else {
+                println!("{} {} already present", opt.artifact_name, version);
+            }
+        }
+        Command::Fsck(opt) => {
+            let report = binrep.fsck(opt.repair).await?;
+            for (path, error) in &report.invalid {
+                eprintln!("Invalid {}: {}", path, error);
+            }
+            for path in &report.missing_files {
+                eprintln!("Missing file {}", path);
+            }
+            for (path, error) in &report.unchecked_files {
+                eprintln!("Cannot check {}: {}", path, error);
+            }
+            for path in &report.orphaned_files {
+                if report.removed_files.contains(path) {
+                    println!("Removed orphaned file {}", path);
+                } else if report.recent_files.contains(path) {
+                    eprintln!(
+                        "Orphaned file {} kept, modified recently or modification time unknown",
+                        path
+                    );
+                } else {
+                    eprintln!("Orphaned file {}", path);
+                }
+            }
+            println!(
+                "{} versions checked, {} invalid, {} missing files, {} orphaned files ({} removed)",
+                report.checked_versions,
+                report.invalid.len(),
+                report.missing_files.len(),
+                report.orphaned_files.len(),
+                report.removed_files.len()
+            );
+            if !report.is_clean() {
+                Err(anyhow::anyhow!("the repository has problems"))?;
+            }
         }
         Command::Utils(opt) => match opt {
             UtilsOpt::GenerateED25519KeyPar => {
@@ -204,6 +978,7 @@ async fn _main(opt: Opt) -> Result<(), Error> {
                 );
             }
         },
+        Command::Config(_) => unreachable!("handled before setting up the repository"),
     }
     Ok(())
 }
@@ -215,16 +990,161 @@ pub fn parse_optional_version_req(input: Option<String>) -> Result<VersionReq, E
     })
 }
 
-fn print_list<T: Display, I: IntoIterator<Item = T>>(collection: I) {
-    for item in collection {
-        println!("{}", item);
+/// Parse a `key=value` label
+fn parse_label(label: &str) -> Result<(String, String), Error> {
+    match label.split_once('=') {
+        Some((key, value)) => Ok((key.to_string(), value.to_string())),
+        None => Err(anyhow::anyhow!(
+            "invalid label {}, expected key=value",
+            label
+        )),
+    }
+}
+
+/// Copy the standard input to the `file_name` file of `dir`
+fn read_stdin_to_file(dir: &Path, file_name: &str) -> Result<PathBuf, Error> {
+    copy_to_file(&mut std::io::stdin().lock(), dir, file_name)
+}
+
+/// Copy `reader` to the `file_name` file of `dir`, `file_name` must not be a path
+fn copy_to_file<R: std::io::Read>(
+    reader: &mut R,
+    dir: &Path,
+    file_name: &str,
+) -> Result<PathBuf, Error> {
+    if Path::new(file_name).file_name() != Some(file_name.as_ref()) {
+        Err(anyhow::anyhow!("invalid file name {:?}", file_name))?;
+    }
+    let path = dir.join(file_name);
+    let mut file = std::fs::File::create(&path)?;
+    std::io::copy(reader, &mut file)?;
+    Ok(path)
+}
+
+/// A date (midnight UTC) or an RFC 3339 timestamp
+fn parse_date(date: &str) -> Result<DateTime<Utc>, Error> {
+    match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        Ok(date) => Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()),
+        Err(_) => Ok(DateTime::parse_from_rfc3339(date)?.with_timezone(&Utc)),
+    }
+}
+
+/// An exact version, else the version matching the selector
+async fn resolve_version(
+    binrep: &mut Binrep<DynProgressReporter>,
+    artifact_name: &str,
+    version: &str,
+) -> Result<Version, Error> {
+    match Version::parse(version) {
+        Ok(version) => Ok(version),
+        Err(_) => {
+            binrep
+                .resolve(artifact_name, parse_version_selector(version)?)
+                .await
+        }
+    }
+}
+
+/// Component incremented by the `auto` push versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VersionBump {
+    Patch,
+    Minor,
+    Major,
+}
+
+/// The last version of the artifact with the `bump` component incremented, lower components are
+/// reset; 0.0.1, 0.1.0 or 1.0.0 for a new artifact
+async fn auto_version(
+    binrep: &mut Binrep<DynProgressReporter>,
+    artifact_name: &str,
+    bump: VersionBump,
+) -> Version {
+    // ignore errors and go on with default version
+    let last = binrep
+        .last_version(artifact_name, &VersionReq::STAR)
+        .await
+        .unwrap_or_default()
+        .unwrap_or_else(|| Version::new(0, 0, 0));
+    match bump {
+        VersionBump::Patch => Version {
+            patch: last.patch + 1,
+            ..last
+        },
+        VersionBump::Minor => Version::new(last.major, last.minor + 1, 0),
+        VersionBump::Major => Version::new(last.major + 1, 0, 0),
+    }
+}
+
+fn print_list<T: Display, I: IntoIterator<Item = T>>(
+    collection: I,
+    output: OutputFormat,
+) -> Result<(), Error> {
+    match output {
+        OutputFormat::Text => {
+            for item in collection {
+                println!("{}", item);
+            }
+        }
+        // an array of strings, versions included
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string(
+                &collection
+                    .into_iter()
+                    .map(|item| item.to_string())
+                    .collect::<Vec<_>>()
+            )?
+        ),
     }
+    Ok(())
+}
+
+/// Maximum number of characters of the release notes sent to slack
+const RELEASE_NOTES_MAX_CHARS: usize = 2000;
+/// Number of lines of the attached file sent to slack when nothing is specified
+const DEFAULT_ATTACH_LINES: usize = 20;
+
+fn release_notes_snippet(path: &Path) -> Result<String, Error> {
+    let notes = std::fs::read_to_string(path)?;
+    let notes = notes.trim();
+    Ok(match notes.char_indices().nth(RELEASE_NOTES_MAX_CHARS) {
+        None => notes.to_string(),
+        Some((idx, _)) => format!("{}\n[...]", &notes[..idx]),
+    })
+}
+
+fn log_excerpt(path: &Path, head: Option<usize>, tail: Option<usize>) -> Result<String, Error> {
+    let content = std::fs::read_to_string(path)?;
+    let lines: Vec<&str> = content.lines().collect();
+    let head = head.unwrap_or(0);
+    let tail = match (head, tail) {
+        (0, None) => DEFAULT_ATTACH_LINES,
+        (_, tail) => tail.unwrap_or(0),
+    };
+    let excerpt = if head + tail >= lines.len() {
+        lines.join("\n")
+    } else {
+        let mut excerpt: Vec<&str> = lines[..head].to_vec();
+        excerpt.push("[...]");
+        excerpt.extend_from_slice(&lines[lines.len() - tail..]);
+        excerpt.join("\n")
+    };
+    Ok(format!(
+        "{}:\n```\n{}\n```",
+        path.file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_else(|| path.to_string_lossy()),
+        excerpt
+    ))
 }
 
 async fn send_slack_push_notif(
     slack: &WebhookConfig,
     artifact_name: &str,
     artifact: &Artifact,
+    release_notes: Option<&str>,
+    log_excerpt: Option<&str>,
 ) -> Result<bool, anyhow::Error> {
     slack
         .send(|| {
@@ -239,15 +1159,31 @@ async fn send_slack_push_notif(
                 if artifact.files.len() > 1 { "s" } else { "" },
                 files
             );
+            let mut attachments = vec![AttachmentBuilder::new(files_text.clone())
+                .text(files_text)
+                .color("good")
+                .build()?];
+            if let Some(release_notes) = release_notes {
+                attachments.push(
+                    AttachmentBuilder::new(release_notes)
+                        .title("Release notes")
+                        .text(release_notes)
+                        .build()?,
+                );
+            }
+            if let Some(log_excerpt) = log_excerpt {
+                attachments.push(
+                    AttachmentBuilder::new(log_excerpt)
+                        .text(log_excerpt)
+                        .build()?,
+                );
+            }
             Ok(PayloadBuilder::new()
                 .text(format!(
                     "Pushed version *{}* of *{}* to artifact repository.",
                     artifact.version, artifact_name
                 ))
-                .attachments(vec![AttachmentBuilder::new(files_text.clone())
-                    .text(files_text)
-                    .color("good")
-                    .build()?]))
+                .attachments(attachments))
         })
         .await
 }
@@ -259,3 +1195,30 @@ pub fn generate_ed25519_key_pair() -> Result<(Vec<u8>, Vec<u8>), ring::error::Un
     let public_key = key_pair.public_key().as_ref().to_vec();
     Ok((pkcs8_bytes.as_ref().to_vec(), public_key))
 }
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn copy_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = super::copy_to_file(&mut &b"content"[..], dir.path(), "data.bin").unwrap();
+        assert_eq!(dir.path().join("data.bin"), path);
+        assert_eq!(b"content".to_vec(), std::fs::read(&path).unwrap());
+
+        for name in &[
+            "",
+            ".",
+            "..",
+            "../data.bin",
+            "sub/data.bin",
+            "/tmp/data.bin",
+        ] {
+            assert!(
+                super::copy_to_file(&mut &b"content"[..], dir.path(), name).is_err(),
+                "{}",
+                name
+            );
+        }
+        assert_eq!(1, std::fs::read_dir(dir.path()).unwrap().count());
+    }
+}